@@ -0,0 +1,82 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use qentities::parse::{QEntitiesParseOptions, QEntitiesVisitor};
+use std::fmt::Write;
+
+/// Generates a q-entities file in which nearly every key and value is distinct.
+fn low_repetition_src(entities: usize) -> Vec<u8> {
+    let mut src = String::new();
+    for entity in 0..entities {
+        writeln!(src, "{{").unwrap();
+        writeln!(src, "\"classname\" \"entity_{entity}\"").unwrap();
+        writeln!(src, "\"targetname\" \"target_{entity}\"").unwrap();
+        writeln!(src, "\"origin\" \"{entity} {} {}\"", entity * 2, entity * 3).unwrap();
+        writeln!(src, "\"key_{entity}\" \"value_{entity}\"").unwrap();
+        writeln!(src, "}}").unwrap();
+    }
+    src.into_bytes()
+}
+
+fn interning(c: &mut Criterion) {
+    let src = low_repetition_src(4096);
+
+    // The number of distinct keys and values of the source, which pre-sizes the byte-chunks and,
+    // when interning, the interning table.
+    let chunk_count = QEntitiesParseOptions::new()
+        .parse(&src[..])
+        .unwrap()
+        .chunk_count();
+
+    let mut group = c.benchmark_group("interning");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    for intern in [true, false] {
+        let parse_opts = QEntitiesParseOptions::new().with_intern(intern);
+        group.bench_with_input(BenchmarkId::from_parameter(intern), &src, |b, src| {
+            b.iter(|| parse_opts.parse(&src[..]).unwrap())
+        });
+
+        let parse_opts = parse_opts.with_expected_chunk_count(chunk_count);
+        group.bench_with_input(BenchmarkId::new("presized", intern), &src, |b, src| {
+            b.iter(|| parse_opts.parse(&src[..]).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// A visitor that does nothing with what it visits.
+struct NullVisitor;
+
+impl QEntitiesVisitor for NullVisitor {
+    fn key_value(&mut self, _key: &[u8], _value: &[u8]) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn small_inputs(c: &mut Criterion) {
+    let srcs: Vec<Vec<u8>> = (0..256).map(|_| low_repetition_src(1)).collect();
+    let parse_opts = QEntitiesParseOptions::new();
+
+    // Each input is parsed on its own with the same options, such that the per-parse overhead of
+    // the parser dominates.
+    let mut group = c.benchmark_group("small_inputs");
+    group.throughput(Throughput::Elements(srcs.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            for src in &srcs {
+                parse_opts.parse(&src[..]).unwrap();
+            }
+        })
+    });
+    group.bench_function("parse_visitor", |b| {
+        b.iter(|| {
+            for src in &srcs {
+                parse_opts
+                    .parse_visitor(&src[..], &mut NullVisitor)
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, interning, small_inputs);
+criterion_main!(benches);
@@ -0,0 +1,168 @@
+//! Module containing the implementation for an arena in which the keys and values of multiple
+//! [`QEntities`] collections are stored.
+
+use super::byte_chunk::ByteChunksBuilder;
+#[cfg(doc)]
+use super::QEntities;
+use hashbrown::hash_map::DefaultHashBuilder;
+
+/// Arena in which the keys and values of multiple [`QEntities`] collections are stored, such that
+/// each distinct key or value is stored only once across all of them.
+///
+/// Collections are built within an arena by parsing with
+/// [`QEntitiesParseOptions::parse_in_arena()`]. This is worthwhile when parsing many related files
+/// whose keys and values are largely the same, as is the case for the entities of the maps of a
+/// single title.
+///
+/// The bytes of the keys and values are stored within reference counted segments that are shared
+/// between the arena and the collections built within it. A collection therefore does not borrow
+/// the arena and may outlive it, and the arena may continue to be used while collections built
+/// within it are alive. The bytes of the arena are freed once the arena and every collection built
+/// within it have been dropped. Collections built within an arena behave identically to any other
+/// collection, though each holds a reference to every segment of the arena that existed when it
+/// was built.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::arena::QEntitiesArena;
+/// use qentities::parse::QEntitiesParseOptions;
+///
+/// let mut arena = QEntitiesArena::new();
+/// let parse_opts = QEntitiesParseOptions::new();
+///
+/// let a = parse_opts
+///     .parse_in_arena(&b"{ classname light origin \"0 0 0\" }"[..], &mut arena)
+///     .unwrap();
+/// let b = parse_opts
+///     .parse_in_arena(&b"{ classname light origin \"0 0 8\" }"[..], &mut arena)
+///     .unwrap();
+/// drop(arena);
+///
+/// assert_eq!(a.get(0).unwrap().value_of(b"origin"), Some(&b"0 0 0"[..]));
+/// assert_eq!(b.get(0).unwrap().value_of(b"origin"), Some(&b"0 0 8"[..]));
+/// ```
+///
+/// [`QEntitiesParseOptions::parse_in_arena()`]: crate::parse::QEntitiesParseOptions::parse_in_arena
+#[derive(Debug, Default)]
+pub struct QEntitiesArena {
+    /// Builder acting as the shared storage of the byte-chunks of the keys and values.
+    pub(crate) byte_chunks: ByteChunksBuilder<DefaultHashBuilder>,
+}
+
+impl QEntitiesArena {
+    /// Creates a new empty arena.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of distinct keys and values stored within the arena.
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.byte_chunks.len()
+    }
+
+    /// Gets the number of bytes used to store the keys and values within the arena.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::arena::QEntitiesArena;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let mut arena = QEntitiesArena::new();
+    /// for src in [&b"{ classname light }"[..], b"{ classname light }"] {
+    ///     QEntitiesParseOptions::new()
+    ///         .parse_in_arena(src, &mut arena)
+    ///         .unwrap();
+    /// }
+    /// assert_eq!(arena.chunk_count(), 2);
+    /// assert_eq!(arena.byte_footprint(), b"classname".len() + b"light".len());
+    /// ```
+    #[inline]
+    pub fn byte_footprint(&self) -> usize {
+        self.byte_chunks.bytes_len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+
+    #[test]
+    fn arena_sharing() {
+        let srcs = [
+            &b"{ classname worldspawn wad a.wad }{ classname light origin \"0 0 0\" }"[..],
+            b"{ classname worldspawn wad b.wad }{ classname light origin \"0 0 0\" }",
+            b"{ classname light light 300 }{ }{ \"\" \"\" }",
+        ];
+        let parse_opts = QEntitiesParseOptions::new();
+
+        let mut arena = QEntitiesArena::new();
+        let collections = srcs
+            .iter()
+            .map(|src| parse_opts.parse_in_arena(*src, &mut arena).unwrap())
+            .collect::<Vec<_>>();
+
+        // Keys and values shared between collections are stored once within the arena.
+        let owned = srcs
+            .iter()
+            .map(|src| parse_opts.parse(*src).unwrap())
+            .collect::<Vec<_>>();
+        let distinct = "classnameworldspawnwada.wadlightorigin0 0 0b.wad300";
+        assert_eq!(arena.byte_footprint(), distinct.len());
+        assert_eq!(arena.chunk_count(), 10);
+        assert!(arena.byte_footprint() < owned.iter().map(|e| e.byte_footprint()).sum::<usize>());
+
+        // Collections behave identically to those that own their bytes.
+        for (in_arena, owned) in collections.iter().zip(&owned) {
+            assert!(in_arena.iter().eq(owned.iter()));
+            assert_eq!(in_arena.chunk_count(), owned.chunk_count());
+            assert_eq!(in_arena.byte_footprint(), owned.byte_footprint());
+            assert_eq!(in_arena.to_string(), owned.to_string());
+            assert_eq!(
+                in_arena
+                    .dedup_keys(crate::KeyDedupStrategy::FirstWins)
+                    .len(),
+                owned.len()
+            );
+
+            let mut packed = Vec::new();
+            in_arena.write_packed(&mut packed).unwrap();
+            let unpacked = crate::QEntities::from_packed(&packed).unwrap();
+            assert!(unpacked.iter().eq(owned.iter()));
+            assert_eq!(unpacked.byte_footprint(), owned.byte_footprint());
+        }
+
+        // Collections outlive the arena.
+        drop(arena);
+        assert_eq!(
+            collections[1].get(0).unwrap().value_of(b"wad"),
+            Some(&b"b.wad"[..])
+        );
+    }
+
+    #[test]
+    fn arena_errors() {
+        let parse_opts = QEntitiesParseOptions::new();
+        let mut arena = QEntitiesArena::new();
+
+        let e = parse_opts
+            .parse_in_arena(&b"{ classname light origin"[..], &mut arena)
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+
+        // Strings stored by a failed parse remain usable by later parses.
+        let entities = parse_opts
+            .parse_in_arena(&b"{ origin light }"[..], &mut arena)
+            .unwrap();
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .pairs()
+            .eq([(&b"origin"[..], &b"light"[..])]));
+        assert_eq!(arena.chunk_count(), 3);
+    }
+}
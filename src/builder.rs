@@ -0,0 +1,112 @@
+//! Module containing the implementation for building [`QEntities`] collections, whether from a
+//! parsed q-entities file or from other sources.
+
+use super::byte_chunk::{ArenaByteChunksBuilder, ByteChunksBuilder, ByteChunksSink};
+use super::{QEntities, QEntityInfo, QEntityKeyValueInfo};
+use core::hash::BuildHasher;
+use std::collections::TryReserveError;
+
+/// Builder for a [`QEntities`] collection.
+///
+/// The keys and values of the collection are stored by a [`ByteChunksSink`], which is either a
+/// [`ByteChunksBuilder`] owned by the builder or an [`ArenaByteChunksBuilder`] that stores them
+/// within a shared arena.
+pub(crate) struct QEntitiesBuilder<B> {
+    /// Infos for the entities that have been built.
+    entities: Vec<QEntityInfo>,
+    /// Infos for the key-values that have been built.
+    key_values: Vec<QEntityKeyValueInfo>,
+    /// Builder for the byte-chunks of the keys and values.
+    byte_chunks: B,
+}
+
+impl<S: BuildHasher> QEntitiesBuilder<ByteChunksBuilder<S>> {
+    /// Creates a new builder using the given hasher.
+    #[inline]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            entities: Vec::new(),
+            key_values: Vec::new(),
+            byte_chunks: ByteChunksBuilder::with_hasher(hash_builder),
+        }
+    }
+
+    /// Creates a new builder with space for at least `capacity` distinct keys and values using the
+    /// given hasher, which interns keys and values only if `intern` is enabled.
+    #[inline]
+    pub fn with_chunk_capacity_intern_and_hasher(
+        capacity: usize,
+        intern: bool,
+        hash_builder: S,
+    ) -> Self {
+        Self {
+            entities: Vec::new(),
+            key_values: Vec::new(),
+            byte_chunks: ByteChunksBuilder::with_chunk_capacity_intern_and_hasher(
+                capacity,
+                intern,
+                hash_builder,
+            ),
+        }
+    }
+}
+
+impl<'a, S: BuildHasher> QEntitiesBuilder<ArenaByteChunksBuilder<'a, S>> {
+    /// Creates a new builder that stores keys and values within the given arena.
+    #[inline]
+    pub fn in_arena(arena: &'a mut ByteChunksBuilder<S>) -> Self {
+        Self {
+            entities: Vec::new(),
+            key_values: Vec::new(),
+            byte_chunks: ArenaByteChunksBuilder::new(arena),
+        }
+    }
+}
+
+impl<B: ByteChunksSink> QEntitiesBuilder<B> {
+    /// Begins a new entity with no key-values.
+    #[inline]
+    pub fn push_entity(&mut self) {
+        self.entities.push(QEntityInfo {
+            first_kv: self.key_values.len(),
+            kvs_length: 0,
+        });
+    }
+
+    /// Pushes a key-value to the most recently begun entity.
+    ///
+    /// An error is returned if the bytes of the key or value cannot be stored because the total
+    /// number of bytes would exceed the maximum size of an allocation, or because allocation
+    /// failed. The key-value is not pushed in that case, though its key may have been stored.
+    ///
+    /// # Panics
+    /// This function panics if no entity has been begun.
+    #[inline]
+    pub fn push_key_value(&mut self, key: &[u8], value: &[u8]) -> Result<(), TryReserveError> {
+        let entity = self
+            .entities
+            .last_mut()
+            .expect("key-values must be pushed to an entity");
+        let key_chunk = self.byte_chunks.chunk(key)?;
+        let value_chunk = self.byte_chunks.chunk(value)?;
+        self.key_values.push(QEntityKeyValueInfo {
+            key_chunk,
+            value_chunk,
+        });
+        entity.kvs_length += 1;
+        Ok(())
+    }
+
+    /// Consume `self` and construct a new [`QEntities`] collection.
+    #[inline]
+    pub fn finish(self) -> QEntities {
+        QEntities {
+            entities: self.entities.into(),
+            key_values: self.key_values.into(),
+            byte_chunks: self.byte_chunks.finish(),
+            line_index: None,
+            comments: None,
+            parse_options: None,
+        }
+    }
+}
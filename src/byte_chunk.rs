@@ -6,22 +6,55 @@
 //! The term _byte-chunks_ is used here to describe a collection of byte-sequences that co-exist
 //! within a single allocation whose memory footprint is optimized by de-duplicating byte-sequences
 //! that are already within the collection.
+//!
+//! The bytes of a collection are stored within one or more _segments_. A collection that owns its
+//! bytes has a single segment unless it has been edited, while the segments of a collection built
+//! within an arena are shared with the arena and with every other collection built within it.
 
 use core::hash::{BuildHasher, Hasher};
 use core::{fmt, ops};
-use hashbrown::hash_map::{HashMap, RawEntryMut};
+use hashbrown::hash_map::{DefaultHashBuilder, Entry, HashMap, RawEntryMut};
+use hashbrown::HashSet;
+use std::collections::TryReserveError;
+use std::io;
+use std::sync::Arc;
 
 /// Information describing a chunk of bytes within a [`ByteChunks`] collection.
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
 struct ByteChunkInfo {
-    /// Offset to the first byte of the chunk.
+    /// Index of the segment in which the chunk resides.
+    segment: usize,
+    /// Offset to the first byte of the chunk within its segment.
     offset: usize,
     /// The length of the byte-chunk.
     length: usize,
 }
 
 impl ByteChunkInfo {
+    /// Reserves space for a new byte-chunk of the given length at the end of the given buffers,
+    /// returning the information that describes where it will reside once the bytes become the
+    /// given segment.
+    ///
+    /// Reserving fails rather than overflowing if the total number of bytes would exceed the
+    /// maximum size of an allocation, such that the offset and length of every byte-chunk always
+    /// sum to an in-bounds position.
+    #[inline]
+    fn reserve(
+        bytes: &mut Vec<u8>,
+        chunks: &mut Vec<ByteChunkInfo>,
+        segment: usize,
+        length: usize,
+    ) -> Result<Self, TryReserveError> {
+        bytes.try_reserve(length)?;
+        chunks.try_reserve(1)?;
+        Ok(Self {
+            segment,
+            offset: bytes.len(),
+            length,
+        })
+    }
+
     /// Uses the description provided by `self` to create a reference to a sub-slice of bytes within
     /// the provided slice of bytes.
     ///
@@ -30,15 +63,56 @@ impl ByteChunkInfo {
     /// slice of bytes.
     #[inline]
     fn slice_from<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
-        let start = self.offset;
-        let end = start + self.length;
-        &bytes[start..end]
+        // Slicing twice avoids computing an end offset that could overflow.
+        &bytes[self.offset..][..self.length]
+    }
+
+    /// Uses the description provided by `self` to create a reference to a sub-slice of bytes within
+    /// either one of the provided segments or, for the segment that follows them, the provided
+    /// unfrozen bytes.
+    ///
+    /// # Panics
+    /// This function panics if `self` describes a sub-slice of bytes out of bounds for the segment
+    /// or for the unfrozen bytes.
+    #[inline]
+    fn slice_in<'a>(&self, segments: &'a [ByteSegment], unfrozen: &'a [u8]) -> &'a [u8] {
+        match segments.get(self.segment) {
+            Some(segment) => self.slice_from(segment),
+            None => self.slice_from(unfrozen),
+        }
+    }
+}
+
+/// A segment of bytes holding the bytes of byte-chunks within a [`ByteChunks`] collection.
+#[derive(Clone)]
+#[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
+enum ByteSegment {
+    /// Bytes that are owned by the collection.
+    Owned(Box<[u8]>),
+    /// Bytes that are shared with an arena.
+    Shared(Arc<[u8]>),
+}
+
+impl ops::Deref for ByteSegment {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Owned(bytes) => bytes,
+            Self::Shared(bytes) => bytes,
+        }
     }
 }
 
 /// Builder for a [`ByteChunks`] collection.
 pub(crate) struct ByteChunksBuilder<S> {
-    /// Buffer holding the full collection of bytes.
+    /// Segments that precede the bytes of the builder, which are either the segments of the
+    /// collection that the builder was created from or were frozen with
+    /// [`freeze()`](Self::freeze).
+    segments: Vec<ByteSegment>,
+    /// Buffer holding the bytes that have not yet been frozen, which become the segment following
+    /// the frozen segments.
     bytes: Vec<u8>,
     /// Buffer holding the information for the individual chunks.
     chunks: Vec<ByteChunkInfo>,
@@ -46,6 +120,10 @@ pub(crate) struct ByteChunksBuilder<S> {
     hash_builder: S,
     /// Hash map for mapping byte-sequences to the indices of byte-chunks.
     hashes: HashMap<usize, (), ()>,
+    /// Whether or not byte-sequences are de-duplicated.
+    intern: bool,
+    /// Whether or not every inserted byte-chunk is known to be distinct.
+    interned: bool,
 }
 
 impl<S: fmt::Debug> fmt::Debug for ByteChunksBuilder<S> {
@@ -53,7 +131,9 @@ impl<S: fmt::Debug> fmt::Debug for ByteChunksBuilder<S> {
         f.debug_struct("ByteChunksBuilder")
             .field(
                 "byte_chunks",
-                &ByteChunksDebugger::new(&self.bytes, &self.chunks),
+                &ByteChunksDebugger::new(&self.chunks, |chunk| {
+                    chunk.slice_in(&self.segments, &self.bytes)
+                }),
             )
             .field("hash_builder", &self.hash_builder)
             .finish()
@@ -64,20 +144,120 @@ impl<S> ByteChunksBuilder<S> {
     /// Creates a new builder using the given hasher.
     #[inline]
     pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_chunk_capacity_intern_and_hasher(0, true, hash_builder)
+    }
+
+    /// Creates a new builder with space for at least `capacity` byte-chunks using the given
+    /// hasher, which de-duplicates byte-sequences only if `intern` is enabled.
+    ///
+    /// When interning is disabled, every call to [`chunk()`](Self::chunk) inserts a new byte-chunk
+    /// without hashing the byte-sequence, and the interning table is never allocated.
+    ///
+    /// The capacity is only a hint, and is ignored if space for the byte-chunks cannot be reserved.
+    /// Otherwise, the interning table is sized for it infallibly.
+    #[inline]
+    pub fn with_chunk_capacity_intern_and_hasher(
+        capacity: usize,
+        intern: bool,
+        hash_builder: S,
+    ) -> Self {
+        // A byte-chunk is larger than a slot of the interning table, so a capacity that the
+        // byte-chunks can be reserved for never overflows the layout of the table. Allocating the
+        // table can still fail, in which case the allocation error handler is called.
+        let mut chunks = Vec::new();
+        let hashes = match chunks.try_reserve(capacity) {
+            Ok(()) if intern => HashMap::with_capacity_and_hasher(capacity, ()),
+            _ => HashMap::with_hasher(()),
+        };
         Self {
+            segments: Vec::new(),
             bytes: Vec::new(),
-            chunks: Vec::new(),
+            chunks,
             hash_builder,
-            hashes: HashMap::with_hasher(()),
+            hashes,
+            intern,
+            interned: true,
         }
     }
 
+    /// Creates a new builder that continues building an existing collection using the given
+    /// hasher.
+    ///
+    /// The byte-chunks of the collection keep their indices, and new byte-chunks are stored within
+    /// a new segment. Byte-sequences are de-duplicated against the existing byte-chunks only if
+    /// the collection is interned.
+    pub fn from_byte_chunks(byte_chunks: ByteChunks, hash_builder: S) -> Self
+    where
+        S: BuildHasher,
+    {
+        let mut builder = Self::with_hasher(hash_builder);
+        builder.segments = byte_chunks.segments.into_vec();
+        builder.chunks = byte_chunks.chunks.into_vec();
+        builder.intern = byte_chunks.interned;
+        builder.interned = byte_chunks.interned;
+
+        if builder.intern {
+            let Self {
+                hash_builder,
+                hashes,
+                segments,
+                chunks,
+                bytes,
+                ..
+            } = &mut builder;
+            let hash_bytes = |bytes: &[u8]| -> u64 {
+                let mut hasher = hash_builder.build_hasher();
+                hasher.write(bytes);
+                hasher.finish()
+            };
+
+            // The byte-chunks of an interned collection are already distinct.
+            for (chunk_index, chunk) in chunks.iter().enumerate() {
+                let bytes_hash = hash_bytes(chunk.slice_in(segments, bytes));
+                if let RawEntryMut::Vacant(vacant) =
+                    hashes.raw_entry_mut().from_hash(bytes_hash, |_| false)
+                {
+                    vacant.insert_with_hasher(bytes_hash, chunk_index, (), |chunk_index| {
+                        hash_bytes(chunks[*chunk_index].slice_in(segments, bytes))
+                    });
+                }
+            }
+        }
+
+        builder
+    }
+
+    /// Inserts a new byte-chunk without checking for an existing associated byte-chunk.
+    #[inline]
+    fn push(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError> {
+        let new_chunk_info = ByteChunkInfo::reserve(
+            &mut self.bytes,
+            &mut self.chunks,
+            self.segments.len(),
+            bytes.len(),
+        )?;
+        self.interned = false;
+        let new_chunk_index = self.chunks.len();
+        self.chunks.push(new_chunk_info);
+        self.bytes.extend_from_slice(bytes);
+        Ok(new_chunk_index)
+    }
+
     /// Gets the index of the associated byte-chunk present in the builder. If there exists no
     /// associated byte-chunk, then a new one is inserted.
-    pub fn chunk(&mut self, bytes: &[u8]) -> usize
+    ///
+    /// If interning is disabled, then a new byte-chunk is always inserted.
+    ///
+    /// An error is returned if a new byte-chunk cannot be inserted because the total number of
+    /// bytes would exceed the maximum size of an allocation, or because allocation failed.
+    pub fn chunk(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError>
     where
         S: BuildHasher,
     {
+        if !self.intern {
+            return self.push(bytes);
+        }
+
         let hash_bytes = |bytes: &[u8]| -> u64 {
             let mut hasher = self.hash_builder.build_hasher();
             hasher.write(bytes);
@@ -89,33 +269,164 @@ impl<S> ByteChunksBuilder<S> {
             .hashes
             .raw_entry_mut()
             .from_hash(bytes_hash, |existing_index| {
-                self.chunks[*existing_index].slice_from(&self.bytes) == bytes
+                self.chunks[*existing_index].slice_in(&self.segments, &self.bytes) == bytes
             }) {
-            RawEntryMut::Occupied(occupied) => *occupied.key(),
+            RawEntryMut::Occupied(occupied) => Ok(*occupied.key()),
             RawEntryMut::Vacant(vacant) => {
-                let new_chunk_info = ByteChunkInfo {
-                    offset: self.bytes.len(),
-                    length: bytes.len(),
-                };
+                let new_chunk_info = ByteChunkInfo::reserve(
+                    &mut self.bytes,
+                    &mut self.chunks,
+                    self.segments.len(),
+                    bytes.len(),
+                )?;
                 let new_chunk_index = self.chunks.len();
                 self.bytes.extend_from_slice(bytes);
                 self.chunks.push(new_chunk_info);
 
                 vacant.insert_with_hasher(bytes_hash, new_chunk_index, (), |chunk_index| {
-                    hash_bytes(self.chunks[*chunk_index].slice_from(&self.bytes))
+                    hash_bytes(self.chunks[*chunk_index].slice_in(&self.segments, &self.bytes))
                 });
 
-                new_chunk_index
+                Ok(new_chunk_index)
             }
         }
     }
 
+    /// Freezes the bytes that have not yet been frozen into a new segment, such that they can be
+    /// shared with collections built within the builder.
+    ///
+    /// A new segment is always pushed, even if it is empty, so that every byte-chunk inserted
+    /// before freezing resides within a frozen segment.
+    pub fn freeze(&mut self) {
+        let bytes = core::mem::take(&mut self.bytes);
+        self.segments.push(ByteSegment::Shared(bytes.into()));
+    }
+
+    /// Gets the number of byte-chunks within the builder.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Gets the total number of bytes backing the byte-chunks within the builder.
+    #[inline]
+    pub fn bytes_len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| segment.len())
+            .sum::<usize>()
+            + self.bytes.len()
+    }
+
     /// Consume `self` and construct a new [`ByteChunks`] collection.
+    ///
+    /// The bytes of the builder become the last segment of the collection, following any segments
+    /// that preceded them.
+    #[inline]
+    pub fn finish(self) -> ByteChunks {
+        let mut segments = self.segments;
+        segments.push(ByteSegment::Owned(self.bytes.into()));
+        ByteChunks {
+            segments: segments.into(),
+            chunks: self.chunks.into(),
+            interned: self.interned,
+        }
+    }
+}
+
+/// Builder of a [`ByteChunks`] collection into which byte-sequences are inserted.
+pub(crate) trait ByteChunksSink {
+    /// Gets the index of the byte-chunk associated with the given byte-sequence, inserting a new
+    /// byte-chunk if needed.
+    ///
+    /// An error is returned if a new byte-chunk cannot be inserted because the total number of
+    /// bytes would exceed the maximum size of an allocation, or because allocation failed.
+    fn chunk(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError>;
+
+    /// Consume `self` and construct a new [`ByteChunks`] collection.
+    fn finish(self) -> ByteChunks;
+}
+
+impl<S: BuildHasher> ByteChunksSink for ByteChunksBuilder<S> {
+    #[inline(always)]
+    fn chunk(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError> {
+        ByteChunksBuilder::chunk(self, bytes)
+    }
+
+    #[inline(always)]
+    fn finish(self) -> ByteChunks {
+        ByteChunksBuilder::finish(self)
+    }
+}
+
+impl<S: BuildHasher> ByteChunksSink for ArenaByteChunksBuilder<'_, S> {
+    #[inline(always)]
+    fn chunk(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError> {
+        ArenaByteChunksBuilder::chunk(self, bytes)
+    }
+
+    #[inline(always)]
+    fn finish(self) -> ByteChunks {
+        ArenaByteChunksBuilder::finish(self)
+    }
+}
+
+/// Builder for a [`ByteChunks`] collection whose bytes are stored within the shared segments of
+/// another [`ByteChunksBuilder`] acting as an arena.
+///
+/// Every distinct byte-sequence is stored once within the arena regardless of how many collections
+/// are built within it, while each collection only holds the information for the byte-chunks that
+/// it uses.
+pub(crate) struct ArenaByteChunksBuilder<'a, S> {
+    /// The arena in which bytes are stored.
+    arena: &'a mut ByteChunksBuilder<S>,
+    /// Buffer holding the information for the individual chunks of the collection.
+    chunks: Vec<ByteChunkInfo>,
+    /// Hash map for mapping the indices of byte-chunks within the arena to those within the
+    /// collection.
+    arena_indices: HashMap<usize, usize, DefaultHashBuilder>,
+}
+
+impl<'a, S> ArenaByteChunksBuilder<'a, S> {
+    /// Creates a new builder that stores its bytes within the given arena.
     #[inline]
+    pub fn new(arena: &'a mut ByteChunksBuilder<S>) -> Self {
+        Self {
+            arena,
+            chunks: Vec::new(),
+            arena_indices: HashMap::default(),
+        }
+    }
+
+    /// Gets the index of the byte-chunk within the collection that is associated with the given
+    /// byte-sequence, inserting the byte-sequence into the arena if it is not already present.
+    ///
+    /// Byte-sequences are always interned within the arena.
+    pub fn chunk(&mut self, bytes: &[u8]) -> Result<usize, TryReserveError>
+    where
+        S: BuildHasher,
+    {
+        let arena_index = self.arena.chunk(bytes)?;
+        match self.arena_indices.entry(arena_index) {
+            Entry::Occupied(occupied) => Ok(*occupied.get()),
+            Entry::Vacant(vacant) => {
+                self.chunks.try_reserve(1)?;
+                let new_chunk_index = self.chunks.len();
+                self.chunks.push(self.arena.chunks[arena_index]);
+                vacant.insert(new_chunk_index);
+                Ok(new_chunk_index)
+            }
+        }
+    }
+
+    /// Consume `self` and construct a new [`ByteChunks`] collection that shares the segments of the
+    /// arena, freezing any bytes of the arena that have not yet been frozen.
     pub fn finish(self) -> ByteChunks {
+        self.arena.freeze();
         ByteChunks {
-            bytes: self.bytes.into(),
+            segments: self.arena.segments.clone().into(),
             chunks: self.chunks.into(),
+            interned: true,
         }
     }
 }
@@ -124,10 +435,13 @@ impl<S: Default> Default for ByteChunksBuilder<S> {
     #[inline]
     fn default() -> Self {
         Self {
+            segments: Vec::new(),
             bytes: Vec::new(),
             chunks: Vec::new(),
             hash_builder: Default::default(),
             hashes: HashMap::with_hasher(()),
+            intern: true,
+            interned: true,
         }
     }
 }
@@ -135,15 +449,167 @@ impl<S: Default> Default for ByteChunksBuilder<S> {
 /// Collection of byte-chunks.
 #[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
 pub(crate) struct ByteChunks {
-    /// The full collection of bytes.
-    bytes: Box<[u8]>,
+    /// The segments holding the bytes of the byte-chunks.
+    segments: Box<[ByteSegment]>,
     /// The individual chunk infos.
     chunks: Box<[ByteChunkInfo]>,
+    /// Whether or not every byte-chunk is distinct, such that byte-chunks are equal exactly when
+    /// their indices are equal.
+    interned: bool,
+}
+
+impl ByteChunks {
+    /// Gets the number of byte-chunks within the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Gets the total number of bytes backing the byte-chunks within the collection.
+    ///
+    /// For collections with multiple segments or whose segments are shared with an arena, only the
+    /// bytes of the byte-chunks within the collection are counted.
+    #[inline]
+    pub fn bytes_len(&self) -> usize {
+        match &*self.segments {
+            [ByteSegment::Owned(bytes)] => bytes.len(),
+            _ => self.chunks.iter().map(|chunk| chunk.length).sum(),
+        }
+    }
+
+    /// Gets the number of bytes of the heap allocations backing the collection, which includes the
+    /// bytes of every segment along with the information describing each segment and byte-chunk.
+    ///
+    /// Segments that are shared with an arena are counted in full.
+    pub fn heap_bytes(&self) -> usize {
+        core::mem::size_of_val(&*self.segments)
+            + self
+                .segments
+                .iter()
+                .map(|segment| segment.len())
+                .sum::<usize>()
+            + core::mem::size_of_val(&*self.chunks)
+    }
+
+    /// Checks whether or not every byte-chunk is distinct, such that byte-chunks are equal exactly
+    /// when their indices are equal.
+    #[inline]
+    pub fn is_interned(&self) -> bool {
+        self.interned
+    }
+
+    /// Writes the collection using the packed layout described by [`crate::packed`].
+    ///
+    /// The bytes of a collection with a single segment are written as they are, while the
+    /// byte-chunks of a collection with any other number of segments are written one after another.
+    pub fn write_packed<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&(self.chunks.len() as u64).to_le_bytes())?;
+        if let [segment] = &*self.segments {
+            for chunk in self.chunks.iter() {
+                w.write_all(&(chunk.offset as u64).to_le_bytes())?;
+                w.write_all(&(chunk.length as u64).to_le_bytes())?;
+            }
+            w.write_all(&(segment.len() as u64).to_le_bytes())?;
+            w.write_all(segment)
+        } else {
+            let mut offset = 0;
+            for chunk in self.chunks.iter() {
+                w.write_all(&(offset as u64).to_le_bytes())?;
+                w.write_all(&(chunk.length as u64).to_le_bytes())?;
+                offset += chunk.length;
+            }
+            w.write_all(&(offset as u64).to_le_bytes())?;
+            for index in 0..self.chunks.len() {
+                w.write_all(&self[index])?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Reads a collection using the packed layout described by [`crate::packed`] from the front of
+    /// a packed reader.
+    ///
+    /// Returns [`None`] if the packed bytes do not describe a valid collection. The byte-chunks of
+    /// the read collection are not assumed to be distinct.
+    pub fn read_packed(packed: &mut PackedReader) -> Option<Self> {
+        let chunks_len = packed.read_len()?;
+        let mut chunks = Vec::with_capacity(chunks_len.min(packed.remaining() / 16));
+        for _ in 0..chunks_len {
+            chunks.push(ByteChunkInfo {
+                segment: 0,
+                offset: packed.read_len()?,
+                length: packed.read_len()?,
+            });
+        }
+
+        let bytes_len = packed.read_len()?;
+        let bytes = packed.read_bytes(bytes_len)?;
+
+        // Validate that every chunk is in bounds so that indexing can never panic.
+        chunks
+            .iter()
+            .all(|chunk| {
+                chunk
+                    .offset
+                    .checked_add(chunk.length)
+                    .is_some_and(|end| end <= bytes.len())
+            })
+            .then(|| Self {
+                segments: Box::new([ByteSegment::Owned(bytes.into())]),
+                chunks: chunks.into(),
+                interned: false,
+            })
+    }
+}
+
+/// Cursor over bytes in the packed layout described by [`crate::packed`].
+pub(crate) struct PackedReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PackedReader<'a> {
+    /// Creates a new reader over some packed bytes.
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Gets the number of bytes that have not yet been read.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Reads a raw little-endian `u64`.
+    pub fn read_u64(&mut self) -> Option<u64> {
+        let (head, tail) = self.bytes.split_first_chunk::<8>()?;
+        self.bytes = tail;
+        Some(u64::from_le_bytes(*head))
+    }
+
+    /// Reads a little-endian `u64` that must be representable as a `usize`.
+    #[inline]
+    pub fn read_len(&mut self) -> Option<usize> {
+        self.read_u64()
+            .and_then(|value| usize::try_from(value).ok())
+    }
+
+    /// Reads a slice of bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        (len <= self.bytes.len()).then(|| {
+            let (head, tail) = self.bytes.split_at(len);
+            self.bytes = tail;
+            head
+        })
+    }
 }
 
 impl fmt::Debug for ByteChunks {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        ByteChunksDebugger::new(&self.bytes, &self.chunks).fmt(f)
+        ByteChunksDebugger::new(&self.chunks, |chunk| {
+            chunk.slice_from(&self.segments[chunk.segment])
+        })
+        .fmt(f)
     }
 }
 
@@ -164,24 +630,66 @@ impl ops::Index<usize> for ByteChunks {
     /// This function panics if the index does not correspond to any byte-chunk in `self`.
     #[inline]
     fn index(&self, index: usize) -> &Self::Output {
-        self.chunks[index].slice_from(&self.bytes)
+        let chunk = &self.chunks[index];
+        chunk.slice_from(&self.segments[chunk.segment])
+    }
+}
+
+/// Set of byte-chunks within a [`ByteChunks`] collection.
+///
+/// Byte-chunks are compared by index when the collection is interned, and by their bytes otherwise.
+pub(crate) enum ByteChunkSet<'a> {
+    /// Set of byte-chunk indices.
+    Indices(HashSet<usize>),
+    /// Set of byte-sequences.
+    Bytes(HashSet<&'a [u8]>),
+}
+
+impl<'a> ByteChunkSet<'a> {
+    /// Creates a new empty set suitable for the byte-chunks of the given collection.
+    #[inline]
+    pub fn new(byte_chunks: &ByteChunks) -> Self {
+        if byte_chunks.is_interned() {
+            Self::Indices(HashSet::new())
+        } else {
+            Self::Bytes(HashSet::new())
+        }
+    }
+
+    /// Inserts a byte-chunk of the given collection into the set, returning whether or not an equal
+    /// byte-chunk was absent.
+    #[inline]
+    pub fn insert(&mut self, byte_chunks: &'a ByteChunks, index: usize) -> bool {
+        match self {
+            Self::Indices(indices) => indices.insert(index),
+            Self::Bytes(bytes) => bytes.insert(&byte_chunks[index]),
+        }
+    }
+
+    /// Removes every byte-chunk from the set.
+    #[inline]
+    pub fn clear(&mut self) {
+        match self {
+            Self::Indices(indices) => indices.clear(),
+            Self::Bytes(bytes) => bytes.clear(),
+        }
     }
 }
 
 /// Type implementing [`fmt::Debug`] with the purpose of debugging a [`ByteChunksBuilder`] or a
 /// [`ByteChunks`] collection.
-struct ByteChunksDebugger<'a> {
-    bytes: &'a [u8],
+struct ByteChunksDebugger<'a, F> {
     chunks: &'a [ByteChunkInfo],
+    slice: F,
 }
 
-impl<'a> ByteChunksDebugger<'a> {
-    fn new(bytes: &'a [u8], chunks: &'a [ByteChunkInfo]) -> Self {
-        Self { bytes, chunks }
+impl<'a, F: Fn(&ByteChunkInfo) -> &'a [u8]> ByteChunksDebugger<'a, F> {
+    fn new(chunks: &'a [ByteChunkInfo], slice: F) -> Self {
+        Self { chunks, slice }
     }
 }
 
-impl fmt::Debug for ByteChunksDebugger<'_> {
+impl<'a, F: Fn(&ByteChunkInfo) -> &'a [u8]> fmt::Debug for ByteChunksDebugger<'a, F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         struct ByteChunkDebugger<'a>(&'a [u8]);
         impl fmt::Debug for ByteChunkDebugger<'_> {
@@ -195,7 +703,7 @@ impl fmt::Debug for ByteChunksDebugger<'_> {
             .entries(
                 self.chunks
                     .iter()
-                    .map(|chunk| ByteChunkDebugger(chunk.slice_from(&self.bytes))),
+                    .map(|chunk| ByteChunkDebugger((self.slice)(chunk))),
             )
             .finish()
     }
@@ -210,18 +718,22 @@ mod tests {
         let bytes = b"classnameworldspawn";
 
         let info_a = ByteChunkInfo {
+            segment: 0,
             offset: 0,
             length: 9,
         };
         let info_b = ByteChunkInfo {
+            segment: 0,
             offset: 9,
             length: 10,
         };
         let info_c = ByteChunkInfo {
+            segment: 0,
             offset: bytes.len(),
             length: 0,
         };
         let info_d = ByteChunkInfo {
+            segment: 0,
             offset: 0,
             length: bytes.len(),
         };
@@ -242,6 +754,7 @@ mod tests {
     fn byte_chunk_slicing_overflow() {
         let bytes = b"classnameworldspawn";
         ByteChunkInfo {
+            segment: 0,
             offset: 0,
             length: bytes.len() + 1,
         }
@@ -253,6 +766,7 @@ mod tests {
     fn byte_chunk_slicing_out_of_bounds() {
         let bytes = b"classnameworldspawn";
         ByteChunkInfo {
+            segment: 0,
             offset: bytes.len() + 1,
             length: 0,
         }
@@ -290,7 +804,7 @@ mod tests {
         }
 
         fn insert_into<S: BuildHasher>(&mut self, builder: &mut ByteChunksBuilder<S>) {
-            let chunk = builder.chunk(self.bytes);
+            let chunk = builder.chunk(self.bytes).unwrap();
             if let Some(expected_chunk) = self.chunk {
                 assert_eq!(expected_chunk, chunk);
             } else {
@@ -307,7 +821,7 @@ mod tests {
 
             // Assuming that the byte-chunks builder de-duplicated the bytes correctly, the total
             // length of the byte-chunks should never exceed the total length of the inputs.
-            assert!(total_length <= byte_chunks.bytes.len());
+            assert!(total_length <= byte_chunks.bytes_len());
         }
 
         fn finalize(self, byte_chunks: &ByteChunks) {
@@ -371,4 +885,56 @@ mod tests {
         test_with_hasher(hashbrown::hash_map::DefaultHashBuilder::default());
         test_with_hasher(core::hash::BuildHasherDefault::<rustc_hash::FxHasher>::default());
     }
+
+    #[test]
+    fn byte_chunk_building_without_interning() {
+        let mut builder = ByteChunksBuilder::with_chunk_capacity_intern_and_hasher(
+            64,
+            false,
+            hashbrown::hash_map::DefaultHashBuilder::default(),
+        );
+
+        // Space is reserved for the byte-chunks, but not for the unused interning table.
+        assert!(builder.chunks.capacity() >= 64);
+        assert_eq!(builder.hashes.capacity(), 0);
+
+        let classname_a = builder.chunk(b"classname").unwrap();
+        let worldspawn = builder.chunk(b"worldspawn").unwrap();
+        let classname_b = builder.chunk(b"classname").unwrap();
+        assert_ne!(classname_a, classname_b);
+
+        assert_eq!(builder.hashes.capacity(), 0);
+
+        let byte_chunks = builder.finish();
+        assert_eq!(byte_chunks.len(), 3);
+        assert_eq!(&byte_chunks[classname_a], b"classname");
+        assert_eq!(&byte_chunks[worldspawn], b"worldspawn");
+        assert_eq!(&byte_chunks[classname_b], b"classname");
+    }
+
+    #[test]
+    fn byte_chunk_building_overflow() {
+        for intern in [true, false] {
+            let mut builder = ByteChunksBuilder::with_chunk_capacity_intern_and_hasher(
+                0,
+                intern,
+                hashbrown::hash_map::DefaultHashBuilder::default(),
+            );
+            let classname = builder.chunk(b"classname").unwrap();
+
+            // A chunk whose end offset would overflow is rejected without modifying the builder.
+            for length in [usize::MAX, usize::MAX - 8, isize::MAX as usize] {
+                let reserved =
+                    ByteChunkInfo::reserve(&mut builder.bytes, &mut builder.chunks, 0, length);
+                assert!(reserved.is_err());
+            }
+
+            let worldspawn = builder.chunk(b"worldspawn").unwrap();
+            let byte_chunks = builder.finish();
+            assert_eq!(byte_chunks.len(), 2);
+            assert_eq!(byte_chunks.bytes_len(), 19);
+            assert_eq!(&byte_chunks[classname], b"classname");
+            assert_eq!(&byte_chunks[worldspawn], b"worldspawn");
+        }
+    }
 }
@@ -0,0 +1,94 @@
+//! Module containing the implementation for an iterator over the comments retained within a
+//! [`QEntities`](crate::QEntities) collection.
+
+use super::QEntitiesComments;
+use core::ops::Range;
+
+/// Iterator over the text of some comments retained within a [`QEntities`](crate::QEntities)
+/// collection.
+///
+/// See [`QEntityRef::leading_comments()`](crate::QEntityRef::leading_comments) and
+/// [`QEntities::trailing_comments()`](crate::QEntities::trailing_comments).
+pub struct QEntitiesCommentsIter<'a> {
+    /// The bytes of every comment retained within the collection.
+    bytes: &'a [u8],
+    /// The offset within the bytes at which each comment retained within the collection ends.
+    ends: &'a [usize],
+    /// The indices of the comments that remain to be yielded.
+    indices: Range<usize>,
+}
+
+impl<'a> QEntitiesCommentsIter<'a> {
+    /// Creates a new iterator over the comments within a range of indices, or an empty iterator
+    /// if the collection has no retained comments.
+    #[inline]
+    pub(super) fn new(comments: Option<&'a QEntitiesComments>, indices: Range<usize>) -> Self {
+        match comments {
+            Some(comments) => Self {
+                bytes: &comments.bytes,
+                ends: &comments.ends,
+                indices,
+            },
+            None => Self {
+                bytes: &[],
+                ends: &[],
+                indices: 0..0,
+            },
+        }
+    }
+
+    /// Gets the text of the comment at an index.
+    #[inline]
+    fn comment(&self, index: usize) -> &'a [u8] {
+        let start = index.checked_sub(1).map_or(0, |prev| self.ends[prev]);
+        &self.bytes[start..self.ends[index]]
+    }
+}
+
+impl<'a> Iterator for QEntitiesCommentsIter<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|index| self.comment(index))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+
+    #[inline(always)]
+    fn count(self) -> usize {
+        self.indices.len()
+    }
+
+    #[inline]
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.indices.nth(n).map(|index| self.comment(index))
+    }
+}
+
+impl<'a> DoubleEndedIterator for QEntitiesCommentsIter<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.indices.next_back().map(|index| self.comment(index))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.indices.nth_back(n).map(|index| self.comment(index))
+    }
+}
+
+impl ExactSizeIterator for QEntitiesCommentsIter<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+}
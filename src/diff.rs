@@ -0,0 +1,335 @@
+//! Module containing the implementation for describing the differences between two [`QEntities`]
+//! collections.
+
+use super::{QEntities, QEntityKeyValueRef, QEntityRef};
+use hashbrown::HashMap;
+
+/// The differences between two [`QEntities`] collections, as created by [`QEntities::diff()`].
+///
+/// Entities are matched by their index, such that the entity at each index of the collection
+/// before is compared against the entity at the same index of the collection after. Entities past
+/// the end of the shorter collection are either [removed](QEntitiesChange::RemovedEntity) or
+/// [added](QEntitiesChange::AddedEntity). Entities whose key-values only differ in their order are
+/// not changed. The changes are ordered by the index of the entity that they describe.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::diff::{QEntitiesChange, QEntityKeyValueChange};
+/// use qentities::parse::QEntitiesParseOptions;
+///
+/// let parse_opts = QEntitiesParseOptions::new();
+/// let before = parse_opts
+///     .parse(&b"{ classname worldspawn }{ classname light light 300 }"[..])
+///     .unwrap();
+/// let after = parse_opts
+///     .parse(&b"{ classname worldspawn }{ classname light light 200 }{ classname info_null }"[..])
+///     .unwrap();
+///
+/// let diff = before.diff(&after);
+/// assert_eq!(diff.len(), 2);
+///
+/// let QEntitiesChange::ChangedEntity(entity_diff) = &diff.changes()[0] else {
+///     panic!("expected a changed entity");
+/// };
+/// assert_eq!(entity_diff.index(), 1);
+/// assert!(matches!(
+///     entity_diff.changes(),
+///     [QEntityKeyValueChange::Changed { before, after }]
+///         if before.value() == b"300" && after.value() == b"200"
+/// ));
+///
+/// assert!(matches!(
+///     diff.changes()[1],
+///     QEntitiesChange::AddedEntity(entity) if entity.index() == 2
+/// ));
+/// ```
+#[derive(Debug, Clone)]
+pub struct QEntitiesDiff<'a> {
+    /// The changes ordered by the index of the entity that they describe.
+    changes: Vec<QEntitiesChange<'a>>,
+}
+
+impl<'a> QEntitiesDiff<'a> {
+    /// Computes the differences between two collections.
+    pub(super) fn new(before: &'a QEntities, after: &'a QEntities) -> Self {
+        let mut changes = Vec::new();
+        let mut before_iter = before.iter();
+        let mut after_iter = after.iter();
+        loop {
+            match (before_iter.next(), after_iter.next()) {
+                (Some(before), Some(after)) => {
+                    if before != after {
+                        let entity_diff = QEntityDiff::new(before, after);
+                        if !entity_diff.changes.is_empty() {
+                            changes.push(QEntitiesChange::ChangedEntity(entity_diff));
+                        }
+                    }
+                }
+                (Some(before), None) => changes.push(QEntitiesChange::RemovedEntity(before)),
+                (None, Some(after)) => changes.push(QEntitiesChange::AddedEntity(after)),
+                (None, None) => break,
+            }
+        }
+        Self { changes }
+    }
+
+    /// Gets the number of changed, removed, and added entities.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns `true` if the collections are equal, disregarding the order of the key-values within
+    /// each entity.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Gets the changes ordered by the index of the entity that they describe.
+    #[inline]
+    pub fn changes(&self) -> &[QEntitiesChange<'a>] {
+        &self.changes
+    }
+
+    /// Creates an iterator that yields the changes ordered by the index of the entity that they
+    /// describe.
+    #[inline]
+    pub fn iter(&self) -> core::slice::Iter<'_, QEntitiesChange<'a>> {
+        self.changes.iter()
+    }
+}
+
+impl<'a> IntoIterator for QEntitiesDiff<'a> {
+    type Item = QEntitiesChange<'a>;
+    type IntoIter = std::vec::IntoIter<QEntitiesChange<'a>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b QEntitiesDiff<'a> {
+    type Item = &'b QEntitiesChange<'a>;
+    type IntoIter = core::slice::Iter<'b, QEntitiesChange<'a>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A change to a single entity within a [`QEntitiesDiff`].
+#[derive(Debug, Clone)]
+pub enum QEntitiesChange<'a> {
+    /// An entity of the collection after that has no counterpart within the collection before.
+    AddedEntity(QEntityRef<'a>),
+    /// An entity of the collection before that has no counterpart within the collection after.
+    RemovedEntity(QEntityRef<'a>),
+    /// An entity whose key-values differ between the collections.
+    ChangedEntity(QEntityDiff<'a>),
+}
+
+/// The differences between the key-values of an entity within two collections.
+///
+/// Key-values are matched by their key. Where a key appears multiple times within an entity, the
+/// first occurrence before is matched with the first occurrence after, the second with the
+/// second, and so on. Matched key-values whose values differ are
+/// [changed](QEntityKeyValueChange::Changed), and unmatched key-values are either
+/// [removed](QEntityKeyValueChange::Removed) or [added](QEntityKeyValueChange::Added).
+///
+/// The changed and removed key-values are ordered as they appear within the entity before, and
+/// are followed by the added key-values ordered as they appear within the entity after. Moving a
+/// key-value within an entity is therefore not a change.
+#[derive(Debug, Clone)]
+pub struct QEntityDiff<'a> {
+    /// The entity within the collection before.
+    before: QEntityRef<'a>,
+    /// The entity within the collection after.
+    after: QEntityRef<'a>,
+    /// The changes to the key-values of the entity.
+    changes: Vec<QEntityKeyValueChange<'a>>,
+}
+
+impl<'a> QEntityDiff<'a> {
+    /// Computes the differences between the key-values of two entities.
+    fn new(before: QEntityRef<'a>, after: QEntityRef<'a>) -> Self {
+        // The unmatched occurrences of each key after, with the first occurrence last.
+        let mut after_kvs = HashMap::<_, Vec<_>>::new();
+        for kv in after.iter().rev() {
+            after_kvs.entry(kv.key()).or_default().push(kv);
+        }
+
+        let mut changes = Vec::new();
+        let mut matched = vec![false; after.len()];
+        for before_kv in before.iter() {
+            match after_kvs
+                .get_mut(before_kv.key())
+                .and_then(|occurrences| occurrences.pop())
+            {
+                Some(after_kv) => {
+                    matched[after_kv.index_in_entity()] = true;
+                    if before_kv.value() != after_kv.value() {
+                        changes.push(QEntityKeyValueChange::Changed {
+                            before: before_kv,
+                            after: after_kv,
+                        });
+                    }
+                }
+                None => changes.push(QEntityKeyValueChange::Removed(before_kv)),
+            }
+        }
+        changes.extend(
+            after
+                .iter()
+                .filter(|kv| !matched[kv.index_in_entity()])
+                .map(QEntityKeyValueChange::Added),
+        );
+
+        Self {
+            before,
+            after,
+            changes,
+        }
+    }
+
+    /// Gets the index of the entity within both collections.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.before.index()
+    }
+
+    /// Gets the entity within the collection before.
+    #[inline]
+    pub fn before(&self) -> QEntityRef<'a> {
+        self.before
+    }
+
+    /// Gets the entity within the collection after.
+    #[inline]
+    pub fn after(&self) -> QEntityRef<'a> {
+        self.after
+    }
+
+    /// Gets the changes to the key-values of the entity.
+    ///
+    /// This is empty if the entities differ only in the order of their key-values.
+    #[inline]
+    pub fn changes(&self) -> &[QEntityKeyValueChange<'a>] {
+        &self.changes
+    }
+}
+
+/// A change to a single key-value within a [`QEntityDiff`].
+#[derive(Debug, Clone, Copy)]
+pub enum QEntityKeyValueChange<'a> {
+    /// A key-value of the entity after that has no counterpart within the entity before.
+    Added(QEntityKeyValueRef<'a>),
+    /// A key-value of the entity before that has no counterpart within the entity after.
+    Removed(QEntityKeyValueRef<'a>),
+    /// A key-value whose value differs between the entities.
+    Changed {
+        /// The key-value within the entity before.
+        before: QEntityKeyValueRef<'a>,
+        /// The key-value within the entity after.
+        after: QEntityKeyValueRef<'a>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QEntitiesChange, QEntityKeyValueChange};
+    use crate::parse::QEntitiesParseOptions;
+
+    /// Describes a key-value change as its key and its values before and after.
+    fn describe<'a>(
+        change: &QEntityKeyValueChange<'a>,
+    ) -> (&'a [u8], Option<&'a [u8]>, Option<&'a [u8]>) {
+        match *change {
+            QEntityKeyValueChange::Added(kv) => (kv.key(), None, Some(kv.value())),
+            QEntityKeyValueChange::Removed(kv) => (kv.key(), Some(kv.value()), None),
+            QEntityKeyValueChange::Changed { before, after } => {
+                (before.key(), Some(before.value()), Some(after.value()))
+            }
+        }
+    }
+
+    #[test]
+    fn diff_entities() {
+        let parse_opts = QEntitiesParseOptions::new();
+        let before = parse_opts
+            .parse(
+                &br#"
+                { classname worldspawn message "old" }
+                { classname light origin "0 0 0" light 300 }
+                { classname func_door target a target b speed 100 }
+                { classname info_null }
+                "#[..],
+            )
+            .unwrap();
+        let after = parse_opts
+            .parse(
+                &br#"
+                { message "old" classname worldspawn }
+                { classname light light 200 _color "1 0 0" }
+                { classname func_door target a target c speed 100 }
+                "#[..],
+            )
+            .unwrap();
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.len(), 3);
+        let changes: Vec<_> = diff.iter().collect();
+
+        // Reordering the key-values of an entity is not a change, so the first change is to the
+        // second entity.
+        let QEntitiesChange::ChangedEntity(entity_diff) = changes[0] else {
+            panic!("expected a changed entity");
+        };
+        assert_eq!(entity_diff.index(), 1);
+        assert_eq!(entity_diff.before(), before.get(1).unwrap());
+        assert_eq!(entity_diff.after(), after.get(1).unwrap());
+        let kv_changes: Vec<_> = entity_diff.changes().iter().map(describe).collect();
+        assert_eq!(
+            kv_changes,
+            [
+                (&b"origin"[..], Some(&b"0 0 0"[..]), None),
+                (b"light", Some(&b"300"[..]), Some(&b"200"[..])),
+                (b"_color", None, Some(&b"1 0 0"[..])),
+            ]
+        );
+
+        // Repeated keys are matched in order of occurrence.
+        let QEntitiesChange::ChangedEntity(entity_diff) = changes[1] else {
+            panic!("expected a changed entity");
+        };
+        let kv_changes: Vec<_> = entity_diff.changes().iter().map(describe).collect();
+        assert_eq!(
+            kv_changes,
+            [(&b"target"[..], Some(&b"b"[..]), Some(&b"c"[..]))]
+        );
+
+        assert!(matches!(
+            changes[2],
+            QEntitiesChange::RemovedEntity(entity) if entity.index() == 3
+        ));
+
+        // The reverse diff adds what was removed.
+        let reverse = after.diff(&before);
+        assert_eq!(reverse.len(), 3);
+        assert!(matches!(
+            reverse.into_iter().last(),
+            Some(QEntitiesChange::AddedEntity(entity)) if entity.index() == 3
+        ));
+
+        // Equal collections have no differences.
+        assert!(before.diff(&before).is_empty());
+        let reparsed = parse_opts.parse(after.to_string().as_bytes()).unwrap();
+        assert!(after.diff(&reparsed).is_empty());
+        let reordered = parse_opts.parse(&b"{ b 2 a 1 }"[..]).unwrap();
+        let original = parse_opts.parse(&b"{ a 1 b 2 }"[..]).unwrap();
+        assert!(original.diff(&reordered).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! Module containing the implementation for editing the values of a [`QEntities`] collection in
+//! place.
+
+use super::byte_chunk::ByteChunksBuilder;
+use super::parse::QEntitiesParseOptions;
+use super::{QEntities, QEntitiesComments, QEntitiesLineIndex, QEntityInfo, QEntityKeyValueInfo};
+use hashbrown::hash_map::DefaultHashBuilder;
+
+/// Editor for changing the values of a [`QEntities`] collection without rebuilding it.
+///
+/// An editor is created with [`QEntities::edit()`] and turned back into a collection with
+/// [`finish()`](Self::finish). Only the key-values whose values are changed are touched; every
+/// other key-value keeps its key and value byte-chunks. New values are interned against the
+/// existing byte-chunks of the collection if the collection is interned, so setting a value to a
+/// string that the collection already contains stores no new bytes.
+///
+/// The bytes of replaced values are retained until the collection is rebuilt, for instance with
+/// [`QEntities::map_key_values()`]. Editing is therefore best suited to changing a small number of
+/// values.
+///
+/// Creating an editor of an interned collection hashes every distinct key and value of the
+/// collection, so that new values can be interned against them. This takes time proportional to
+/// the [byte footprint](QEntities::byte_footprint) of the collection, so changing many values
+/// with one editor is much cheaper than creating an editor for each value.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::parse::QEntitiesParseOptions;
+///
+/// let src = b"{ classname worldspawn wad a.wad } { classname light origin \"0 0 0\" }";
+///
+/// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+/// let mut editor = entities.edit();
+/// editor.set_value(0, 1, b"b.wad").set_value(1, 1, b"0 0 8");
+/// let entities = editor.finish();
+///
+/// assert_eq!(entities.get(0).unwrap().value_of(b"wad"), Some(&b"b.wad"[..]));
+/// assert_eq!(entities.get(1).unwrap().value_of(b"origin"), Some(&b"0 0 8"[..]));
+/// ```
+#[derive(Debug)]
+pub struct QEntitiesEditor {
+    /// The entities of the collection, which are never changed.
+    entities: Box<[QEntityInfo]>,
+    /// The key-values of the collection, whose value byte-chunks are changed.
+    key_values: Box<[QEntityKeyValueInfo]>,
+    /// Builder continuing the byte-chunks of the collection, within which new values are stored.
+    byte_chunks: ByteChunksBuilder<DefaultHashBuilder>,
+    /// The line index of the collection, which the edited collection keeps.
+    line_index: Option<QEntitiesLineIndex>,
+    /// The comments retained by the collection, which the edited collection keeps.
+    comments: Option<Box<QEntitiesComments>>,
+    /// The options the collection was parsed with, which the edited collection keeps.
+    parse_options: Option<Box<QEntitiesParseOptions>>,
+}
+
+impl QEntitiesEditor {
+    /// Creates a new editor of the given collection.
+    pub(crate) fn new(entities: QEntities) -> Self {
+        Self {
+            entities: entities.entities,
+            key_values: entities.key_values,
+            byte_chunks: ByteChunksBuilder::from_byte_chunks(
+                entities.byte_chunks,
+                DefaultHashBuilder::default(),
+            ),
+            line_index: entities.line_index,
+            comments: entities.comments,
+            parse_options: entities.parse_options,
+        }
+    }
+
+    /// Gets the number of entities within the collection being edited.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Checks whether or not the collection being edited has no entities.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Sets the value of the key-value at `kv_index` within the entity at `entity_index`.
+    ///
+    /// # Panics
+    /// This function panics if either index is out of bounds, or if the total size of the keys and
+    /// values exceeds the maximum size of an allocation.
+    pub fn set_value(&mut self, entity_index: usize, kv_index: usize, value: &[u8]) -> &mut Self {
+        let entity_info = &self.entities[entity_index];
+        assert!(
+            kv_index < entity_info.kvs_length,
+            "key-value index out of bounds: the entity has {} key-values but the index is {}",
+            entity_info.kvs_length,
+            kv_index,
+        );
+
+        let value_chunk = self
+            .byte_chunks
+            .chunk(value)
+            .expect("keys and values must fit in memory");
+        self.key_values[entity_info.first_kv + kv_index].value_chunk = value_chunk;
+        self
+    }
+
+    /// Consumes the editor and constructs the edited collection.
+    pub fn finish(self) -> QEntities {
+        QEntities {
+            entities: self.entities,
+            key_values: self.key_values,
+            byte_chunks: self.byte_chunks.finish(),
+            line_index: self.line_index,
+            comments: self.comments,
+            parse_options: self.parse_options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::QEntitiesArena;
+    use crate::parse::QEntitiesParseOptions;
+    use crate::QEntities;
+
+    const SRC: &[u8] =
+        b"{ classname worldspawn wad a.wad }{ classname light origin \"0 0 0\" light 300 }";
+
+    fn chunk_indices(entities: &QEntities) -> Vec<(usize, usize)> {
+        entities
+            .key_values
+            .iter()
+            .map(|kv_info| (kv_info.key_chunk, kv_info.value_chunk))
+            .collect()
+    }
+
+    #[test]
+    fn edit_values() {
+        let entities = QEntitiesParseOptions::new().parse(SRC).unwrap();
+        let before = chunk_indices(&entities);
+        let chunk_count = entities.chunk_count();
+
+        // Values that already exist within the collection are shared rather than stored again.
+        let mut editor = entities.edit();
+        editor.set_value(1, 2, b"light");
+        let entities = editor.finish();
+        assert_eq!(entities.chunk_count(), chunk_count);
+        let light = entities.get(1).unwrap();
+        assert_eq!(light.value_of(b"light"), Some(&b"light"[..]));
+        assert!(light
+            .get(2)
+            .unwrap()
+            .shares_storage_with(&light.get(0).unwrap()));
+
+        // New values are stored once, and untouched key-values keep their byte-chunks.
+        let mut editor = entities.edit();
+        assert_eq!(editor.len(), 2);
+        editor
+            .set_value(0, 1, b"b.wad")
+            .set_value(1, 1, b"b.wad")
+            .set_value(1, 2, b"300");
+        let entities = editor.finish();
+        assert_eq!(entities.chunk_count(), chunk_count + 1);
+        let after = chunk_indices(&entities);
+        for (index, (before, after)) in before.iter().zip(&after).enumerate() {
+            assert_eq!(before.0, after.0);
+            if index != 1 && index != 3 {
+                assert_eq!(before.1, after.1);
+            }
+        }
+        assert_eq!(after[1].1, after[3].1);
+        assert_ne!(after[1].1, before[1].1);
+
+        let expected = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad b.wad }{ classname light origin b.wad light 300 }"[..])
+            .unwrap();
+        assert!(entities.iter().eq(expected.iter()));
+        assert_eq!(entities.to_string(), expected.to_string());
+
+        let mut packed = Vec::new();
+        entities.write_packed(&mut packed).unwrap();
+        let unpacked = QEntities::from_packed(&packed).unwrap();
+        assert!(unpacked.iter().eq(expected.iter()));
+        assert_eq!(unpacked.byte_footprint(), entities.byte_footprint());
+
+        // The bytes of replaced values are retained until the collection is rebuilt.
+        let replaced = b"a.wad".len() + b"0 0 0".len();
+        assert_eq!(
+            entities.byte_footprint(),
+            expected.byte_footprint() + replaced
+        );
+        let rebuilt = entities.map_key_values(|k, v| Some((k.into(), v.into())));
+        assert_eq!(rebuilt.byte_footprint(), expected.byte_footprint());
+    }
+
+    #[test]
+    fn edit_uninterned_values() {
+        let entities = QEntitiesParseOptions::new()
+            .intern(false)
+            .parse(SRC)
+            .unwrap();
+        let chunk_count = entities.chunk_count();
+
+        let mut editor = entities.edit();
+        editor.set_value(0, 1, b"light").set_value(1, 1, b"light");
+        let entities = editor.finish();
+        assert_eq!(entities.chunk_count(), chunk_count + 2);
+        assert_eq!(
+            entities.get(0).unwrap().value_of(b"wad"),
+            Some(&b"light"[..])
+        );
+    }
+
+    #[test]
+    fn edit_arena_values() {
+        let parse_opts = QEntitiesParseOptions::new();
+        let mut arena = QEntitiesArena::new();
+        let entities = parse_opts.parse_in_arena(SRC, &mut arena).unwrap();
+        let other = parse_opts.parse_in_arena(SRC, &mut arena).unwrap();
+
+        let mut editor = entities.edit();
+        editor.set_value(1, 1, b"0 0 8");
+        let entities = editor.finish();
+        assert_eq!(
+            entities.get(1).unwrap().value_of(b"origin"),
+            Some(&b"0 0 8"[..])
+        );
+
+        // Neither the arena nor other collections built within it are affected.
+        assert_eq!(arena.chunk_count(), 8);
+        assert!(other.iter().eq(parse_opts.parse(SRC).unwrap().iter()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn edit_out_of_bounds() {
+        let entities = QEntitiesParseOptions::new().parse(SRC).unwrap();
+        entities.edit().set_value(0, 2, b"");
+    }
+}
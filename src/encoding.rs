@@ -0,0 +1,92 @@
+//! Module containing the implementation for decoding keys and values that are stored using legacy
+//! single-byte encodings.
+//!
+//! This module requires the `encoding` feature.
+
+/// Single-byte character encodings that keys and values may be decoded from.
+///
+/// Files written by older tools predate the widespread use of UTF-8 and often store text using the
+/// code page of the system that wrote them. These encodings map each byte to exactly one character,
+/// so decoding never fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SingleByteEncoding {
+    /// ISO-8859-1, in which every byte is decoded as the unicode code point of the same value.
+    Latin1,
+    /// Windows code page 1252, which replaces the C1 control codes of ISO-8859-1 (`0x80..=0x9F`)
+    /// with printable characters such as `€` and `™`.
+    ///
+    /// The five bytes that are left undefined by the code page (`0x81`, `0x8D`, `0x8F`, `0x90`,
+    /// and `0x9D`) are decoded as the C1 control codes of the same value.
+    Windows1252,
+}
+
+/// Characters of the bytes `0x80..=0x9F` under Windows code page 1252.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl SingleByteEncoding {
+    /// Decodes a single byte as a character.
+    #[inline]
+    pub fn decode_byte(self, byte: u8) -> char {
+        match (self, byte) {
+            (Self::Windows1252, 0x80..=0x9F) => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        }
+    }
+
+    /// Decodes a sequence of bytes as a string.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::encoding::SingleByteEncoding;
+    ///
+    /// assert_eq!(SingleByteEncoding::Latin1.decode(b"caf\xE9"), "café");
+    /// assert_eq!(SingleByteEncoding::Windows1252.decode(b"\x93quoted\x94"), "\u{201C}quoted\u{201D}");
+    /// ```
+    pub fn decode(self, bytes: &[u8]) -> String {
+        bytes.iter().map(|&byte| self.decode_byte(byte)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_high_bytes() {
+        let bytes = b"\x80\x81\x8A\x99\x9F\xA0\xA9\xE9\xFF";
+        assert_eq!(
+            SingleByteEncoding::Latin1.decode(bytes),
+            "\u{80}\u{81}\u{8A}\u{99}\u{9F}\u{A0}©éÿ"
+        );
+        assert_eq!(
+            SingleByteEncoding::Windows1252.decode(bytes),
+            "€\u{81}Š™Ÿ\u{A0}©éÿ"
+        );
+
+        // Both encodings agree with ASCII and with each other outside of `0x80..=0x9F`.
+        for byte in (0x00..=0x7F).chain(0xA0..=0xFF) {
+            assert_eq!(
+                SingleByteEncoding::Latin1.decode_byte(byte),
+                char::from(byte)
+            );
+            assert_eq!(
+                SingleByteEncoding::Windows1252.decode_byte(byte),
+                char::from(byte)
+            );
+        }
+        for byte in 0x80..=0x9F {
+            let c = SingleByteEncoding::Windows1252.decode_byte(byte);
+            assert_eq!(
+                u32::from(c) > 0xFF,
+                ![0x81, 0x8D, 0x8F, 0x90, 0x9D].contains(&byte)
+            );
+        }
+    }
+}
@@ -2,7 +2,7 @@
 //! collection.
 
 use super::{QEntities, QEntityInfo, QEntityRef};
-use core::slice;
+use core::{ops, slice};
 
 /// Iterator over some entities within a [`QEntities`] collection.
 pub struct QEntitiesIter<'a> {
@@ -21,6 +21,18 @@ impl<'a> QEntitiesIter<'a> {
             inner_iter: entities.entities.iter(),
         }
     }
+
+    /// Creates a new iterator over some of the entities of a [`QEntities`] collection.
+    #[inline]
+    pub(super) fn from_entity_infos(
+        entities: &'a QEntities,
+        entity_infos: &'a [QEntityInfo],
+    ) -> Self {
+        Self {
+            entities,
+            inner_iter: entity_infos.iter(),
+        }
+    }
 }
 
 impl<'a> Iterator for QEntitiesIter<'a> {
@@ -80,3 +92,87 @@ impl ExactSizeIterator for QEntitiesIter<'_> {
         self.inner_iter.len()
     }
 }
+
+/// Consuming iterator over the entities within a [`QEntities`] collection, yielding each entity as
+/// an owned list of its key-value pairs.
+///
+/// The keys and values of each yielded entity are copied out of the byte-chunks of the collection,
+/// such that a key or value that is shared by many key-values through interning is copied once for
+/// each of them. The byte-chunks of the collection are released once the iterator is dropped.
+pub struct QEntitiesIntoIter {
+    /// The collection of q-entities being iterated.
+    entities: QEntities,
+    /// The indices of the entities that have yet to be yielded.
+    indices: ops::Range<usize>,
+}
+
+impl QEntitiesIntoIter {
+    /// Creates a new consuming iterator over the entities of a [`QEntities`] collection.
+    #[inline]
+    pub(super) fn new(entities: QEntities) -> Self {
+        let indices = 0..entities.len();
+        Self { entities, indices }
+    }
+
+    /// Copies the key-value pairs of an entity.
+    #[inline]
+    fn owned_entity(&self, index: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.entities
+            .entity_ref(&self.entities.entities[index])
+            .pairs()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect()
+    }
+}
+
+impl Iterator for QEntitiesIntoIter {
+    type Item = Vec<(Vec<u8>, Vec<u8>)>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|index| self.owned_entity(index))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+
+    #[inline(always)]
+    fn count(self) -> usize {
+        self.indices.len()
+    }
+
+    #[inline]
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.indices.nth(n).map(|index| self.owned_entity(index))
+    }
+}
+
+impl DoubleEndedIterator for QEntitiesIntoIter {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.indices
+            .next_back()
+            .map(|index| self.owned_entity(index))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.indices
+            .nth_back(n)
+            .map(|index| self.owned_entity(index))
+    }
+}
+
+impl ExactSizeIterator for QEntitiesIntoIter {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+}
@@ -0,0 +1,74 @@
+//! Module containing the implementation for an iterator over the key and value bytes of the
+//! key-values of an entity within a [`QEntities`](crate::QEntities) collection.
+
+use super::entity_kvs_iter::QEntityKeyValuesIter;
+use super::QEntityKeyValueRef;
+
+/// Iterator over the key and value bytes of some key-values of an entity within a
+/// [`QEntities`](crate::QEntities) collection.
+pub struct QEntityPairsIter<'a> {
+    /// The inner iterator for the key-values whose bytes are yielded.
+    inner_iter: QEntityKeyValuesIter<'a>,
+}
+
+impl<'a> QEntityPairsIter<'a> {
+    /// Creates a new iterator over the key and value bytes of the key-values of an entity.
+    #[inline]
+    pub(super) fn new(inner_iter: QEntityKeyValuesIter<'a>) -> Self {
+        Self { inner_iter }
+    }
+}
+
+/// Converts a key-value reference into its key and value bytes.
+#[inline(always)]
+fn pair_of<'a>(kv: QEntityKeyValueRef<'a>) -> (&'a [u8], &'a [u8]) {
+    (kv.key(), kv.value())
+}
+
+impl<'a> Iterator for QEntityPairsIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner_iter.next().map(pair_of)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner_iter.size_hint()
+    }
+
+    #[inline(always)]
+    fn count(self) -> usize {
+        self.inner_iter.count()
+    }
+
+    #[inline]
+    fn last(self) -> Option<Self::Item> {
+        self.inner_iter.last().map(pair_of)
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_iter.nth(n).map(pair_of)
+    }
+}
+
+impl<'a> DoubleEndedIterator for QEntityPairsIter<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner_iter.next_back().map(pair_of)
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_iter.nth_back(n).map(pair_of)
+    }
+}
+
+impl ExactSizeIterator for QEntityPairsIter<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.inner_iter.len()
+    }
+}
@@ -0,0 +1,187 @@
+//! Module containing the implementation for an index over the entities within a [`QEntities`]
+//! collection.
+
+use super::{QEntities, QEntityRef};
+use hashbrown::HashMap;
+
+/// Index mapping the `classname` and `targetname` values of the entities within a [`QEntities`]
+/// collection to the indices of those entities.
+///
+/// Building the index requires a single pass over every key-value of the collection and allocates
+/// an entry for every distinct `classname` and `targetname` value. In exchange, each lookup
+/// requires a single hash of the looked up bytes rather than a scan over every key-value of the
+/// collection, so building the index pays off once more than a handful of lookups are made.
+///
+/// The `classname` and `targetname` of an entity are determined by [`QEntityRef::value_of()`].
+/// The index borrows the collection and so is never out of date.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::parse::QEntitiesParseOptions;
+///
+/// let src = b"{ classname worldspawn }
+///             { classname func_door targetname door }
+///             { classname light targetname door }";
+///
+/// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+/// let index = entities.build_index();
+/// assert_eq!(index.by_classname(b"worldspawn"), [0]);
+/// assert_eq!(index.by_targetname(b"door"), [1, 2]);
+/// assert!(index.by_classname(b"info_null").is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct QEntitiesIndex<'a> {
+    /// The indexed collection of q-entities.
+    entities: &'a QEntities,
+    /// Map from `classname` values to the indices of the entities with that `classname`.
+    by_classname: HashMap<&'a [u8], Vec<usize>>,
+    /// Map from `targetname` values to the indices of the entities with that `targetname`.
+    by_targetname: HashMap<&'a [u8], Vec<usize>>,
+}
+
+impl<'a> QEntitiesIndex<'a> {
+    /// Builds a new index over the entities of a [`QEntities`] collection.
+    pub(super) fn new(entities: &'a QEntities) -> Self {
+        let mut by_classname = HashMap::<_, Vec<_>>::new();
+        let mut by_targetname = HashMap::<_, Vec<_>>::new();
+        for (entity_index, entity) in entities.iter().enumerate() {
+            // Scan the key-values once in reverse so that the last value of each key wins.
+            let (mut classname, mut targetname) = (None, None);
+            for kv in entity.iter().rev() {
+                match kv.key() {
+                    b"classname" if classname.is_none() => classname = Some(kv.value()),
+                    b"targetname" if targetname.is_none() => targetname = Some(kv.value()),
+                    _ => continue,
+                }
+                if classname.is_some() && targetname.is_some() {
+                    break;
+                }
+            }
+
+            if let Some(classname) = classname {
+                by_classname
+                    .entry(classname)
+                    .or_default()
+                    .push(entity_index);
+            }
+            if let Some(targetname) = targetname {
+                by_targetname
+                    .entry(targetname)
+                    .or_default()
+                    .push(entity_index);
+            }
+        }
+
+        Self {
+            entities,
+            by_classname,
+            by_targetname,
+        }
+    }
+
+    /// Gets the indexed collection.
+    #[inline]
+    pub fn entities(&self) -> &'a QEntities {
+        self.entities
+    }
+
+    /// Gets the indices of the entities whose `classname` is byte-wise equal to the given
+    /// classname.
+    ///
+    /// The indices are in ascending order, and the returned slice is empty if there are no such
+    /// entities.
+    #[inline]
+    pub fn by_classname(&self, classname: &[u8]) -> &[usize] {
+        self.by_classname.get(classname).map_or(&[], Vec::as_slice)
+    }
+
+    /// Gets the indices of the entities whose `targetname` is byte-wise equal to the given
+    /// targetname.
+    ///
+    /// The indices are in ascending order, and the returned slice is empty if there are no such
+    /// entities.
+    #[inline]
+    pub fn by_targetname(&self, targetname: &[u8]) -> &[usize] {
+        self.by_targetname
+            .get(targetname)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Creates an iterator that yields every entity whose `classname` is byte-wise equal to the
+    /// given classname.
+    ///
+    /// This is equivalent to getting every entity indexed by
+    /// [`by_classname()`](Self::by_classname).
+    #[inline]
+    pub fn entities_by_classname(
+        &self,
+        classname: &[u8],
+    ) -> impl Iterator<Item = QEntityRef<'a>> + '_ {
+        self.refs(self.by_classname(classname))
+    }
+
+    /// Creates an iterator that yields every entity whose `targetname` is byte-wise equal to the
+    /// given targetname.
+    ///
+    /// This is equivalent to getting every entity indexed by
+    /// [`by_targetname()`](Self::by_targetname).
+    #[inline]
+    pub fn entities_by_targetname(
+        &self,
+        targetname: &[u8],
+    ) -> impl Iterator<Item = QEntityRef<'a>> + '_ {
+        self.refs(self.by_targetname(targetname))
+    }
+
+    /// Creates an iterator that yields the entities for the given indices.
+    #[inline]
+    fn refs<'b>(&self, indices: &'b [usize]) -> impl Iterator<Item = QEntityRef<'a>> + 'b
+    where
+        'a: 'b,
+    {
+        let entities = self.entities;
+        indices
+            .iter()
+            .copied()
+            .map(move |entity_index| entities.entity_ref(&entities.entities[entity_index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::QEntitiesParseOptions;
+
+    #[test]
+    fn index_lookups() {
+        let src = b"
+            { classname worldspawn }
+            { classname light targetname a }
+            { targetname b classname light classname info_null }
+            { targetname a targetname c }
+            { }
+            { classname light targetname a }
+        ";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let index = entities.build_index();
+
+        for classname in [&b"worldspawn"[..], b"light", b"info_null", b"missing"] {
+            let expected: Vec<_> = entities
+                .iter_by_key(b"classname")
+                .filter(|(_, value)| *value == classname)
+                .map(|(entity_index, _)| entity_index)
+                .collect();
+            assert_eq!(index.by_classname(classname), expected);
+        }
+        assert_eq!(index.by_classname(b"light"), [1, 5]);
+        assert_eq!(index.by_targetname(b"a"), [1, 5]);
+        assert_eq!(index.by_targetname(b"b"), [2]);
+        assert_eq!(index.by_targetname(b"c"), [3]);
+        assert!(index.by_targetname(b"").is_empty());
+
+        let lights: Vec<_> = index.entities_by_classname(b"light").collect();
+        assert_eq!(lights, [entities.get(1).unwrap(), entities.get(5).unwrap()]);
+        assert_eq!(index.entities_by_targetname(b"c").count(), 1);
+        assert!(std::ptr::eq(index.entities(), &entities));
+    }
+}
@@ -2,15 +2,39 @@
 
 #![warn(missing_docs)]
 
+pub mod arena;
+mod builder;
 mod byte_chunk;
+pub mod comments_iter;
+pub mod diff;
+pub mod editor;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 pub mod entities_iter;
 pub mod entity_kvs_iter;
+pub mod entity_pairs_iter;
+pub mod index;
+pub mod packed;
 pub mod parse;
+pub mod schema;
+pub mod write;
 
-use byte_chunk::ByteChunks;
-use core::fmt;
-use entities_iter::QEntitiesIter;
+use builder::QEntitiesBuilder;
+use byte_chunk::{ByteChunkSet, ByteChunks};
+use comments_iter::QEntitiesCommentsIter;
+use core::{cmp, fmt};
+use diff::QEntitiesDiff;
+use editor::QEntitiesEditor;
+use entities_iter::{QEntitiesIntoIter, QEntitiesIter};
 use entity_kvs_iter::QEntityKeyValuesIter;
+use entity_pairs_iter::QEntityPairsIter;
+use hashbrown::hash_map::DefaultHashBuilder;
+use index::QEntitiesIndex;
+use parse::QEntitiesParserLocation;
+use schema::{QEntitiesSchema, QEntitiesSchemaViolation};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
 
 /// Information describing an entity instance within a [`QEntities`] collection.
 #[derive(Debug, Clone, Copy)]
@@ -32,21 +56,146 @@ struct QEntityKeyValueInfo {
     value_chunk: usize,
 }
 
+/// Comments retained while parsing a [`QEntities`] collection.
+#[derive(Debug)]
+#[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
+struct QEntitiesComments {
+    /// The bytes of every comment, stored contiguously.
+    bytes: Box<[u8]>,
+    /// The offset within the bytes at which each comment ends.
+    ends: Box<[usize]>,
+    /// The number of comments that precede each entity, including those of earlier entities.
+    entity_ends: Box<[usize]>,
+}
+
+impl QEntitiesComments {
+    /// Computes the total heap memory used by the comments, including the comments themselves.
+    fn heap_bytes(&self) -> usize {
+        core::mem::size_of::<Self>()
+            + core::mem::size_of_val(&*self.bytes)
+            + core::mem::size_of_val(&*self.ends)
+            + core::mem::size_of_val(&*self.entity_ends)
+    }
+}
+
+/// Offsets at which the lines of a parsed q-entities file begin.
+#[derive(Debug)]
+#[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
+struct QEntitiesLineIndex {
+    /// The number of the line that begins at the first offset.
+    first_line: u64,
+    /// The offset at which each line begins.
+    line_starts: Box<[u64]>,
+    /// The offset at which the parsed q-entities file ends.
+    end: u64,
+}
+
+/// Computes the index of an element within a slice from a reference to that element.
+///
+/// # Panics
+/// In debug builds this function panics if the element is not contained within the slice. In
+/// release builds the returned index is meaningless if this condition is violated.
+#[inline]
+fn slice_index_of<T>(slice: &[T], element: &T) -> usize {
+    debug_assert!(
+        slice.as_ptr_range().contains(&(element as *const _)),
+        "element must be contained within the slice",
+    );
+    (element as *const T as usize - slice.as_ptr() as usize) / core::mem::size_of::<T>()
+}
+
+/// Writes the debug representation of a list of entities, which is truncated to the first
+/// [`QEntities::DEBUG_ENTITY_LIMIT`] entities unless the alternate form is used.
+fn debug_entities<'a>(
+    f: &mut fmt::Formatter<'_>,
+    entities: impl ExactSizeIterator<Item = QEntityRef<'a>>,
+) -> fmt::Result {
+    /// Marker describing the number of entities that were omitted.
+    struct Omitted(usize);
+
+    impl fmt::Debug for Omitted {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "\u{2026} (+{} more)", self.0)
+        }
+    }
+
+    let len = entities.len();
+    if f.alternate() || len <= QEntities::DEBUG_ENTITY_LIMIT {
+        f.debug_list().entries(entities).finish()
+    } else {
+        f.debug_list()
+            .entries(entities.take(QEntities::DEBUG_ENTITY_LIMIT))
+            .entry(&Omitted(len - QEntities::DEBUG_ENTITY_LIMIT))
+            .finish()
+    }
+}
+
+/// Strategy for choosing which key-value survives when deduplicating the keys of an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDedupStrategy {
+    /// The first key-value with a given key survives.
+    FirstWins,
+    /// The last key-value with a given key survives.
+    LastWins,
+}
+
 /// Collection of q-entities.
 #[cfg_attr(feature = "deepsize", derive(deepsize::DeepSizeOf))]
 pub struct QEntities {
     entities: Box<[QEntityInfo]>,
     key_values: Box<[QEntityKeyValueInfo]>,
     byte_chunks: ByteChunks,
+    line_index: Option<QEntitiesLineIndex>,
+    comments: Option<Box<QEntitiesComments>>,
+    parse_options: Option<Box<parse::QEntitiesParseOptions>>,
 }
 
+/// The debug representation lists at most [`DEBUG_ENTITY_LIMIT`](Self::DEBUG_ENTITY_LIMIT)
+/// entities, followed by the number of entities that were omitted. The alternate form (`{:#?}`)
+/// lists every entity.
 impl fmt::Debug for QEntities {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self.iter()).finish()
+        debug_entities(f, self.iter())
+    }
+}
+
+/// Writes the collection as a q-entities file.
+///
+/// Provided that every key and value is valid UTF-8 without a double-quote (`"`), the output is the
+/// same as that of [`QEntitiesWriteOptions::write()`] and can be parsed back into an equal
+/// collection using the baseline grammar of [`QEntitiesParseOptions::new()`].
+///
+/// Otherwise the output is lossy. Bytes that are not valid UTF-8 are written as the unicode
+/// replacement character, and double-quotes are written as-is rather than being rejected, such
+/// that the output either fails to parse or parses into a different collection.
+///
+/// [`QEntitiesParseOptions::new()`]: parse::QEntitiesParseOptions::new
+/// [`QEntitiesWriteOptions::write()`]: write::QEntitiesWriteOptions::write
+impl fmt::Display for QEntities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use bstr::BStr;
+        for entity in self.iter() {
+            writeln!(f, "{{")?;
+            for kv in entity.iter() {
+                writeln!(
+                    f,
+                    "\"{}\" \"{}\"",
+                    BStr::new(kv.key()),
+                    BStr::new(kv.value()),
+                )?;
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
     }
 }
 
 impl QEntities {
+    /// The maximum number of entities listed by the non-alternate debug representation of a
+    /// collection, such that accidentally logging a large collection does not produce an enormous
+    /// line.
+    pub const DEBUG_ENTITY_LIMIT: usize = 32;
+
     /// Creates a new reference to an entity within the collection.
     ///
     /// # Panics
@@ -127,12 +276,45 @@ impl QEntities {
         self.entities.len()
     }
 
+    /// Returns `true` if the collection contains no entities.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
     /// Gets a [`QEntityRef`] by index.
     #[inline]
-    pub fn get(&self, index: usize) -> Option<QEntityRef> {
+    pub fn get(&self, index: usize) -> Option<QEntityRef<'_>> {
         self.entities.get(index).map(self.entity_ref_inator())
     }
 
+    /// Gets multiple [`QEntityRef`]s by index at once.
+    ///
+    /// Returns [`None`] if any of the indices is out of bounds. Indices do not need to be distinct
+    /// or in order, and the references are returned in the order of the indices.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light } { classname info_null }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    ///
+    /// let [null, world] = entities.get_many([2, 0]).unwrap();
+    /// assert_eq!(null.value_of(b"classname"), Some(&b"info_null"[..]));
+    /// assert_eq!(world.value_of(b"classname"), Some(&b"worldspawn"[..]));
+    ///
+    /// assert!(entities.get_many([1, 3]).is_none());
+    /// ```
+    pub fn get_many<const N: usize>(&self, indices: [usize; N]) -> Option<[QEntityRef<'_>; N]> {
+        if indices.iter().any(|&index| index >= self.entities.len()) {
+            return None;
+        }
+        Some(indices.map(|index| self.entity_ref(&self.entities[index])))
+    }
+
     /// Gets a [`QEntityRef`] by index without performing bounds checking.
     ///
     /// For a safe alternative see [`get()`](Self::get).
@@ -144,125 +326,2437 @@ impl QEntities {
     ///
     /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
     #[inline]
-    pub unsafe fn get_unchecked(&self, index: usize) -> QEntityRef {
+    pub unsafe fn get_unchecked(&self, index: usize) -> QEntityRef<'_> {
         self.entity_ref(self.entities.get_unchecked(index))
     }
 
+    /// Gets a [`QEntityKeyValueRef`] by its index across all key-values of the collection.
+    ///
+    /// Key-values are stored contiguously in the order of their entities, such that the key-value
+    /// at index `kv_index` within the entity at index `entity_index` has the global index
+    /// `first + kv_index`, where `first` is the sum of the [`len()`](QEntityRef::len) of the
+    /// entities preceding it. This is the inverse of [`QEntityKeyValueRef::global_index()`].
+    ///
+    /// Returns [`None`] if the index is not less than the total number of key-values.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn wad base.wad } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let kv = entities.key_value(2).unwrap();
+    /// assert_eq!(kv.value(), b"light");
+    /// assert_eq!(kv, entities.get(1).unwrap().get(0).unwrap());
+    /// assert!(entities.key_value(3).is_none());
+    /// ```
+    #[inline]
+    pub fn key_value(&self, global_index: usize) -> Option<QEntityKeyValueRef<'_>> {
+        self.key_values.get(global_index).map(self.kv_ref_inator())
+    }
+
     /// Creates an iterator that yields [`QEntityRef`]s for the entities of the collection.
     #[inline]
-    pub fn iter(&self) -> QEntitiesIter {
+    pub fn iter(&self) -> QEntitiesIter<'_> {
         QEntitiesIter::new(self)
     }
-}
 
-impl<'a> IntoIterator for &'a QEntities {
-    type IntoIter = QEntitiesIter<'a>;
-    type Item = QEntityRef<'a>;
+    /// Creates a parallel iterator that yields [`QEntityRef`]s for the entities of the collection.
+    ///
+    /// This is the parallel counterpart to [`iter()`](Self::iter), and requires the `rayon`
+    /// feature.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use rayon::prelude::*;
+    ///
+    /// let src = b"{ classname worldspawn wad a.wad } { classname light } { }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let kvs_count: usize = entities.par_iter().map(|entity| entity.len()).sum();
+    /// assert_eq!(kvs_count, 3);
+    /// ```
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_iter(&self) -> impl rayon::iter::IndexedParallelIterator<Item = QEntityRef<'_>> {
+        use rayon::prelude::*;
+        self.entities.par_iter().map(self.entity_ref_inator())
+    }
 
-    #[inline(always)]
-    fn into_iter(self) -> Self::IntoIter {
+    /// Divides the entities of the collection into two views at an index.
+    ///
+    /// The first view contains the entities within `[0, mid)` and the second contains those within
+    /// `[mid, len)`. Both views borrow the collection, and so share its keys and values. This is
+    /// useful for distributing the entities of a collection across multiple workers.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light } { classname info_null }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let (left, right) = entities.split_at(1);
+    /// assert_eq!(left.len(), 1);
+    /// assert_eq!(right.len(), 2);
+    /// assert_eq!(right.get(0).unwrap().value_of(b"classname"), Some(&b"light"[..]));
+    /// assert_eq!(right.get(0).unwrap().index(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if `mid > len`.
+    #[inline]
+    pub fn split_at(&self, mid: usize) -> (QEntitiesSlice<'_>, QEntitiesSlice<'_>) {
+        QEntitiesSlice::new(self, &self.entities).split_at(mid)
+    }
+
+    /// Creates an iterator that yields the index of the entity along with the key and value bytes
+    /// for every key-value of every entity in the collection.
+    ///
+    /// This is the collection-wide counterpart to [`QEntityRef::pairs()`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let pairs: Vec<_> = entities.iter_pairs().collect();
+    /// assert_eq!(
+    ///     pairs,
+    ///     [
+    ///         (0, &b"classname"[..], &b"worldspawn"[..]),
+    ///         (2, &b"classname"[..], &b"light"[..]),
+    ///     ],
+    /// );
+    /// ```
+    pub fn iter_pairs(&self) -> impl Iterator<Item = (usize, &[u8], &[u8])> {
+        self.iter().enumerate().flat_map(|(entity_index, entity)| {
+            entity
+                .pairs()
+                .map(move |(key, value)| (entity_index, key, value))
+        })
+    }
+
+    /// Creates an iterator that yields the index of every entity having the given key paired with
+    /// the value for that key.
+    ///
+    /// If an entity has multiple key-values with the given key, then the value of the last such
+    /// key-value is yielded.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { origin \"0 0 0\" } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let classnames: Vec<_> = entities.iter_by_key(b"classname").collect();
+    /// assert_eq!(classnames, [(0, &b"worldspawn"[..]), (2, &b"light"[..])]);
+    /// ```
+    pub fn iter_by_key<'a>(&'a self, key: &'a [u8]) -> impl Iterator<Item = (usize, &'a [u8])> {
         self.iter()
+            .enumerate()
+            .filter_map(move |(entity_index, entity)| {
+                entity.value_of(key).map(|value| (entity_index, value))
+            })
     }
-}
 
-/// Reference to an entity within a [`QEntities`] collection.
-#[derive(Clone, Copy)]
-pub struct QEntityRef<'a> {
-    /// The collection of q-entities in which the entity resides.
-    entities: &'a QEntities,
-    /// Information about the referenced entity.
-    entity_info: &'a QEntityInfo,
-}
+    /// Finds the first entity whose `classname` is byte-wise equal to the given classname.
+    ///
+    /// The `classname` of an entity is determined by [`QEntityRef::value_of()`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light light 100 } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let light = entities.find_by_classname(b"light").unwrap();
+    /// assert_eq!(light.value_of(b"light"), Some(&b"100"[..]));
+    /// assert!(entities.find_by_classname(b"info_null").is_none());
+    /// ```
+    pub fn find_by_classname(&self, classname: &[u8]) -> Option<QEntityRef<'_>> {
+        self.iter()
+            .find(|entity| entity.value_of(b"classname") == Some(classname))
+    }
 
-impl fmt::Debug for QEntityRef<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self.iter()).finish()
+    /// Creates an iterator that yields every entity whose `targetname` is byte-wise equal to the
+    /// `target` of the given entity.
+    ///
+    /// The `target` and `targetname` of an entity are determined by [`QEntityRef::value_of()`]. If
+    /// the given entity has no `target` key, then nothing is yielded. The given entity does not need
+    /// to reside within the collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname trigger_once target door }
+    ///             { classname func_door targetname door }
+    ///             { classname func_door targetname door }
+    ///             { classname light targetname lamp }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let trigger = entities.get(0).unwrap();
+    /// let doors: Vec<_> = entities.targets_of(trigger).map(|e| e.index()).collect();
+    /// assert_eq!(doors, [1, 2]);
+    /// ```
+    pub fn targets_of<'a>(
+        &'a self,
+        entity: QEntityRef<'a>,
+    ) -> impl Iterator<Item = QEntityRef<'a>> {
+        self.linked_by(entity.value_of(b"target"), b"targetname")
     }
-}
 
-impl<'a> QEntityRef<'a> {
-    /// Gets the number of key-values that compose the entity.
+    /// Creates an iterator that yields every entity whose `target` is byte-wise equal to the
+    /// `targetname` of the given entity.
+    ///
+    /// This is the inverse of [`targets_of()`](Self::targets_of). If the given entity has no
+    /// `targetname` key, then nothing is yielded.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname trigger_once target door }
+    ///             { classname trigger_multiple target door }
+    ///             { classname func_door targetname door }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let door = entities.get(2).unwrap();
+    /// let triggers: Vec<_> = entities.targeters_of(door).map(|e| e.index()).collect();
+    /// assert_eq!(triggers, [0, 1]);
+    /// ```
+    pub fn targeters_of<'a>(
+        &'a self,
+        entity: QEntityRef<'a>,
+    ) -> impl Iterator<Item = QEntityRef<'a>> {
+        self.linked_by(entity.value_of(b"targetname"), b"target")
+    }
+
+    /// Creates an iterator that yields every entity whose value for the given key is byte-wise
+    /// equal to the given value, yielding nothing if there is no value.
+    fn linked_by<'a>(
+        &'a self,
+        value: Option<&'a [u8]>,
+        key: &'static [u8],
+    ) -> impl Iterator<Item = QEntityRef<'a>> {
+        value
+            .into_iter()
+            .flat_map(move |value| {
+                self.iter_by_key(key)
+                    .filter(move |(_, linked_value)| *linked_value == value)
+            })
+            .map(|(entity_index, _)| self.entity_ref(&self.entities[entity_index]))
+    }
+
+    /// Builds an index over the `classname` and `targetname` values of the entities within the
+    /// collection.
+    ///
+    /// See [`QEntitiesIndex`] for when building an index is worthwhile.
     #[inline]
-    pub fn len(&self) -> usize {
-        self.entity_info.kvs_length
+    pub fn build_index(&self) -> QEntitiesIndex<'_> {
+        QEntitiesIndex::new(self)
     }
 
-    /// Gets a [`QEntityKeyValueRef`] by index.
+    /// Computes the differences between this collection and another collection, treating this
+    /// collection as the one before and the other as the one after.
+    ///
+    /// Entities are matched by their index. See [`QEntitiesDiff`] for how the differences are
+    /// described.
     #[inline]
-    pub fn get(&self, index: usize) -> Option<QEntityKeyValueRef> {
-        (index < self.entity_info.kvs_length).then(|| {
-            self.entities
-                .kv_ref(&self.entities.key_values[self.entity_info.first_kv + index])
-        })
+    pub fn diff<'a>(&'a self, other: &'a QEntities) -> QEntitiesDiff<'a> {
+        QEntitiesDiff::new(self, other)
     }
 
-    /// Gets a [`QEntityKeyValueRef`] by index without performing bounds checking.
+    /// Checks the keys of the entities within the collection against a schema, returning every
+    /// violation of the schema.
     ///
-    /// For a safe alternative see [`get()`](Self::get).
+    /// The violations are ordered by the index of the entity. The unknown keys of an entity are
+    /// reported in the order that they first appear within it, each only once, followed by the
+    /// missing required keys in the order that they were required. See [`QEntitiesSchema`] for
+    /// which entities are checked.
+    #[inline]
+    pub fn validate<'a>(
+        &'a self,
+        schema: &'a QEntitiesSchema,
+    ) -> Vec<QEntitiesSchemaViolation<'a>> {
+        schema.validate(self)
+    }
+
+    /// Finds the first `worldspawn` entity.
     ///
-    /// # Safety
-    /// The index that this function is called with must always be less than the value returned by
-    /// [`len()`](Self::len). Using any index that does not meet this requirement may result in
-    /// *[undefined behavior]*.
+    /// This is equivalent to calling [`find_by_classname()`](Self::find_by_classname) with
+    /// `b"worldspawn"`.
+    #[inline]
+    pub fn worldspawn(&self) -> Option<QEntityRef<'_>> {
+        self.find_by_classname(b"worldspawn")
+    }
+
+    /// Counts the number of entities with each `classname`.
     ///
-    /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
+    /// The `classname` of an entity is determined by [`QEntityRef::value_of()`]. Entities without a
+    /// `classname` key are not counted and so are absent from the returned map.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light } { classname light } { light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let counts = entities.classname_counts();
+    /// assert_eq!(counts.len(), 2);
+    /// assert_eq!(counts[&b"worldspawn"[..]], 1);
+    /// assert_eq!(counts[&b"light"[..]], 2);
+    /// ```
+    pub fn classname_counts(&self) -> HashMap<&[u8], usize> {
+        let mut counts = HashMap::new();
+        for classname in self
+            .iter()
+            .filter_map(|entity| entity.value_of(b"classname"))
+        {
+            *counts.entry(classname).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Groups the entities by their `classname`.
+    ///
+    /// The `classname` of an entity is determined by [`QEntityRef::value_of()`]. The entities of
+    /// each group are in the order they appear in the collection. Entities without a `classname`
+    /// key are not grouped and so are absent from the returned map.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light } { classname light } { light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let groups = entities.group_by_classname();
+    /// assert_eq!(groups.len(), 2);
+    /// assert_eq!(groups[&b"light"[..]], [entities.get(1).unwrap(), entities.get(2).unwrap()]);
+    /// ```
+    pub fn group_by_classname(&self) -> HashMap<&[u8], Vec<QEntityRef<'_>>> {
+        let mut groups = HashMap::<_, Vec<_>>::new();
+        for entity in self.iter() {
+            if let Some(classname) = entity.value_of(b"classname") {
+                groups.entry(classname).or_default().push(entity);
+            }
+        }
+        groups
+    }
+
+    /// Gets the number of bytes used to store the keys and values of the collection.
+    ///
+    /// When a collection is parsed with interning enabled, each distinct key or value contributes
+    /// its bytes only once. Collections produced by [`filter()`](Self::filter),
+    /// [`map_key_values()`](Self::map_key_values), and [`dedup_keys()`](Self::dedup_keys) are
+    /// interned in the same way and only store the keys and values they contain, so their
+    /// footprint never includes bytes of the source collection that were left behind.
+    ///
+    /// This does not hold for every collection:
+    /// * When parsed with [`intern()`](parse::QEntitiesParseOptions::intern) disabled, every key
+    ///   and value contributes its bytes, even if it is repeated.
+    /// * When [edited](Self::edit), the bytes of replaced values are still counted.
+    /// * When built within an [`arena`], each distinct key or value used by the collection is
+    ///   counted, even though its bytes are shared with the other collections of the arena.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// assert_eq!(entities.byte_footprint(), b"classname".len() + b"light".len());
+    /// ```
     #[inline]
-    pub unsafe fn get_unchecked(&self, index: usize) -> QEntityKeyValueRef {
-        self.entities.kv_ref(
-            self.entities
-                .key_values
-                .get_unchecked(self.entity_info.first_kv + index),
-        )
+    pub fn byte_footprint(&self) -> usize {
+        self.byte_chunks.bytes_len()
     }
 
-    /// Creates an iterator that yields [`QEntityKeyValueRef`]s for the key-values of the entity.
+    /// Gets the options that the collection was parsed with, if they were retained.
+    ///
+    /// Options are only retained by collections parsed with
+    /// [`retain_parse_options()`](parse::QEntitiesParseOptions::retain_parse_options) enabled.
+    /// This is [`None`] for collections that were not produced by parsing, including those that
+    /// were read with [`from_packed()`](Self::from_packed) and those derived from another
+    /// collection by [`filter()`](Self::filter), [`map_key_values()`](Self::map_key_values), or
+    /// [`dedup_keys()`](Self::dedup_keys). Collections that were [edited](Self::edit) retain
+    /// the options of the collection they were edited from.
+    ///
+    /// This allows a collection to be re-serialized according to the grammar it was parsed with,
+    /// such as by escaping the same bytes that were escapable when it was parsed.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::quake()
+    ///     .with_intern(false)
+    ///     .with_retain_parse_options(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// let parse_opts = entities.parse_options().unwrap();
+    /// assert!(parse_opts.parse(&src[..]).unwrap().iter().eq(entities.iter()));
+    ///
+    /// let filtered = entities.filter(|_| true);
+    /// assert!(filtered.parse_options().is_none());
+    /// ```
     #[inline]
-    pub fn iter(&self) -> QEntityKeyValuesIter<'a> {
-        QEntityKeyValuesIter::new(self.entities, self.entity_info)
+    pub fn parse_options(&self) -> Option<&parse::QEntitiesParseOptions> {
+        self.parse_options.as_deref()
     }
-}
 
-impl<'a> IntoIterator for QEntityRef<'a> {
-    type IntoIter = QEntityKeyValuesIter<'a>;
-    type Item = QEntityKeyValueRef<'a>;
+    /// Gets the total number of bytes of the heap allocations backing the collection.
+    ///
+    /// Whereas [`byte_footprint()`](Self::byte_footprint) only counts the logical bytes of the
+    /// keys and values, this also counts the structural memory of the collection: the tables
+    /// describing each entity and key-value, the table describing each stored key and value, the
+    /// [`line_index()`](Self::line_index) if one was recorded, and the
+    /// [`parse_options()`](Self::parse_options) if they were retained. The size of the [`QEntities`]
+    /// value itself and any unused capacity of the allocator are not counted.
+    ///
+    /// The bytes of collections built within an [`arena`] are shared with the arena and every
+    /// other collection built within it, and are counted in full by each of them.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// assert!(entities.heap_bytes() > entities.byte_footprint());
+    /// ```
+    pub fn heap_bytes(&self) -> usize {
+        core::mem::size_of_val(&*self.entities)
+            + core::mem::size_of_val(&*self.key_values)
+            + self.byte_chunks.heap_bytes()
+            + self.line_index.as_ref().map_or(0, |line_index| {
+                core::mem::size_of_val(&*line_index.line_starts)
+            })
+            + self
+                .comments
+                .as_deref()
+                .map_or(0, QEntitiesComments::heap_bytes)
+            + self
+                .parse_options
+                .as_deref()
+                .map_or(0, core::mem::size_of_val)
+    }
 
-    #[inline(always)]
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    /// Gets the number of byte-chunks used to store the keys and values of the collection.
+    ///
+    /// When interning is enabled this is the number of distinct keys and values, which can be
+    /// given to [`QEntitiesParseOptions::expected_chunk_count()`] to pre-size the interning table
+    /// of later parses of similar files.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light } { classname light origin \"0 0 0\" }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// assert_eq!(entities.chunk_count(), 4);
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .intern(false)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.chunk_count(), 6);
+    /// ```
+    ///
+    /// [`QEntitiesParseOptions::expected_chunk_count()`]: parse::QEntitiesParseOptions::expected_chunk_count
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.byte_chunks.len()
     }
-}
 
-/// Reference to a key-value within a [`QEntities`] collection.
-#[derive(Clone, Copy)]
-pub struct QEntityKeyValueRef<'a> {
-    /// The collection of q-entities in which the key-value resides.
-    entities: &'a QEntities,
-    /// Information about the referenced key-value.
-    kv_info: &'a QEntityKeyValueInfo,
-}
+    /// Gets a summary of the size of the collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let summary = entities.summary();
+    /// assert_eq!(summary.entities(), 2);
+    /// assert_eq!(summary.key_values(), 3);
+    /// assert_eq!(summary.to_string(), "2 entities, 3 key-values, 27 bytes");
+    /// ```
+    #[inline]
+    pub fn summary(&self) -> QEntitiesSummary {
+        QEntitiesSummary {
+            entities: self.entities.len(),
+            key_values: self.key_values.len(),
+            byte_footprint: self.byte_footprint(),
+        }
+    }
 
-impl fmt::Debug for QEntityKeyValueRef<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use bstr::BStr;
-        write!(
-            f,
-            "{:?}: {:?}",
-            BStr::new(self.key()),
-            BStr::new(self.value()),
-        )
+    /// Creates a new collection containing only the entities for which the given predicate returns
+    /// `true`.
+    ///
+    /// The order of the retained entities is preserved. The bytes of the new collection are
+    /// interned anew such that it only holds the keys and values of the retained entities.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname func_detail } { classname light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let filtered = entities.filter(|entity| entity.value_of(b"classname") != Some(b"func_detail"));
+    /// assert_eq!(filtered.len(), 2);
+    /// assert!(filtered.find_by_classname(b"func_detail").is_none());
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if memory for the keys and values of the retained entities cannot be
+    /// allocated.
+    pub fn filter<F: FnMut(QEntityRef) -> bool>(&self, mut f: F) -> QEntities {
+        let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+        for entity in self.iter().filter(|entity| f(*entity)) {
+            builder.push_entity();
+            for (key, value) in entity.pairs() {
+                builder
+                    .push_key_value(key, value)
+                    .expect("keys and values must fit in memory");
+            }
+        }
+        builder.finish()
     }
-}
 
-impl<'a> QEntityKeyValueRef<'a> {
-    /// Gets a reference to the bytes of the key.
-    #[inline]
-    pub fn key(&self) -> &'a [u8] {
-        &self.entities.byte_chunks[self.kv_info.key_chunk]
+    /// Creates a new collection by transforming every key-value of the collection with the given
+    /// function.
+    ///
+    /// The function is called with the key and value of each key-value and returns either the new
+    /// key and value, or [`None`] to drop the key-value. Entities are preserved even if all of
+    /// their key-values are dropped, and the order of entities and key-values is preserved. The
+    /// bytes of the new collection are interned anew.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use std::borrow::Cow;
+    ///
+    /// let src = b"{ classname light _light 300 _color \"1 1 1\" }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let mapped = entities.map_key_values(|key, value| match key {
+    ///     b"_light" => Some((Cow::Borrowed(&b"light"[..]), Cow::Borrowed(value))),
+    ///     b"_color" => None,
+    ///     _ => Some((Cow::Borrowed(key), Cow::Borrowed(value))),
+    /// });
+    ///
+    /// let entity = mapped.get(0).unwrap();
+    /// assert!(entity.pairs().eq([
+    ///     (&b"classname"[..], &b"light"[..]),
+    ///     (&b"light"[..], &b"300"[..]),
+    /// ]));
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if the total size of the new keys and values exceeds the maximum size
+    /// of an allocation, or if memory for them cannot be allocated.
+    pub fn map_key_values<'a, F>(&'a self, mut f: F) -> QEntities
+    where
+        F: FnMut(&'a [u8], &'a [u8]) -> Option<(Cow<'a, [u8]>, Cow<'a, [u8]>)>,
+    {
+        let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+        for entity in self.iter() {
+            builder.push_entity();
+            for (key, value) in entity.pairs() {
+                if let Some((key, value)) = f(key, value) {
+                    builder
+                        .push_key_value(&key, &value)
+                        .expect("keys and values must fit in memory");
+                }
+            }
+        }
+        builder.finish()
     }
 
-    /// Gets a reference to the bytes of the value.
+    /// Creates a new collection in which each entity has at most one key-value for each distinct
+    /// key.
+    ///
+    /// Keys are compared byte-wise, and which of the key-values with the same key survives is
+    /// determined by the given strategy. Surviving key-values retain their relative order. The
+    /// bytes of the new collection are interned anew.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::KeyDedupStrategy;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ target a classname trigger_multiple target b }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    ///
+    /// let first_wins = entities.dedup_keys(KeyDedupStrategy::FirstWins);
+    /// assert!(first_wins.get(0).unwrap().pairs().eq([
+    ///     (&b"target"[..], &b"a"[..]),
+    ///     (&b"classname"[..], &b"trigger_multiple"[..]),
+    /// ]));
+    ///
+    /// let last_wins = entities.dedup_keys(KeyDedupStrategy::LastWins);
+    /// assert!(last_wins.get(0).unwrap().pairs().eq([
+    ///     (&b"classname"[..], &b"trigger_multiple"[..]),
+    ///     (&b"target"[..], &b"b"[..]),
+    /// ]));
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if memory for the keys and values of the surviving key-values cannot be
+    /// allocated.
+    pub fn dedup_keys(&self, strategy: KeyDedupStrategy) -> QEntities {
+        let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+
+        let mut seen_key_chunks = ByteChunkSet::new(&self.byte_chunks);
+        let mut surviving_kvs = Vec::new();
+        for entity in self.iter() {
+            seen_key_chunks.clear();
+            surviving_kvs.clear();
+
+            let mut survives = |kv: &QEntityKeyValueRef| {
+                seen_key_chunks.insert(&self.byte_chunks, kv.kv_info.key_chunk)
+            };
+            match strategy {
+                KeyDedupStrategy::FirstWins => {
+                    surviving_kvs.extend(entity.iter().filter(|kv| survives(kv)));
+                }
+                KeyDedupStrategy::LastWins => {
+                    surviving_kvs.extend(entity.iter().rev().filter(|kv| survives(kv)));
+                    surviving_kvs.reverse();
+                }
+            }
+
+            builder.push_entity();
+            for kv in surviving_kvs.iter() {
+                builder
+                    .push_key_value(kv.key(), kv.value())
+                    .expect("keys and values must fit in memory");
+            }
+        }
+        builder.finish()
+    }
+
+    /// Consumes the collection and creates an editor for changing its values in place.
+    ///
+    /// Unlike [`map_key_values()`](Self::map_key_values), the collection is not rebuilt and only
+    /// the changed values are interned. Creating the editor still hashes every distinct key and
+    /// value of an interned collection once. See [`QEntitiesEditor`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light _light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let mut editor = entities.edit();
+    /// editor.set_value(0, 1, b"200");
+    /// let entities = editor.finish();
+    /// assert_eq!(entities.get(0).unwrap().value_of(b"_light"), Some(&b"200"[..]));
+    /// ```
     #[inline]
-    pub fn value(&self) -> &'a [u8] {
-        &self.entities.byte_chunks[self.kv_info.value_chunk]
+    pub fn edit(self) -> QEntitiesEditor {
+        QEntitiesEditor::new(self)
+    }
+
+    /// Consumes the collection and creates an iterator that yields an owned map of the key-values
+    /// for each entity.
+    ///
+    /// If an entity has multiple key-values with the same key, then the value of the last such
+    /// key-value is retained within the map.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light origin \"0 0 0\" }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let maps: Vec<_> = entities.into_owned_maps().collect();
+    /// assert_eq!(maps.len(), 2);
+    /// assert_eq!(maps[1][&b"origin"[..]], b"0 0 0");
+    /// ```
+    pub fn into_owned_maps(self) -> impl Iterator<Item = HashMap<Vec<u8>, Vec<u8>>> {
+        (0..self.len()).map(move |index| {
+            self.entity_ref(&self.entities[index])
+                .iter()
+                .map(|kv| (kv.key().to_vec(), kv.value().to_vec()))
+                .collect()
+        })
+    }
+
+    /// Checks whether or not both collections have the same key schema.
+    ///
+    /// Two collections have the same key schema when they have the same number of entities and
+    /// each entity has the same set of keys as the entity at the same index of the other
+    /// collection. Values, the order of key-values, and repetitions of a key within an entity are
+    /// all disregarded.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let parse_opts = QEntitiesParseOptions::new();
+    /// let a = parse_opts.parse(&b"{ classname light origin \"0 0 0\" }"[..]).unwrap();
+    /// let b = parse_opts.parse(&b"{ origin \"8 8 8\" classname light }"[..]).unwrap();
+    /// let c = parse_opts.parse(&b"{ classname light }"[..]).unwrap();
+    ///
+    /// assert!(a.same_key_schema(&b));
+    /// assert!(!a.same_key_schema(&c));
+    /// ```
+    pub fn same_key_schema(&self, other: &QEntities) -> bool {
+        fn sorted_keys<'a>(entity: QEntityRef<'a>, keys: &mut Vec<&'a [u8]>) {
+            keys.clear();
+            keys.extend(entity.iter().map(|kv| kv.key()));
+            keys.sort_unstable();
+            keys.dedup();
+        }
+
+        if self.len() != other.len() {
+            return false;
+        }
+
+        let mut self_keys = Vec::new();
+        let mut other_keys = Vec::new();
+        self.iter()
+            .zip(other.iter())
+            .all(|(self_entity, other_entity)| {
+                sorted_keys(self_entity, &mut self_keys);
+                sorted_keys(other_entity, &mut other_keys);
+                self_keys == other_keys
+            })
+    }
+
+    /// Creates an iterator over every distinct key used by any entity within the collection.
+    ///
+    /// Keys are yielded in the order of their first appearance within the collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn wad a.wad } { classname light light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let keys = entities.distinct_keys().collect::<Vec<_>>();
+    /// assert_eq!(keys, [&b"classname"[..], b"wad", b"light"]);
+    /// ```
+    pub fn distinct_keys(&self) -> impl Iterator<Item = &[u8]> {
+        let mut seen_key_chunks = ByteChunkSet::new(&self.byte_chunks);
+        self.key_values
+            .iter()
+            .filter(move |kv_info| seen_key_chunks.insert(&self.byte_chunks, kv_info.key_chunk))
+            .map(|kv_info| &self.byte_chunks[kv_info.key_chunk])
+    }
+
+    /// Gets the offsets at which each line of the parsed q-entities file begins.
+    ///
+    /// The offset at index `n` is the offset of the first byte of line number `n + 1`. For the
+    /// documents parsed by [`parse_multi()`](parse::QEntitiesParseOptions::parse_multi), the
+    /// index instead begins with the line on which the document begins.
+    ///
+    /// This is only available for collections parsed with
+    /// [`record_line_index()`](parse::QEntitiesParseOptions::record_line_index) enabled.
+    #[inline]
+    pub fn line_index(&self) -> Option<&[u64]> {
+        self.line_index
+            .as_ref()
+            .map(|line_index| &*line_index.line_starts)
+    }
+
+    /// Creates an iterator that yields the text of the comments following the last entity of the
+    /// parsed q-entities file.
+    ///
+    /// If the file has no entities, then these are all of the comments of the file. This is only
+    /// ever non-empty for collections parsed with
+    /// [`retain_comments()`](parse::QEntitiesParseOptions::retain_comments) enabled.
+    #[inline]
+    pub fn trailing_comments(&self) -> QEntitiesCommentsIter<'_> {
+        let comments = self.comments.as_deref();
+        let indices = comments.map_or(0..0, |comments| {
+            comments.entity_ends.last().copied().unwrap_or(0)..comments.ends.len()
+        });
+        QEntitiesCommentsIter::new(comments, indices)
+    }
+
+    /// Maps an absolute offset within the parsed q-entities file to its line and column.
+    ///
+    /// This performs a binary search of the collection's [line index](Self::line_index) and as such
+    /// returns [`None`] if the collection has no line index, or if the offset precedes the first
+    /// line of the index or follows the end of the parsed q-entities file.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{\nclassname worldspawn\n}";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .record_line_index(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    ///
+    /// let location = entities.offset_to_location(12).unwrap();
+    /// assert_eq!(location.line(), 2);
+    /// assert_eq!(location.column(), 11);
+    /// ```
+    pub fn offset_to_location(&self, offset: u64) -> Option<QEntitiesParserLocation> {
+        let line_index = self.line_index.as_ref()?;
+        if offset > line_index.end {
+            return None;
+        }
+
+        let lines_before = line_index
+            .line_starts
+            .partition_point(|&line_start| line_start <= offset);
+        let line = lines_before.checked_sub(1)?;
+        let line_start = line_index.line_starts[line];
+        Some(QEntitiesParserLocation::new(
+            offset,
+            line_index.first_line + line as u64,
+            offset - line_start + 1,
+        ))
+    }
+}
+
+impl<'a> IntoIterator for &'a QEntities {
+    type IntoIter = QEntitiesIter<'a>;
+    type Item = QEntityRef<'a>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Consumes the collection, yielding each entity as an owned list of its key-value pairs.
+///
+/// The keys and values are copied out of the collection, see [`QEntitiesIntoIter`].
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::parse::QEntitiesParseOptions;
+///
+/// let src = b"{ classname worldspawn } { classname light origin \"0 0 0\" }";
+///
+/// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+/// let mut classnames = Vec::new();
+/// for entity in entities {
+///     for (key, value) in entity {
+///         if key == b"classname" {
+///             classnames.push(value);
+///         }
+///     }
+/// }
+/// assert_eq!(classnames, [b"worldspawn".to_vec(), b"light".to_vec()]);
+/// ```
+impl IntoIterator for QEntities {
+    type IntoIter = QEntitiesIntoIter;
+    type Item = Vec<(Vec<u8>, Vec<u8>)>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        QEntitiesIntoIter::new(self)
+    }
+}
+
+/// Parses a q-entities file using the baseline grammar of [`QEntitiesParseOptions::new()`].
+///
+/// See [`QEntitiesParseOptions`] for parsing with other options.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::QEntities;
+///
+/// let src = b"{ classname worldspawn }{ classname light }";
+/// let entities = QEntities::try_from(&src[..]).unwrap();
+/// assert_eq!(entities.len(), 2);
+///
+/// let e = QEntities::try_from(&b"{ classname"[..]).unwrap_err();
+/// assert!(e.is_syntax());
+/// ```
+///
+/// [`QEntitiesParseOptions`]: parse::QEntitiesParseOptions
+/// [`QEntitiesParseOptions::new()`]: parse::QEntitiesParseOptions::new
+impl TryFrom<&[u8]> for QEntities {
+    type Error = parse::QEntitiesParseError;
+
+    #[inline]
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        parse::QEntitiesParseOptions::new().parse(value)
+    }
+}
+
+/// Summary of the size of a [`QEntities`] collection.
+///
+/// See [`QEntities::summary()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QEntitiesSummary {
+    /// The number of entities.
+    entities: usize,
+    /// The total number of key-values across all entities.
+    key_values: usize,
+    /// The number of bytes used to store the keys and values.
+    byte_footprint: usize,
+}
+
+impl QEntitiesSummary {
+    /// Gets the number of entities within the collection.
+    #[inline]
+    pub fn entities(&self) -> usize {
+        self.entities
+    }
+
+    /// Gets the total number of key-values across all entities within the collection.
+    #[inline]
+    pub fn key_values(&self) -> usize {
+        self.key_values
+    }
+
+    /// Gets the number of bytes used to store the keys and values of the collection.
+    ///
+    /// See [`QEntities::byte_footprint()`].
+    #[inline]
+    pub fn byte_footprint(&self) -> usize {
+        self.byte_footprint
+    }
+}
+
+impl fmt::Display for QEntitiesSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} entities, {} key-values, {} bytes",
+            self.entities, self.key_values, self.byte_footprint,
+        )
+    }
+}
+
+/// View of a contiguous range of the entities within a [`QEntities`] collection.
+///
+/// See [`QEntities::split_at()`].
+#[derive(Clone, Copy)]
+pub struct QEntitiesSlice<'a> {
+    /// The collection of q-entities in which the entities reside.
+    entities: &'a QEntities,
+    /// Information about the entities within the view.
+    entity_infos: &'a [QEntityInfo],
+}
+
+/// The debug representation is truncated in the same manner as that of [`QEntities`].
+impl fmt::Debug for QEntitiesSlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_entities(f, self.iter())
+    }
+}
+
+impl<'a> QEntitiesSlice<'a> {
+    /// Creates a new view of some of the entities of a collection.
+    #[inline]
+    fn new(entities: &'a QEntities, entity_infos: &'a [QEntityInfo]) -> Self {
+        Self {
+            entities,
+            entity_infos,
+        }
+    }
+
+    /// Gets the number of entities within the view.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entity_infos.len()
+    }
+
+    /// Returns `true` if the view contains no entities.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entity_infos.is_empty()
+    }
+
+    /// Gets a [`QEntityRef`] by index within the view.
+    ///
+    /// Note that the [`index()`](QEntityRef::index) of the returned entity is its index within the
+    /// collection rather than within the view.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<QEntityRef<'a>> {
+        self.entity_infos
+            .get(index)
+            .map(self.entities.entity_ref_inator())
+    }
+
+    /// Creates an iterator that yields [`QEntityRef`]s for the entities within the view.
+    #[inline]
+    pub fn iter(&self) -> QEntitiesIter<'a> {
+        QEntitiesIter::from_entity_infos(self.entities, self.entity_infos)
+    }
+
+    /// Divides the view into two views at an index.
+    ///
+    /// See [`QEntities::split_at()`].
+    ///
+    /// # Panics
+    /// This function panics if `mid > len`.
+    #[inline]
+    pub fn split_at(&self, mid: usize) -> (QEntitiesSlice<'a>, QEntitiesSlice<'a>) {
+        let (left, right) = self.entity_infos.split_at(mid);
+        (
+            Self::new(self.entities, left),
+            Self::new(self.entities, right),
+        )
+    }
+}
+
+impl<'a> IntoIterator for QEntitiesSlice<'a> {
+    type Item = QEntityRef<'a>;
+    type IntoIter = QEntitiesIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Reference to an entity within a [`QEntities`] collection.
+#[derive(Clone, Copy)]
+pub struct QEntityRef<'a> {
+    /// The collection of q-entities in which the entity resides.
+    entities: &'a QEntities,
+    /// Information about the referenced entity.
+    entity_info: &'a QEntityInfo,
+}
+
+impl fmt::Debug for QEntityRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Entities are equal when they have equal key-values in the same order, regardless of the
+/// collections in which they reside.
+impl PartialEq for QEntityRef<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for QEntityRef<'_> {}
+
+impl PartialOrd for QEntityRef<'_> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Entities are ordered lexicographically by their sequence of key-values, which are in turn
+/// ordered as described by the [`Ord`] implementation of [`QEntityKeyValueRef`].
+impl Ord for QEntityRef<'_> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<'a> QEntityRef<'a> {
+    /// Gets the index of the entity within its collection.
+    ///
+    /// This is computed in constant time from the position of the entity's information within the
+    /// collection.
+    #[inline]
+    pub fn index(&self) -> usize {
+        slice_index_of(&self.entities.entities, self.entity_info)
+    }
+
+    /// Creates an iterator that yields the text of the comments preceding the entity within the
+    /// parsed q-entities file, following the previous entity if any.
+    ///
+    /// This is only ever non-empty for collections parsed with
+    /// [`retain_comments()`](parse::QEntitiesParseOptions::retain_comments) enabled.
+    #[inline]
+    pub fn leading_comments(&self) -> QEntitiesCommentsIter<'a> {
+        let comments = self.entities.comments.as_deref();
+        let indices = comments.map_or(0..0, |comments| {
+            let index = self.index();
+            let start = index
+                .checked_sub(1)
+                .map_or(0, |prev| comments.entity_ends[prev]);
+            start..comments.entity_ends[index]
+        });
+        QEntitiesCommentsIter::new(comments, indices)
+    }
+
+    /// Gets the number of key-values that compose the entity.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entity_info.kvs_length
+    }
+
+    /// Returns `true` if the entity has no key-values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entity_info.kvs_length == 0
+    }
+
+    /// Gets a [`QEntityKeyValueRef`] by index.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<QEntityKeyValueRef<'_>> {
+        (index < self.entity_info.kvs_length).then(|| {
+            self.entities
+                .kv_ref(&self.entities.key_values[self.entity_info.first_kv + index])
+        })
+    }
+
+    /// Gets a [`QEntityKeyValueRef`] by index from the end of the entity, such that an index of
+    /// zero gets the last key-value.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname trigger_once OnTrigger a OnTrigger b }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.get_back(0).unwrap().value(), b"b");
+    /// assert_eq!(entity.get_back(2).unwrap().key(), b"classname");
+    /// assert!(entity.get_back(3).is_none());
+    /// ```
+    #[inline]
+    pub fn get_back(&self, index: usize) -> Option<QEntityKeyValueRef<'_>> {
+        let kvs_length = self.entity_info.kvs_length;
+        if index < kvs_length {
+            self.get(kvs_length - 1 - index)
+        } else {
+            None
+        }
+    }
+
+    /// Gets a [`QEntityKeyValueRef`] by index without performing bounds checking.
+    ///
+    /// For a safe alternative see [`get()`](Self::get).
+    ///
+    /// # Safety
+    /// The index that this function is called with must always be less than the value returned by
+    /// [`len()`](Self::len). Using any index that does not meet this requirement may result in
+    /// *[undefined behavior]*.
+    ///
+    /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
+    #[inline]
+    pub unsafe fn get_unchecked(&self, index: usize) -> QEntityKeyValueRef<'_> {
+        self.entities.kv_ref(
+            self.entities
+                .key_values
+                .get_unchecked(self.entity_info.first_kv + index),
+        )
+    }
+
+    /// Gets the value for a key of the entity.
+    ///
+    /// If the entity has multiple key-values with the given key, then the value of the last such
+    /// key-value is returned.
+    #[inline]
+    pub fn value_of(&self, key: &[u8]) -> Option<&'a [u8]> {
+        self.iter()
+            .rev()
+            .find(|kv| kv.key() == key)
+            .map(|kv| kv.value())
+    }
+
+    /// Creates an iterator that yields [`QEntityKeyValueRef`]s for the key-values of the entity.
+    #[inline]
+    pub fn iter(&self) -> QEntityKeyValuesIter<'a> {
+        QEntityKeyValuesIter::new(self.entities, self.entity_info)
+    }
+
+    /// Creates an iterator that yields the key and value bytes for the key-values of the entity.
+    ///
+    /// This is equivalent to mapping each [`QEntityKeyValueRef`] yielded by [`iter()`](Self::iter)
+    /// to a tuple of its [`key()`](QEntityKeyValueRef::key) and
+    /// [`value()`](QEntityKeyValueRef::value).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light origin \"0 0 0\" }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let pairs: Vec<_> = entities.get(0).unwrap().pairs().collect();
+    /// assert_eq!(
+    ///     pairs,
+    ///     [(&b"classname"[..], &b"light"[..]), (&b"origin"[..], &b"0 0 0"[..])],
+    /// );
+    /// ```
+    #[inline]
+    pub fn pairs(&self) -> QEntityPairsIter<'a> {
+        QEntityPairsIter::new(self.iter())
+    }
+
+    /// Creates a map from the keys of the entity to their values.
+    ///
+    /// If the entity has multiple key-values with the same key, then the value of the last such
+    /// key-value is kept, which matches [`value_of()`](Self::value_of). The keys and values are
+    /// borrowed from the collection, so the map can outlive this reference but not the collection.
+    ///
+    /// This is useful for performing many lookups, each of which would otherwise require a linear
+    /// search over the key-values of the entity.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light light 200 light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let map = entities.get(0).unwrap().to_map();
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map.get(&b"classname"[..]), Some(&&b"light"[..]));
+    /// assert_eq!(map.get(&b"light"[..]), Some(&&b"300"[..]));
+    /// ```
+    pub fn to_map(&self) -> HashMap<&'a [u8], &'a [u8]> {
+        self.pairs().collect()
+    }
+
+    /// Creates a map from the keys of the entity to all of their values.
+    ///
+    /// The values of each key are in the order that they appear within the entity. Like
+    /// [`to_map()`](Self::to_map), the keys and values are borrowed from the collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light light 200 light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let map = entities.get(0).unwrap().to_multimap();
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map[&b"classname"[..]], [&b"light"[..]]);
+    /// assert_eq!(map[&b"light"[..]], [&b"200"[..], b"300"]);
+    /// ```
+    pub fn to_multimap(&self) -> HashMap<&'a [u8], Vec<&'a [u8]>> {
+        let mut map = HashMap::<_, Vec<_>>::new();
+        for (key, value) in self.pairs() {
+            map.entry(key).or_default().push(value);
+        }
+        map
+    }
+
+    /// Writes the entity to a writer as a single `{ ... }` block of a q-entities file.
+    ///
+    /// The written block is the same as that which
+    /// [`QEntitiesWriteOptions::write()`](write::QEntitiesWriteOptions::write) writes for the
+    /// entity as part of its collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use qentities::write::QEntitiesWriteOptions;
+    ///
+    /// let src = b"{ classname worldspawn }{ classname light light 300 }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    ///
+    /// let mut dst = Vec::new();
+    /// entities
+    ///     .get(1)
+    ///     .unwrap()
+    ///     .write_to(&mut dst, &QEntitiesWriteOptions::new())
+    ///     .unwrap();
+    /// assert_eq!(dst, b"{\n\"classname\" \"light\"\n\"light\" \"300\"\n}\n");
+    /// ```
+    #[inline]
+    pub fn write_to<W: io::Write>(
+        &self,
+        w: &mut W,
+        opts: &write::QEntitiesWriteOptions,
+    ) -> io::Result<()> {
+        opts.write_entity(*self, w, &mut Vec::new())
+    }
+}
+
+impl<'a> IntoIterator for QEntityRef<'a> {
+    type IntoIter = QEntityKeyValuesIter<'a>;
+    type Item = QEntityKeyValueRef<'a>;
+
+    #[inline(always)]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Reference to a key-value within a [`QEntities`] collection.
+#[derive(Clone, Copy)]
+pub struct QEntityKeyValueRef<'a> {
+    /// The collection of q-entities in which the key-value resides.
+    entities: &'a QEntities,
+    /// Information about the referenced key-value.
+    kv_info: &'a QEntityKeyValueInfo,
+}
+
+impl fmt::Debug for QEntityKeyValueRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use bstr::BStr;
+        write!(
+            f,
+            "{:?}: {:?}",
+            BStr::new(self.key()),
+            BStr::new(self.value()),
+        )
+    }
+}
+
+/// Key-values are equal when they have byte-wise equal keys and values, regardless of the
+/// collections in which they reside.
+impl PartialEq for QEntityKeyValueRef<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        (self.key(), self.value()) == (other.key(), other.value())
+    }
+}
+
+impl Eq for QEntityKeyValueRef<'_> {}
+
+impl PartialOrd for QEntityKeyValueRef<'_> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Key-values are ordered byte-lexicographically by their keys, and then byte-lexicographically by
+/// their values.
+impl Ord for QEntityKeyValueRef<'_> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (self.key(), self.value()).cmp(&(other.key(), other.value()))
+    }
+}
+
+impl<'a> QEntityKeyValueRef<'a> {
+    /// Gets the index of the key-value across all key-values of its collection.
+    ///
+    /// Key-values are stored contiguously in the order of their entities, so the key-values of an
+    /// entity with `n` key-values occupy `n` consecutive global indices. This is computed in
+    /// constant time from the position of the key-value's information within the collection.
+    #[inline]
+    pub fn global_index(&self) -> usize {
+        slice_index_of(&self.entities.key_values, self.kv_info)
+    }
+
+    /// Gets the index of the key-value within its entity.
+    ///
+    /// Unlike [`global_index()`](Self::global_index) this is not computed in constant time, as the
+    /// entity containing the key-value must be located with a binary search over the entities of
+    /// the collection.
+    pub fn index_in_entity(&self) -> usize {
+        let global_index = self.global_index();
+
+        // Entities are stored such that their key-values are contiguous and in order, so the last
+        // entity whose first key-value is at or before the key-value is the one that contains it.
+        let entities = &self.entities.entities;
+        let entity_index = entities.partition_point(|entity| entity.first_kv <= global_index) - 1;
+        global_index - entities[entity_index].first_kv
+    }
+
+    /// Gets a reference to the bytes of the key.
+    #[inline]
+    pub fn key(&self) -> &'a [u8] {
+        &self.entities.byte_chunks[self.kv_info.key_chunk]
+    }
+
+    /// Gets a reference to the bytes of the value.
+    #[inline]
+    pub fn value(&self) -> &'a [u8] {
+        &self.entities.byte_chunks[self.kv_info.value_chunk]
+    }
+
+    /// Gets a reference to the bytes of the key as a [`BStr`](bstr::BStr).
+    ///
+    /// This is the same as [`key()`](Self::key), but is convenient for displaying the key or
+    /// searching it using the methods of [`bstr`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use bstr::ByteSlice;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ _light_color \"255 128 0\" }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// let kv = entity.iter().next().unwrap();
+    ///
+    /// assert!(kv.key_bstr().starts_with_str("_"));
+    /// assert_eq!(kv.key_bstr().to_string(), "_light_color");
+    /// ```
+    #[inline]
+    pub fn key_bstr(&self) -> &'a bstr::BStr {
+        bstr::BStr::new(self.key())
+    }
+
+    /// Gets a reference to the bytes of the value as a [`BStr`](bstr::BStr).
+    ///
+    /// This is the same as [`value()`](Self::value), but is convenient for displaying the value or
+    /// searching it using the methods of [`bstr`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use bstr::ByteSlice;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ wad \"gfx/base.wad;gfx/mywad.wad\" }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// let kv = entity.iter().next().unwrap();
+    ///
+    /// assert_eq!(kv.value_bstr().find("mywad"), Some(17));
+    /// let wads = kv.value_bstr().split_str(";").collect::<Vec<_>>();
+    /// assert_eq!(wads, [&b"gfx/base.wad"[..], b"gfx/mywad.wad"]);
+    /// assert_eq!(format!("{}", kv.value_bstr()), "gfx/base.wad;gfx/mywad.wad");
+    /// ```
+    #[inline]
+    pub fn value_bstr(&self) -> &'a bstr::BStr {
+        bstr::BStr::new(self.value())
+    }
+
+    /// Decodes the bytes of the key as a string using a legacy single-byte encoding.
+    ///
+    /// This requires the `encoding` feature. See [`value_decode()`](Self::value_decode).
+    #[cfg(feature = "encoding")]
+    #[inline]
+    pub fn key_decode(&self, encoding: encoding::SingleByteEncoding) -> String {
+        encoding.decode(self.key())
+    }
+
+    /// Decodes the bytes of the value as a string using a legacy single-byte encoding.
+    ///
+    /// Unlike decoding the value as UTF-8, decoding never fails as every byte is decoded as exactly
+    /// one character. This requires the `encoding` feature.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::encoding::SingleByteEncoding;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ message \"Caf\xE9 \x96 Start\" }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// let kv = entity.get(0).unwrap();
+    ///
+    /// assert_eq!(kv.value_decode(SingleByteEncoding::Windows1252), "Café \u{2013} Start");
+    /// assert_eq!(kv.value_decode(SingleByteEncoding::Latin1), "Café \u{96} Start");
+    /// ```
+    #[cfg(feature = "encoding")]
+    #[inline]
+    pub fn value_decode(&self, encoding: encoding::SingleByteEncoding) -> String {
+        encoding.decode(self.value())
+    }
+
+    /// Checks whether or not the key and value are stored as the same bytes within the collection.
+    ///
+    /// This is an artifact of interning rather than a logical property of the key-value. A key
+    /// and value that are byte-wise equal only share their storage if the collection was built
+    /// with interning enabled, so use `kv.key() == kv.value()` to compare their bytes.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ light light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// assert!(entities.get(0).unwrap().get(0).unwrap().key_is_value());
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .intern(false)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert!(!entities.get(0).unwrap().get(0).unwrap().key_is_value());
+    /// ```
+    #[inline]
+    pub fn key_is_value(&self) -> bool {
+        self.kv_info.key_chunk == self.kv_info.value_chunk
+    }
+
+    /// Checks whether or not the key-value stores its key or value as the same bytes as the key or
+    /// value of another key-value.
+    ///
+    /// Key-values from different collections only share storage if the collections were built
+    /// within the same [arena](arena::QEntitiesArena), and empty keys and values store no bytes
+    /// to share. Like [`key_is_value()`](Self::key_is_value) this is an artifact of interning
+    /// rather than a logical property of the key-values, so it is mostly useful for analysing the
+    /// memory footprint of a collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname light } { classname func_door } { target light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// let kvs: Vec<_> = entities.iter().flat_map(|entity| entity.iter()).collect();
+    /// let (light, door, target) = (kvs[0], kvs[1], kvs[2]);
+    /// assert!(light.shares_storage_with(&door));
+    /// assert!(light.shares_storage_with(&target));
+    /// assert!(!door.shares_storage_with(&target));
+    /// ```
+    pub fn shares_storage_with(&self, other: &QEntityKeyValueRef) -> bool {
+        // The bytes themselves are compared by address rather than by byte-chunk, since the
+        // collections of an arena share bytes without sharing byte-chunks.
+        let shares = |bytes: &[u8]| {
+            !bytes.is_empty()
+                && (core::ptr::eq(bytes, other.key()) || core::ptr::eq(bytes, other.value()))
+        };
+        shares(self.key()) || shares(self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::QEntitiesParseOptions;
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<QEntities>();
+        assert_send_sync::<QEntityRef<'_>>();
+        assert_send_sync::<QEntityKeyValueRef<'_>>();
+        assert_send_sync::<QEntitiesIter<'_>>();
+        assert_send_sync::<QEntityKeyValuesIter<'_>>();
+        assert_send_sync::<QEntitiesIndex<'_>>();
+        assert_send_sync::<QEntityPairsIter<'_>>();
+        assert_send_sync::<QEntitiesSummary>();
+        assert_send_sync::<arena::QEntitiesArena>();
+        assert_send_sync::<QEntitiesEditor>();
+        assert_send_sync::<QEntitiesSlice<'_>>();
+        assert_send_sync::<QEntitiesDiff<'_>>();
+        assert_send_sync::<diff::QEntityDiff<'_>>();
+        assert_send_sync::<diff::QEntitiesChange<'_>>();
+        assert_send_sync::<diff::QEntityKeyValueChange<'_>>();
+        assert_send_sync::<QEntitiesIntoIter>();
+        assert_send_sync::<QEntitiesSchema>();
+        assert_send_sync::<QEntitiesSchemaViolation<'_>>();
+        assert_send_sync::<QEntitiesCommentsIter<'_>>();
+
+        assert_send_sync::<parse::QEntitiesParseOptions>();
+        assert_send_sync::<write::QEntitiesWriteOptions>();
+        assert_send_sync::<write::QEntitiesWriter<Vec<u8>>>();
+
+        assert_send_sync::<parse::QEntitiesParseError>();
+        assert_send_sync::<parse::QEntitiesParseErrorCastError>();
+        assert_send_sync::<parse::QEntitiesUnexpectedTokenError>();
+        assert_send_sync::<parse::QEntitiesLengthError>();
+        assert_send_sync::<packed::QEntitiesFromPackedError>();
+        assert_send_sync::<packed::QEntitiesReadBinaryError>();
+        assert_send_sync::<parse::QEntitiesWarning>();
+    }
+
+    #[test]
+    fn same_key_schema() {
+        let parse_opts = QEntitiesParseOptions::new();
+        let entities = parse_opts
+            .parse(&b"{ classname worldspawn wad a.wad }{ classname light origin \"0 0 0\" }"[..])
+            .unwrap();
+
+        let different_values = parse_opts
+            .parse(&b"{ wad b.wad classname worldspawn }{ origin \"1 2 3\" classname light classname light }"[..])
+            .unwrap();
+        assert!(entities.same_key_schema(&different_values));
+        assert!(different_values.same_key_schema(&entities));
+        assert!(entities.same_key_schema(&entities));
+
+        let missing_key = parse_opts
+            .parse(&b"{ classname worldspawn wad a.wad }{ classname light }"[..])
+            .unwrap();
+        assert!(!entities.same_key_schema(&missing_key));
+        assert!(!missing_key.same_key_schema(&entities));
+
+        let missing_entity = parse_opts
+            .parse(&b"{ classname worldspawn wad a.wad }"[..])
+            .unwrap();
+        assert!(!entities.same_key_schema(&missing_entity));
+        assert!(!missing_entity.same_key_schema(&entities));
+    }
+
+    #[test]
+    fn into_owned_maps() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(
+                &b"{ classname worldspawn wad a.wad }{}{ classname light light 100 light 200 }"[..],
+            )
+            .unwrap();
+
+        let maps: Vec<_> = entities.into_owned_maps().collect();
+        assert_eq!(maps.len(), 3);
+
+        assert_eq!(maps[0].len(), 2);
+        assert_eq!(maps[0][&b"classname"[..]], b"worldspawn");
+        assert_eq!(maps[0][&b"wad"[..]], b"a.wad");
+
+        assert!(maps[1].is_empty());
+
+        assert_eq!(maps[2].len(), 2);
+        assert_eq!(maps[2][&b"classname"[..]], b"light");
+        assert_eq!(maps[2][&b"light"[..]], b"200");
+    }
+
+    #[test]
+    fn entity_ordering() {
+        let parse_opts = QEntitiesParseOptions::new();
+        let a = parse_opts
+            .parse(&b"{ classname light origin \"0 0 0\" }{ classname light }{}"[..])
+            .unwrap();
+        let b = parse_opts
+            .parse(&b"{ classname light }{ classname light origin \"0 0 0\" }{ classname light origin \"0 0 1\" }{ classname info_null }"[..])
+            .unwrap();
+
+        let (a0, a1, a2) = (a.get(0).unwrap(), a.get(1).unwrap(), a.get(2).unwrap());
+        let (b0, b1, b2, b3) = (
+            b.get(0).unwrap(),
+            b.get(1).unwrap(),
+            b.get(2).unwrap(),
+            b.get(3).unwrap(),
+        );
+
+        assert_eq!(a0, b1);
+        assert_eq!(a1, b0);
+        assert_ne!(a0, a1);
+        assert_ne!(a0, b2);
+        assert_ne!(a2, b0);
+
+        assert!(a2 < a1, "empty entities order first");
+        assert!(a1 < a0, "prefixes order first");
+        assert!(a0 < b2, "values are compared");
+        assert!(b3 < b0, "keys then values are compared");
+
+        let mut sorted: Vec<_> = a.iter().chain(b.iter()).collect();
+        sorted.sort();
+        assert_eq!(sorted, [a2, b3, a1, b0, a0, b1, b2]);
+
+        assert_eq!(a0.get(0), b0.get(0));
+        assert!(a0.get(1).unwrap() > a0.get(0).unwrap());
+    }
+
+    #[test]
+    fn find_by_classname() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname light id 0 }{ classname worldspawn }{ classname light id 1 }{ classname info_null classname light }"[..])
+            .unwrap();
+
+        let light = entities.find_by_classname(b"light").unwrap();
+        assert_eq!(light.value_of(b"id"), Some(&b"0"[..]));
+        assert_eq!(light.value_of(b"origin"), None);
+
+        assert_eq!(entities.worldspawn(), entities.get(1));
+        assert!(entities.find_by_classname(b"info_null").is_none());
+        assert!(entities.find_by_classname(b"Light").is_none());
+
+        let no_worldspawn = QEntitiesParseOptions::new()
+            .parse(&b"{ classname light }"[..])
+            .unwrap();
+        assert!(no_worldspawn.worldspawn().is_none());
+    }
+
+    #[test]
+    fn classname_groups() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn }{ classname light id 0 }{ id 1 }{ classname info_null classname light }{ classname info_null }"[..])
+            .unwrap();
+
+        let counts = entities.classname_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts.get(&b"worldspawn"[..]), Some(&1));
+        assert_eq!(counts.get(&b"light"[..]), Some(&2));
+        assert_eq!(counts.get(&b"info_null"[..]), Some(&1));
+
+        let groups = entities.group_by_classname();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            groups[&b"light"[..]],
+            [entities.get(1).unwrap(), entities.get(3).unwrap()]
+        );
+        assert_eq!(groups[&b"info_null"[..]], [entities.get(4).unwrap()]);
+        assert_eq!(
+            groups.values().map(Vec::len).sum::<usize>(),
+            counts.values().sum::<usize>()
+        );
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert!(empty.classname_counts().is_empty());
+        assert!(empty.group_by_classname().is_empty());
+    }
+
+    #[test]
+    fn target_links() {
+        let src = br#"
+            { classname trigger_once target relay }
+            { classname trigger_relay targetname relay target lights }
+            { classname light targetname lights }
+            { classname light targetname Lights }
+            { classname light targetname lights target lights }
+            { classname trigger_multiple target relay targetname relay }
+            { classname info_null target "" }
+            { classname info_null targetname "" }
+        "#;
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let indices = |iter: &mut dyn Iterator<Item = QEntityRef>| {
+            iter.map(|entity| entity.index()).collect::<Vec<_>>()
+        };
+
+        let trigger = entities.get(0).unwrap();
+        assert_eq!(indices(&mut entities.targets_of(trigger)), [1, 5]);
+        assert!(entities.targeters_of(trigger).next().is_none());
+
+        let relay = entities.get(1).unwrap();
+        assert_eq!(indices(&mut entities.targets_of(relay)), [2, 4]);
+        assert_eq!(indices(&mut entities.targeters_of(relay)), [0, 5]);
+
+        // Entities can target themselves, and names are compared byte-wise.
+        let light = entities.get(4).unwrap();
+        assert_eq!(indices(&mut entities.targets_of(light)), [2, 4]);
+        assert_eq!(indices(&mut entities.targeters_of(light)), [1, 4]);
+        let upper_light = entities.get(3).unwrap();
+        assert!(entities.targeters_of(upper_light).next().is_none());
+
+        // Empty names are compared like any other.
+        let empty_target = entities.get(6).unwrap();
+        assert_eq!(indices(&mut entities.targets_of(empty_target)), [7]);
+
+        // The entity can come from another collection.
+        let other = QEntitiesParseOptions::new()
+            .parse(&b"{ target lights }"[..])
+            .unwrap();
+        let other_trigger = other.get(0).unwrap();
+        assert_eq!(indices(&mut entities.targets_of(other_trigger)), [2, 4]);
+    }
+
+    #[test]
+    fn shared_storage() {
+        let src = b"{ a a b a }{ b c }";
+        let interned = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let not_interned = QEntitiesParseOptions::new()
+            .intern(false)
+            .parse(&src[..])
+            .unwrap();
+
+        let (first, second) = (interned.get(0).unwrap(), interned.get(1).unwrap());
+        let (aa, ba, bc) = (
+            first.get(0).unwrap(),
+            first.get(1).unwrap(),
+            second.get(0).unwrap(),
+        );
+        assert!(aa.key_is_value());
+        assert!(!ba.key_is_value());
+        assert!(aa.shares_storage_with(&aa));
+        assert!(aa.shares_storage_with(&ba));
+        assert!(ba.shares_storage_with(&bc));
+        assert!(!aa.shares_storage_with(&bc));
+
+        let first_copy = not_interned.get(0).unwrap();
+        let (aa_copy, ba_copy) = (first_copy.get(0).unwrap(), first_copy.get(1).unwrap());
+        assert!(!aa_copy.key_is_value());
+        assert!(aa_copy.shares_storage_with(&aa_copy));
+        assert!(!aa_copy.shares_storage_with(&ba_copy));
+
+        // Key-values from different collections only share storage when built within the same
+        // arena.
+        assert_eq!(aa, aa_copy);
+        assert!(!aa.shares_storage_with(&aa_copy));
+
+        let mut arena = arena::QEntitiesArena::new();
+        let parse_opts = QEntitiesParseOptions::new();
+        let first_arena = parse_opts.parse_in_arena(&src[..], &mut arena).unwrap();
+        let second_arena = parse_opts
+            .parse_in_arena(&b"{ a a }"[..], &mut arena)
+            .unwrap();
+        let first_aa = first_arena.key_value(0).unwrap();
+        let second_aa = second_arena.key_value(0).unwrap();
+        assert!(first_aa.shares_storage_with(&second_aa));
+        assert!(!first_aa.shares_storage_with(&aa));
+
+        // Empty keys and values never share storage.
+        let empty = QEntitiesParseOptions::new()
+            .parse(&br#"{ "" "" }"#[..])
+            .unwrap();
+        let empty = empty.key_value(0).unwrap();
+        assert!(!empty.shares_storage_with(&empty));
+    }
+
+    #[test]
+    fn pairs_iter() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ a 1 b 2 c 3 d 4 }{}"[..])
+            .unwrap();
+        let entity = entities.get(0).unwrap();
+
+        let mut pairs = entity.pairs();
+        assert_eq!(pairs.len(), 4);
+        assert_eq!(pairs.next(), Some((&b"a"[..], &b"1"[..])));
+        assert_eq!(pairs.next_back(), Some((&b"d"[..], &b"4"[..])));
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs.size_hint(), (2, Some(2)));
+        assert_eq!(pairs.nth_back(1), Some((&b"b"[..], &b"2"[..])));
+        assert_eq!(pairs.len(), 0);
+        assert_eq!(pairs.next(), None);
+
+        let reversed: Vec<_> = entity.pairs().rev().map(|(key, _)| key).collect();
+        assert_eq!(reversed, [&b"d"[..], b"c", b"b", b"a"]);
+        assert!(entity
+            .pairs()
+            .eq(entity.iter().map(|kv| (kv.key(), kv.value()))));
+        assert_eq!(entity.pairs().nth(2), Some((&b"c"[..], &b"3"[..])));
+        assert_eq!(entity.pairs().count(), 4);
+
+        let empty = entities.get(1).unwrap();
+        assert_eq!(empty.pairs().len(), 0);
+        assert_eq!(empty.pairs().next(), None);
+        assert_eq!(empty.pairs().next_back(), None);
+        assert_eq!(empty.pairs().nth(1), None);
+        assert_eq!(empty.pairs().count(), 0);
+    }
+
+    #[test]
+    fn iter_pairs_empty_entities() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{}{ a 1 }{}{}{ b 2 c 3 }{}"[..])
+            .unwrap();
+        let pairs: Vec<_> = entities.iter_pairs().collect();
+        assert_eq!(
+            pairs,
+            [(1, &b"a"[..], &b"1"[..]), (4, b"b", b"2"), (4, b"c", b"3")]
+        );
+
+        let entities = QEntitiesParseOptions::new().parse(&b"{}{}"[..]).unwrap();
+        assert_eq!(entities.iter_pairs().next(), None);
+        let entities = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert_eq!(entities.iter_pairs().next(), None);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_iter() {
+        use rayon::prelude::*;
+
+        let src = b"{ id 0 a b }{ id 1 }{}{ id 3 c d e f }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        let par_entities: Vec<_> = entities.par_iter().collect();
+        assert!(par_entities.into_iter().eq(entities.iter()));
+        assert_eq!(entities.par_iter().len(), 4);
+        assert_eq!(
+            entities
+                .par_iter()
+                .map(|entity| entity.len())
+                .sum::<usize>(),
+            entities.key_values.len()
+        );
+        assert_eq!(
+            entities
+                .par_iter()
+                .rev()
+                .map(|entity| entity.index())
+                .collect::<Vec<_>>(),
+            [3, 2, 1, 0]
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad a.wad }{}{ classname light light 300 }"[..])
+            .unwrap();
+        let summary = entities.summary();
+        assert_eq!(summary.entities(), 3);
+        assert_eq!(summary.key_values(), 4);
+        assert_eq!(
+            summary.byte_footprint(),
+            "classnameworldspawnwada.wadlight300".len()
+        );
+        assert_eq!(summary.to_string(), "3 entities, 4 key-values, 35 bytes");
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert_eq!(
+            empty.summary().to_string(),
+            "0 entities, 0 key-values, 0 bytes"
+        );
+    }
+
+    #[test]
+    fn heap_bytes() {
+        let src = b"{ classname light }\n{ classname light origin \"0 0 0\" }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let word = core::mem::size_of::<usize>();
+        let options = core::mem::size_of::<QEntitiesParseOptions>();
+
+        // 2 entities and 3 key-values of two words each, 4 byte-chunks of three words each, a
+        // single segment of at most four words, and 25 bytes of keys and values.
+        assert_eq!(entities.byte_footprint(), 25);
+        let lower = 2 * 2 * word + 3 * 2 * word + 4 * 3 * word + 2 * word + 25;
+        let upper = lower + 2 * word;
+        assert!((lower..=upper).contains(&entities.heap_bytes()));
+
+        // The line index counts one word per line.
+        let indexed = QEntitiesParseOptions::new()
+            .with_record_line_index(true)
+            .parse(&src[..])
+            .unwrap();
+        assert_eq!(indexed.heap_bytes(), entities.heap_bytes() + 2 * 8);
+
+        // Retained options count their own size.
+        let retaining = QEntitiesParseOptions::new()
+            .with_retain_parse_options(true)
+            .parse(&src[..])
+            .unwrap();
+        assert_eq!(retaining.heap_bytes(), entities.heap_bytes() + options);
+
+        // An empty collection still has an empty segment.
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert!(empty.heap_bytes() <= 4 * word);
+    }
+
+    #[test]
+    fn get_back() {
+        let src = b"{ a 1 b 2 c 3 }{}";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        let entity = entities.get(0).unwrap();
+        for index in 0..entity.len() {
+            assert_eq!(entity.get_back(index), entity.get(entity.len() - 1 - index));
+        }
+        assert_eq!(entity.get_back(0).unwrap().key(), b"c");
+        assert_eq!(entity.get_back(2).unwrap().key(), b"a");
+        assert_eq!(entity.get_back(3), None);
+        assert_eq!(entity.get_back(usize::MAX), None);
+
+        let empty = entities.get(1).unwrap();
+        assert_eq!(empty.get_back(0), None);
+    }
+
+    #[test]
+    fn split_at() {
+        let src = b"{ id 0 }{ id 1 }{ id 2 }{ id 3 }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        for mid in 0..=entities.len() {
+            let (left, right) = entities.split_at(mid);
+            assert_eq!(left.len(), mid);
+            assert_eq!(right.len(), entities.len() - mid);
+            assert_eq!(left.is_empty(), mid == 0);
+            assert_eq!(right.is_empty(), mid == entities.len());
+            assert!(left.iter().chain(right).eq(entities.iter()));
+            assert_eq!(left.get(mid), None);
+            assert_eq!(right.get(entities.len() - mid), None);
+            if let Some(entity) = right.get(0) {
+                assert_eq!(entity.index(), mid);
+            }
+        }
+
+        // Views can be split further.
+        let (_, right) = entities.split_at(1);
+        let (middle, last) = right.split_at(2);
+        assert!(middle
+            .iter()
+            .map(|entity| entity.value_of(b"id").unwrap())
+            .eq([b"1", b"2"]));
+        assert_eq!(last.get(0).unwrap().index(), 3);
+        assert_eq!(
+            format!("{middle:?}"),
+            format!("{:?}", entities.get_many([1, 2]).unwrap())
+        );
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        let (left, right) = empty.split_at(0);
+        assert!(left.is_empty() && right.is_empty());
+        assert_eq!(left.iter().next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_at_out_of_bounds() {
+        let entities = QEntitiesParseOptions::new().parse(&b"{ }"[..]).unwrap();
+        let _ = entities.split_at(2);
+    }
+
+    #[test]
+    fn parse_options() {
+        let src = b"{ classname light }";
+        let parse_opts = QEntitiesParseOptions::quake()
+            .with_single_quoted_strings(true)
+            .with_escape_options(Some(
+                parse::QEntitiesParseEscapeOptions::new().with_double_quotes(true),
+            ))
+            .with_retain_parse_options(true);
+        let entities = parse_opts.parse(&src[..]).unwrap();
+
+        // The retained options parse identically to the original options.
+        let retained = entities.parse_options().unwrap();
+        let src = br#"{ classname 'a "b' message "\"c\"" }"#;
+        assert!(retained
+            .parse(&src[..])
+            .unwrap()
+            .iter()
+            .eq(parse_opts.parse(&src[..]).unwrap().iter()));
+        assert!(!QEntitiesParseOptions::new()
+            .parse(&src[..])
+            .is_ok_and(|other| other.iter().eq(retained.parse(&src[..]).unwrap().iter())));
+
+        // Options are only retained if asked to be.
+        assert!(QEntitiesParseOptions::quake()
+            .parse(&src[..])
+            .unwrap()
+            .parse_options()
+            .is_none());
+
+        // Every means of parsing a collection retains the options.
+        let mut arena = crate::arena::QEntitiesArena::new();
+        assert!(parse_opts
+            .parse_in_arena(&src[..], &mut arena)
+            .unwrap()
+            .parse_options()
+            .is_some());
+        assert!(parse_opts
+            .parse_with_warnings(&src[..])
+            .unwrap()
+            .0
+            .parse_options()
+            .is_some());
+        assert!(parse_opts
+            .parse_multi(&b"{}\0{}"[..], b'\0')
+            .all(|entities| entities.unwrap().parse_options().is_some()));
+
+        // Edited collections keep the options, while other derived collections do not.
+        let mut editor = entities.edit();
+        editor.set_value(0, 0, b"info_null");
+        let entities = editor.finish();
+        assert!(entities.parse_options().is_some());
+        assert!(entities.filter(|_| true).parse_options().is_none());
+        assert!(entities
+            .map_key_values(|k, v| Some((k.into(), v.into())))
+            .parse_options()
+            .is_none());
+        assert!(entities
+            .dedup_keys(KeyDedupStrategy::FirstWins)
+            .parse_options()
+            .is_none());
+
+        let mut packed = Vec::new();
+        entities.write_packed(&mut packed).unwrap();
+        assert!(QEntities::from_packed(&packed)
+            .unwrap()
+            .parse_options()
+            .is_none());
+    }
+
+    #[test]
+    fn debug_truncation() {
+        let limit = QEntities::DEBUG_ENTITY_LIMIT;
+        let src = "{ classname light }".repeat(limit + 5);
+        let entities = QEntitiesParseOptions::new().parse(src.as_bytes()).unwrap();
+        let entity_debug = format!("{:?}", entities.get(0).unwrap());
+
+        // Only the first entities are listed.
+        let debug = format!("{entities:?}");
+        let expected_entities = vec![entity_debug.as_str(); limit].join(", ");
+        assert_eq!(debug, format!("[{expected_entities}, \u{2026} (+5 more)]"));
+
+        // The alternate form lists every entity.
+        let alternate = format!("{entities:#?}");
+        assert!(!alternate.contains('\u{2026}'));
+        assert_eq!(alternate.matches("classname").count(), limit + 5);
+
+        // Collections up to the limit are not truncated.
+        let (head, tail) = entities.split_at(limit);
+        assert_eq!(format!("{head:?}"), format!("[{expected_entities}]"));
+        assert_eq!(
+            format!("{tail:?}"),
+            format!("[{}]", vec![entity_debug; 5].join(", "))
+        );
+        assert_eq!(format!("{:?}", entities.filter(|_| false)), "[]");
+    }
+
+    #[test]
+    fn into_iter_owned() {
+        let src = b"{ classname worldspawn } { a 1 b 2 a 1 } { }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let expected: Vec<Vec<(Vec<u8>, Vec<u8>)>> = entities
+            .iter()
+            .map(|entity| {
+                entity
+                    .pairs()
+                    .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                    .collect()
+            })
+            .collect();
+
+        let mut iter = entities.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back().unwrap(), expected[2]);
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.collect::<Vec<_>>(), expected[..2]);
+
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let owned: Vec<_> = entities.into_iter().collect();
+        assert_eq!(owned, expected);
+        assert_eq!(
+            owned[1],
+            [
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"a".to_vec(), b"1".to_vec()),
+            ]
+        );
+
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let mut iter = entities.into_iter();
+        assert_eq!(iter.nth(1).unwrap(), expected[1]);
+        assert_eq!(iter.nth_back(0).unwrap(), expected[2]);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn key_value_by_global_index() {
+        let src = b"{ classname worldspawn wad a.wad } {} { classname light light 300 } { a b }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        // Mapping each (entity, key-value) index pair to a global index and back.
+        let mut global_index = 0;
+        for entity in entities.iter() {
+            for (kv_index, kv) in entity.iter().enumerate() {
+                assert_eq!(kv.global_index(), global_index);
+                let global_kv = entities.key_value(global_index).unwrap();
+                assert_eq!(global_kv, kv);
+                assert_eq!(global_kv.global_index(), global_index);
+                assert_eq!(global_kv.index_in_entity(), kv_index);
+                global_index += 1;
+            }
+        }
+        assert_eq!(global_index, 5);
+        assert!(entities.key_value(global_index).is_none());
+        assert!(entities.key_value(usize::MAX).is_none());
+
+        // Mapping each global index back to the (entity, key-value) index pair that it refers to.
+        let pairs: Vec<_> = (0..global_index)
+            .map(|global_index| {
+                let kv = entities.key_value(global_index).unwrap();
+                let entity = entities
+                    .iter()
+                    .find(|entity| {
+                        entity
+                            .iter()
+                            .any(|other| other.global_index() == global_index)
+                    })
+                    .unwrap();
+                (entity.index(), kv.index_in_entity())
+            })
+            .collect();
+        assert_eq!(pairs, [(0, 0), (0, 1), (2, 0), (2, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn get_many() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ id 0 }{ id 1 }{ id 2 }"[..])
+            .unwrap();
+
+        let [a, b, c, d] = entities.get_many([1, 2, 0, 1]).unwrap();
+        assert_eq!(a, entities.get(1).unwrap());
+        assert_eq!(b, entities.get(2).unwrap());
+        assert_eq!(c, entities.get(0).unwrap());
+        assert_eq!(d, entities.get(1).unwrap());
+        assert_eq!(entities.get_many([]), Some([]));
+
+        assert!(entities.get_many([3]).is_none());
+        assert!(entities.get_many([0, 1, usize::MAX]).is_none());
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert!(empty.get_many([0]).is_none());
+        assert_eq!(empty.get_many([]), Some([]));
+    }
+
+    #[test]
+    fn entity_maps() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ a 1 b 2 a 3 c 4 a 5 }{}"[..])
+            .unwrap();
+
+        // The maps borrow from the collection rather than the entity reference.
+        let (map, multimap) = {
+            let entity = entities.get(0).unwrap();
+            (entity.to_map(), entity.to_multimap())
+        };
+        let entity = entities.get(0).unwrap();
+        assert_eq!(map.len(), 3);
+        for (key, value) in &map {
+            assert_eq!(entity.value_of(key), Some(*value));
+        }
+        assert_eq!(multimap.len(), 3);
+        assert_eq!(multimap[&b"a"[..]], [&b"1"[..], b"3", b"5"]);
+        assert_eq!(multimap[&b"b"[..]], [&b"2"[..]]);
+        assert_eq!(multimap[&b"c"[..]], [&b"4"[..]]);
+
+        let empty = entities.get(1).unwrap();
+        assert!(empty.to_map().is_empty());
+        assert!(empty.to_multimap().is_empty());
+    }
+
+    #[test]
+    fn distinct_keys() {
+        let src = b"{ classname worldspawn wad a.wad }{ classname light origin \"0 0 0\" light 300 }{}{ origin \"1 1 1\" wad b.wad classname light classname light }";
+        for intern in [true, false] {
+            let entities = QEntitiesParseOptions::new()
+                .with_intern(intern)
+                .parse(&src[..])
+                .unwrap();
+            let keys = entities.distinct_keys().collect::<Vec<_>>();
+            assert_eq!(keys, [&b"classname"[..], b"wad", b"origin", b"light"]);
+        }
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert_eq!(empty.distinct_keys().count(), 0);
+    }
+
+    #[test]
+    fn byte_footprint() {
+        let src = b"{ classname worldspawn wad a.wad }{ classname light origin \"0 0 0\" }{ classname info_null targetname null }{ classname light origin \"1 1 1\" }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        assert_eq!(
+            entities.byte_footprint(),
+            "classnameworldspawnwada.wadlightorigin0 0 0info_nulltargetnamenull1 1 1".len()
+        );
+
+        let lights = entities.filter(|entity| entity.value_of(b"classname") == Some(b"light"));
+        assert_eq!(lights.len(), 2);
+        assert_eq!(
+            lights.byte_footprint(),
+            "classnamelightorigin0 0 01 1 1".len()
+        );
+
+        let none = entities.filter(|_| false);
+        assert_eq!(none.byte_footprint(), 0);
+
+        let mapped = entities.map_key_values(|key, value| {
+            (key == b"classname").then_some((Cow::Borrowed(key), Cow::Borrowed(value)))
+        });
+        assert_eq!(
+            mapped.byte_footprint(),
+            "classnameworldspawnlightinfo_null".len()
+        );
+
+        // Repeated keys and values are counted each time without interning.
+        let not_interned = QEntitiesParseOptions::new()
+            .with_intern(false)
+            .parse(&b"{ classname light }{ classname light }"[..])
+            .unwrap();
+        assert_eq!(
+            not_interned.byte_footprint(),
+            "classnamelightclassnamelight".len()
+        );
+
+        // Replaced values are still counted after editing.
+        let mut editor = lights.edit();
+        editor.set_value(0, 1, b"0 0 8");
+        let edited = editor.finish();
+        assert_eq!(
+            edited.byte_footprint(),
+            "classnamelightorigin0 0 01 1 10 0 8".len()
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad a.wad }{ classname func_detail model *1 }{ classname light light 300 }{ classname func_detail model *2 }"[..])
+            .unwrap();
+
+        let filtered =
+            entities.filter(|entity| entity.value_of(b"classname") != Some(b"func_detail"));
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.get(0).unwrap().pairs().eq([
+            (&b"classname"[..], &b"worldspawn"[..]),
+            (&b"wad"[..], &b"a.wad"[..]),
+        ]));
+        assert!(filtered.get(1).unwrap().pairs().eq([
+            (&b"classname"[..], &b"light"[..]),
+            (&b"light"[..], &b"300"[..]),
+        ]));
+
+        // Only the strings of the retained entities are interned, and `light` is interned once
+        // despite being used as both a key and a value.
+        assert_eq!(filtered.byte_chunks.len(), 6);
+
+        assert!(entities.filter(|_| false).is_empty());
+        assert_eq!(entities.filter(|_| true).len(), entities.len());
+    }
+
+    #[test]
+    fn map_key_values() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn _wad a.wad }{ classname light origin \" 0  0 8 \" _color red }"[..])
+            .unwrap();
+
+        let renamed = entities.map_key_values(|key, value| {
+            Some((
+                Cow::Borrowed(key.strip_prefix(b"_").unwrap_or(key)),
+                Cow::Borrowed(value),
+            ))
+        });
+        assert!(renamed.get(0).unwrap().pairs().eq([
+            (&b"classname"[..], &b"worldspawn"[..]),
+            (&b"wad"[..], &b"a.wad"[..]),
+        ]));
+        assert!(renamed.get(1).unwrap().pairs().eq([
+            (&b"classname"[..], &b"light"[..]),
+            (&b"origin"[..], &b" 0  0 8 "[..]),
+            (&b"color"[..], &b"red"[..]),
+        ]));
+
+        let rewritten = entities.map_key_values(|key, value| {
+            let value = if key == b"origin" {
+                Cow::Owned(
+                    value
+                        .split(|byte| *byte == b' ')
+                        .filter(|component| !component.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(&b' '),
+                )
+            } else {
+                Cow::Borrowed(value)
+            };
+            Some((Cow::Borrowed(key), value))
+        });
+        assert_eq!(
+            rewritten.get(1).unwrap().value_of(b"origin"),
+            Some(&b"0 0 8"[..])
+        );
+        assert_eq!(
+            rewritten.get(0).unwrap().value_of(b"_wad"),
+            Some(&b"a.wad"[..])
+        );
+
+        let dropped = entities.map_key_values(|key, value| {
+            (!key.starts_with(b"_")).then_some((Cow::Borrowed(key), Cow::Borrowed(value)))
+        });
+        assert_eq!(dropped.len(), 2);
+        assert!(dropped
+            .get(0)
+            .unwrap()
+            .pairs()
+            .eq([(&b"classname"[..], &b"worldspawn"[..])]));
+        assert!(dropped.get(1).unwrap().pairs().eq([
+            (&b"classname"[..], &b"light"[..]),
+            (&b"origin"[..], &b" 0  0 8 "[..]),
+        ]));
+        assert_eq!(dropped.byte_chunks.len(), 5);
+
+        let emptied = entities.map_key_values(|_, _| None);
+        assert_eq!(emptied.len(), 2);
+        assert!(emptied.iter().all(|entity| entity.is_empty()));
+    }
+
+    #[test]
+    fn dedup_keys() {
+        let src = b"{ a 1 b 2 a 3 c 4 b 5 a 6 }{}{ a 1 b 2 }";
+        for intern in [true, false] {
+            let entities = QEntitiesParseOptions::new()
+                .with_intern(intern)
+                .parse(&src[..])
+                .unwrap();
+            let first_wins = entities.dedup_keys(KeyDedupStrategy::FirstWins);
+            assert_eq!(first_wins.len(), 3);
+            assert!(first_wins.get(0).unwrap().pairs().eq([
+                (&b"a"[..], &b"1"[..]),
+                (&b"b"[..], &b"2"[..]),
+                (&b"c"[..], &b"4"[..]),
+            ]));
+            assert!(first_wins.get(1).unwrap().is_empty());
+            assert_eq!(first_wins.get(2), entities.get(2));
+
+            let last_wins = entities.dedup_keys(KeyDedupStrategy::LastWins);
+            assert_eq!(last_wins.len(), 3);
+            assert!(last_wins.get(0).unwrap().pairs().eq([
+                (&b"c"[..], &b"4"[..]),
+                (&b"b"[..], &b"5"[..]),
+                (&b"a"[..], &b"6"[..]),
+            ]));
+            assert!(last_wins.get(1).unwrap().is_empty());
+            assert_eq!(last_wins.get(2), entities.get(2));
+        }
+    }
+
+    #[test]
+    fn ref_indices() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{}{ a 1 b 2 }{}{ c 3 }{ d 4 e 5 f 6 }{}"[..])
+            .unwrap();
+
+        let mut global_index = 0;
+        for (entity_index, entity) in entities.iter().enumerate() {
+            assert_eq!(entity.index(), entity_index);
+            for (kv_index, kv) in entity.iter().enumerate() {
+                assert_eq!(kv.index_in_entity(), kv_index);
+                assert_eq!(kv.global_index(), global_index);
+                global_index += 1;
+            }
+        }
+        assert_eq!(global_index, 6);
+
+        let entity = entities.get(4).unwrap();
+        assert_eq!(entity.index(), 4);
+        let kv = entity.get(2).unwrap();
+        assert_eq!((kv.index_in_entity(), kv.global_index()), (2, 5));
     }
 }
@@ -0,0 +1,462 @@
+//! Module containing the implementation for writing and reading [`QEntities`] collections using a
+//! packed binary layout, as well as a versioned binary format built upon it.
+//!
+//! The packed layout is a direct representation of the internal arrays of a collection and as such
+//! can be read back without any lexing or interning. All integers are written as little-endian
+//! `u64`s, and the layout is as follows:
+//!
+//! | Field                  | Size                |
+//! |------------------------|---------------------|
+//! | Entity count (`E`)     | 8                   |
+//! | Entities               | `E` × 16            |
+//! | Key-value count (`K`)  | 8                   |
+//! | Key-values             | `K` × 16            |
+//! | Byte-chunk count (`C`) | 8                   |
+//! | Byte-chunks            | `C` × 16            |
+//! | Byte count (`B`)       | 8                   |
+//! | Bytes                  | `B`                 |
+//!
+//! Each entity is written as the index of its first key-value followed by its number of
+//! key-values, where the key-values of each entity must immediately follow those of the preceding
+//! entity. Each key-value is written as the index of its key's byte-chunk followed by the index
+//! of its value's byte-chunk. Each byte-chunk is written as its offset within the bytes followed by
+//! its length.
+//!
+//! Because every field preceding the bytes is a multiple of eight bytes in size, the tables of a
+//! packed collection remain eight byte aligned relative to the start of the packed collection.
+//!
+//! # Binary Format
+//! The binary format is intended for caching collections, and consists of an eight byte header
+//! followed by a collection in the packed layout. The header is composed of the magic bytes
+//! `QENT` followed by the version of the format as a little-endian `u32`. The current version is
+//! [`BINARY_VERSION`], and reading a different version is an error.
+
+use super::byte_chunk::{ByteChunks, PackedReader};
+use super::{QEntities, QEntityInfo, QEntityKeyValueInfo};
+use core::fmt;
+use std::{error, io};
+
+/// An error that can occur when attempting to read a [`QEntities`] collection from packed bytes.
+#[derive(Debug)]
+pub struct QEntitiesFromPackedError {
+    /// Sealant to prevent users from constructing this type.
+    _sealed: (),
+}
+
+impl QEntitiesFromPackedError {
+    /// Creates a new [`QEntitiesFromPackedError`].
+    #[inline(always)]
+    fn new() -> Self {
+        Self { _sealed: () }
+    }
+}
+
+impl fmt::Display for QEntitiesFromPackedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid packed q-entities")
+    }
+}
+
+impl error::Error for QEntitiesFromPackedError {
+    #[inline(always)]
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+/// The magic bytes at the start of the [binary format](crate::packed#binary-format).
+const BINARY_MAGIC: [u8; 4] = *b"QENT";
+
+/// The version of the [binary format](crate::packed#binary-format) that is written and can be
+/// read.
+pub const BINARY_VERSION: u32 = 1;
+
+/// The internal error enumeration for errors that can occur while reading the binary format.
+#[derive(Debug)]
+enum ReadBinaryError {
+    /// An I/O error occured.
+    Io(io::Error),
+    /// The magic bytes were incorrect.
+    InvalidMagic,
+    /// The version was not supported.
+    UnsupportedVersion(u32),
+    /// The packed collection was invalid.
+    InvalidPacked,
+}
+
+/// An error that can occur when reading a [`QEntities`] collection from the
+/// [binary format](crate::packed#binary-format).
+#[derive(Debug)]
+pub struct QEntitiesReadBinaryError {
+    repr: ReadBinaryError,
+}
+
+/// A discriminant for a kind of error that can occur when reading a [`QEntities`] collection from
+/// the [binary format](crate::packed#binary-format).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QEntitiesReadBinaryErrorKind {
+    /// An I/O error occured.
+    Io,
+    /// The magic bytes were incorrect.
+    InvalidMagic,
+    /// The version was not supported.
+    UnsupportedVersion,
+    /// The packed collection was invalid.
+    InvalidPacked,
+}
+
+impl QEntitiesReadBinaryError {
+    /// Gets the error's kind.
+    #[inline]
+    pub fn kind(&self) -> QEntitiesReadBinaryErrorKind {
+        match self.repr {
+            ReadBinaryError::Io(_) => QEntitiesReadBinaryErrorKind::Io,
+            ReadBinaryError::InvalidMagic => QEntitiesReadBinaryErrorKind::InvalidMagic,
+            ReadBinaryError::UnsupportedVersion(_) => {
+                QEntitiesReadBinaryErrorKind::UnsupportedVersion
+            }
+            ReadBinaryError::InvalidPacked => QEntitiesReadBinaryErrorKind::InvalidPacked,
+        }
+    }
+
+    /// Gets the unsupported version that was read, if the error is due to an unsupported version.
+    #[inline]
+    pub fn unsupported_version(&self) -> Option<u32> {
+        match self.repr {
+            ReadBinaryError::UnsupportedVersion(version) => Some(version),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for QEntitiesReadBinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            ReadBinaryError::Io(e) => write!(f, "io error: {e}"),
+            ReadBinaryError::InvalidMagic => write!(f, "invalid binary q-entities magic"),
+            ReadBinaryError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported binary q-entities version {version} (expected {BINARY_VERSION})",
+            ),
+            ReadBinaryError::InvalidPacked => write!(f, "invalid packed q-entities"),
+        }
+    }
+}
+
+impl error::Error for QEntitiesReadBinaryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.repr {
+            ReadBinaryError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReadBinaryError> for QEntitiesReadBinaryError {
+    #[inline]
+    fn from(value: ReadBinaryError) -> Self {
+        Self { repr: value }
+    }
+}
+
+impl From<io::Error> for QEntitiesReadBinaryError {
+    #[inline]
+    fn from(value: io::Error) -> Self {
+        Self {
+            repr: ReadBinaryError::Io(value),
+        }
+    }
+}
+
+impl QEntities {
+    /// Writes the collection using the [binary format](crate::packed#binary-format).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::QEntities;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .parse(&b"{ classname worldspawn }"[..])
+    ///     .unwrap();
+    ///
+    /// let mut binary = Vec::new();
+    /// entities.write_binary(&mut binary).unwrap();
+    ///
+    /// let read = QEntities::read_binary(&mut &binary[..]).unwrap();
+    /// assert_eq!(read.worldspawn(), entities.worldspawn());
+    /// ```
+    pub fn write_binary<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&BINARY_MAGIC)?;
+        w.write_all(&BINARY_VERSION.to_le_bytes())?;
+        self.write_packed(w)
+    }
+
+    /// Reads a collection using the [binary format](crate::packed#binary-format).
+    ///
+    /// The reader is read until its end, and the read collection never has a
+    /// [line index](Self::line_index).
+    pub fn read_binary<R: io::Read>(r: &mut R) -> Result<Self, QEntitiesReadBinaryError> {
+        let mut header = [0u8; 8];
+        r.read_exact(&mut header)?;
+
+        let (magic, version) = header.split_at(4);
+        if magic != BINARY_MAGIC {
+            return Err(ReadBinaryError::InvalidMagic.into());
+        }
+
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != BINARY_VERSION {
+            return Err(ReadBinaryError::UnsupportedVersion(version).into());
+        }
+
+        let mut packed = Vec::new();
+        r.read_to_end(&mut packed)?;
+        Self::from_packed(&packed).map_err(|_| ReadBinaryError::InvalidPacked.into())
+    }
+
+    /// Writes the collection using the [packed layout](crate::packed).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::QEntities;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .parse(&b"{ classname worldspawn }"[..])
+    ///     .unwrap();
+    ///
+    /// let mut packed = Vec::new();
+    /// entities.write_packed(&mut packed).unwrap();
+    ///
+    /// let unpacked = QEntities::from_packed(&packed).unwrap();
+    /// assert_eq!(unpacked.len(), 1);
+    ///
+    /// let entity = unpacked.get(0).unwrap();
+    /// let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"classname");
+    /// assert_eq!(value, b"worldspawn");
+    /// ```
+    pub fn write_packed<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&(self.entities.len() as u64).to_le_bytes())?;
+        for entity in self.entities.iter() {
+            w.write_all(&(entity.first_kv as u64).to_le_bytes())?;
+            w.write_all(&(entity.kvs_length as u64).to_le_bytes())?;
+        }
+
+        w.write_all(&(self.key_values.len() as u64).to_le_bytes())?;
+        for kv in self.key_values.iter() {
+            w.write_all(&(kv.key_chunk as u64).to_le_bytes())?;
+            w.write_all(&(kv.value_chunk as u64).to_le_bytes())?;
+        }
+
+        self.byte_chunks.write_packed(w)
+    }
+
+    /// Reads a collection using the [packed layout](crate::packed).
+    ///
+    /// The packed bytes are fully validated such that a successfully read collection is always
+    /// safe to use, and are copied into the collection such that the packed bytes do not need to be
+    /// retained.
+    ///
+    /// The returned collection never has a [line index](Self::line_index).
+    ///
+    /// # Errors
+    /// An error is returned if the packed bytes are truncated, have trailing bytes, contain any
+    /// indices that are out of bounds, or describe entities whose key-values are not contiguous.
+    pub fn from_packed(packed: &[u8]) -> Result<Self, QEntitiesFromPackedError> {
+        let mut reader = PackedReader::new(packed);
+        Self::read_packed(&mut reader)
+            .filter(|_| reader.remaining() == 0)
+            .ok_or_else(QEntitiesFromPackedError::new)
+    }
+
+    /// Reads a collection using the [packed layout](crate::packed) from the front of a packed
+    /// reader.
+    ///
+    /// Returns [`None`] if the packed bytes do not describe a valid collection.
+    pub(crate) fn read_packed(reader: &mut PackedReader) -> Option<Self> {
+        let entities_len = reader.read_len()?;
+        let mut entities = Vec::with_capacity(entities_len.min(reader.remaining() / 16));
+        for _ in 0..entities_len {
+            entities.push(QEntityInfo {
+                first_kv: reader.read_len()?,
+                kvs_length: reader.read_len()?,
+            });
+        }
+
+        let kvs_len = reader.read_len()?;
+        let mut key_values = Vec::with_capacity(kvs_len.min(reader.remaining() / 16));
+        for _ in 0..kvs_len {
+            key_values.push(QEntityKeyValueInfo {
+                key_chunk: reader.read_len()?,
+                value_chunk: reader.read_len()?,
+            });
+        }
+
+        let byte_chunks = ByteChunks::read_packed(reader)?;
+
+        // Validate that the key-values of entities are contiguous and in order, which additionally
+        // ensures that every key-value index is in bounds.
+        let mut next_kv = 0usize;
+        let entities_valid = entities.iter().all(|entity| {
+            let valid = entity.first_kv == next_kv;
+            next_kv = next_kv.saturating_add(entity.kvs_length);
+            valid
+        }) && next_kv == key_values.len();
+        let kvs_valid = key_values
+            .iter()
+            .all(|kv| kv.key_chunk < byte_chunks.len() && kv.value_chunk < byte_chunks.len());
+
+        (entities_valid && kvs_valid).then(|| Self {
+            entities: entities.into(),
+            key_values: key_values.into(),
+            byte_chunks,
+            line_index: None,
+            comments: None,
+            parse_options: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::QEntitiesParseOptions;
+
+    fn packed(entities: &QEntities) -> Vec<u8> {
+        let mut packed = Vec::new();
+        entities.write_packed(&mut packed).unwrap();
+        packed
+    }
+
+    #[test]
+    fn packed_round_trip() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad a.wad }{}{ classname light light light }"[..])
+            .unwrap();
+
+        let packed = packed(&entities);
+        assert_eq!(
+            packed.len() % 8,
+            (b"classnameworldspawnwada.wadlight".len()) % 8
+        );
+
+        let unpacked = QEntities::from_packed(&packed).unwrap();
+        assert_eq!(unpacked.len(), entities.len());
+        assert_eq!(unpacked.byte_chunks.len(), entities.byte_chunks.len());
+        for (entity, unpacked_entity) in entities.iter().zip(unpacked.iter()) {
+            assert!(entity
+                .iter()
+                .map(|kv| (kv.key(), kv.value()))
+                .eq(unpacked_entity.iter().map(|kv| (kv.key(), kv.value()))));
+        }
+
+        // Packing the unpacked collection must reproduce identical bytes.
+        assert_eq!(self::packed(&unpacked), packed);
+
+        let empty = QEntitiesParseOptions::new().parse(&b""[..]).unwrap();
+        assert!(QEntities::from_packed(&self::packed(&empty))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn packed_rejects_invalid() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn }{ classname light }"[..])
+            .unwrap();
+        let packed = packed(&entities);
+
+        // Every truncation of the packed bytes must be rejected.
+        for len in 0..packed.len() {
+            assert!(
+                QEntities::from_packed(&packed[..len]).is_err(),
+                "truncation to {len} bytes was accepted",
+            );
+        }
+
+        // Trailing bytes must be rejected.
+        let mut trailing = packed.clone();
+        trailing.push(0);
+        assert!(QEntities::from_packed(&trailing).is_err());
+
+        // An entity referring to key-values out of bounds must be rejected.
+        let mut bad_entity = packed.clone();
+        bad_entity[16..24].copy_from_slice(&3u64.to_le_bytes());
+        assert!(QEntities::from_packed(&bad_entity).is_err());
+
+        // Entities whose key-values are not contiguous must be rejected.
+        let mut bad_entity = packed.clone();
+        bad_entity[24..32].copy_from_slice(&0u64.to_le_bytes());
+        assert!(QEntities::from_packed(&bad_entity).is_err());
+
+        // A key-value referring to a byte-chunk out of bounds must be rejected.
+        let mut bad_kv = packed.clone();
+        bad_kv[48..56].copy_from_slice(&4u64.to_le_bytes());
+        assert!(QEntities::from_packed(&bad_kv).is_err());
+
+        // A byte-chunk out of bounds of the bytes must be rejected.
+        let mut bad_chunk = packed.clone();
+        bad_chunk[88..96].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(QEntities::from_packed(&bad_chunk).is_err());
+
+        // Counts far exceeding the packed bytes must be rejected without allocating for them.
+        let mut huge_count = packed;
+        huge_count[0..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(QEntities::from_packed(&huge_count).is_err());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad a.wad }{}{ classname light light light }"[..])
+            .unwrap();
+
+        let mut binary = Vec::new();
+        entities.write_binary(&mut binary).unwrap();
+        assert_eq!(&binary[..4], b"QENT");
+        assert_eq!(&binary[8..], &packed(&entities)[..]);
+
+        let read = QEntities::read_binary(&mut &binary[..]).unwrap();
+        assert!(read.iter().eq(entities.iter()));
+        assert_eq!(read.byte_chunks.len(), entities.byte_chunks.len());
+    }
+
+    #[test]
+    fn binary_rejects_invalid() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn }"[..])
+            .unwrap();
+        let mut binary = Vec::new();
+        entities.write_binary(&mut binary).unwrap();
+
+        let e = QEntities::read_binary(&mut &binary[..7]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesReadBinaryErrorKind::Io);
+
+        let mut bad_magic = binary.clone();
+        bad_magic[0] = b'X';
+        let e = QEntities::read_binary(&mut &bad_magic[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesReadBinaryErrorKind::InvalidMagic);
+
+        let mut bad_version = binary.clone();
+        bad_version[4..8].copy_from_slice(&(BINARY_VERSION + 1).to_le_bytes());
+        let e = QEntities::read_binary(&mut &bad_version[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesReadBinaryErrorKind::UnsupportedVersion);
+        assert_eq!(e.unsupported_version(), Some(BINARY_VERSION + 1));
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "unsupported binary q-entities version {} (expected {BINARY_VERSION})",
+                BINARY_VERSION + 1,
+            ),
+        );
+
+        let e = QEntities::read_binary(&mut &binary[..binary.len() - 1]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesReadBinaryErrorKind::InvalidPacked);
+        assert_eq!(e.unsupported_version(), None);
+    }
+}
@@ -1,17 +1,33 @@
 //! Module containing the types for parsing q-entities files.
 
-use super::byte_chunk::ByteChunksBuilder;
-use super::{QEntities, QEntityInfo, QEntityKeyValueInfo};
+use super::arena::QEntitiesArena;
+use super::builder::QEntitiesBuilder;
+use super::byte_chunk::{ByteChunksBuilder, ByteChunksSink};
+use super::{QEntities, QEntitiesComments, QEntitiesLineIndex};
 use bitflags::bitflags;
 use core::fmt;
 use core::hash::BuildHasher;
-use core::slice;
 use hashbrown::hash_map::DefaultHashBuilder;
 
-use std::{error, io};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::{error, fs, io};
 
 /// Location within a q-entities file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// # Line Endings
+/// A line is ended by a line feed (`\n`), a carriage return (`\r`), or a carriage return followed
+/// by a line feed (`\r\n`), so files using Unix, classic Mac OS, or Windows line endings, or any
+/// mix of them, are all numbered as expected. A line feed followed by a carriage return (`\n\r`)
+/// ends two lines.
+///
+/// The byte following a line ending begins a new line at column one. Columns count bytes rather
+/// than characters, so a tab or a multi-byte UTF-8 character is not treated specially.
+///
+/// Locations are ordered by their offset. Since the line and column of a location are derived from
+/// its offset, locations within the same file are ordered as they appear within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct QEntitiesParserLocation {
     /// Absolute offset from the beginning of the file.
     offset: u64,
@@ -22,6 +38,18 @@ pub struct QEntitiesParserLocation {
 }
 
 impl QEntitiesParserLocation {
+    /// Creates a new location.
+    ///
+    /// Lines and columns are numbered starting from one.
+    #[inline]
+    pub fn new(offset: u64, line: u64, column: u64) -> Self {
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+
     /// Gets the location's absolute offset from the beginning of the file.
     #[inline]
     pub fn offset(&self) -> u64 {
@@ -39,6 +67,35 @@ impl QEntitiesParserLocation {
     pub fn column(&self) -> u64 {
         self.column
     }
+
+    /// Gets the location that is the given number of columns before this location on the same line.
+    ///
+    /// The offset is retreated by the same number of bytes as the column. The retreat saturates at
+    /// the first column of the line.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let e = QEntitiesParseOptions::new().parse(&b"\n  }"[..]).unwrap_err();
+    /// let location = e.location().unwrap();
+    /// assert_eq!((location.offset(), location.line(), location.column()), (3, 2, 3));
+    ///
+    /// let start = location.retreat_columns(2);
+    /// assert_eq!((start.offset(), start.line(), start.column()), (1, 2, 1));
+    /// assert_eq!(location.retreat_columns(100), start);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn retreat_columns(&self, n: u64) -> Self {
+        let n = n.min(self.column.saturating_sub(1));
+        Self {
+            offset: self.offset.saturating_sub(n),
+            line: self.line,
+            column: self.column - n,
+        }
+    }
 }
 
 impl std::fmt::Display for QEntitiesParserLocation {
@@ -52,19 +109,47 @@ impl std::fmt::Display for QEntitiesParserLocation {
 }
 
 /// An error describing an unexpected token within a q-entities file.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QEntitiesUnexpectedTokenError {
     /// The unexpected token's kind.
     kind: QEntitiesTokenKind,
     /// The location of the unexpected token.
     location: QEntitiesParserLocation,
+    /// The kinds of tokens that would have been valid in place of the unexpected token.
+    expected: &'static [QEntitiesTokenKind],
+    /// The bytes that opened and closed entities, by which brace tokens are displayed.
+    entity_delimiters: (u8, u8),
 }
 
 impl QEntitiesUnexpectedTokenError {
     /// Creates a new unexpected token error.
     #[inline]
-    fn new(kind: QEntitiesTokenKind, location: QEntitiesParserLocation) -> Self {
-        Self { kind, location }
+    fn new(
+        kind: QEntitiesTokenKind,
+        location: QEntitiesParserLocation,
+        expected: &'static [QEntitiesTokenKind],
+        entity_delimiters: (u8, u8),
+    ) -> Self {
+        Self {
+            kind,
+            location,
+            expected,
+            entity_delimiters,
+        }
+    }
+
+    /// Writes a quoted description of a kind of token, describing braces by the bytes that opened
+    /// and closed entities.
+    fn fmt_kind(&self, f: &mut fmt::Formatter<'_>, kind: QEntitiesTokenKind) -> fmt::Result {
+        match kind {
+            QEntitiesTokenKind::OpenBrace => {
+                write!(f, "\"{}\"", self.entity_delimiters.0.escape_ascii())
+            }
+            QEntitiesTokenKind::CloseBrace => {
+                write!(f, "\"{}\"", self.entity_delimiters.1.escape_ascii())
+            }
+            _ => write!(f, "\"{kind}\""),
+        }
     }
 
     /// Gets the location at which the unexpected token appeared.
@@ -78,16 +163,110 @@ impl QEntitiesUnexpectedTokenError {
     pub fn kind(&self) -> QEntitiesTokenKind {
         self.kind
     }
+
+    /// Gets the kinds of tokens that would have been valid in place of the unexpected token.
+    ///
+    /// These depend upon where the unexpected token appeared and upon the parse options. For
+    /// example, only an [`OpenBrace`](QEntitiesTokenKind::OpenBrace) is valid between entities.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{
+    ///     QEntitiesParseOptions, QEntitiesTokenKind, QEntitiesUnexpectedTokenError,
+    /// };
+    ///
+    /// let e = QEntitiesParseOptions::new().parse(&b"{ a b } }"[..]).unwrap_err();
+    /// let e = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+    /// assert_eq!(e.kind(), QEntitiesTokenKind::CloseBrace);
+    /// assert_eq!(e.expected(), [QEntitiesTokenKind::OpenBrace]);
+    /// assert_eq!(e.to_string(), r#"expected "{", found "}" token @8 line#1 column#9"#);
+    /// ```
+    #[inline]
+    pub fn expected(&self) -> &[QEntitiesTokenKind] {
+        self.expected
+    }
 }
 
 impl fmt::Display for QEntitiesUnexpectedTokenError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "unexpected \"{}\" token {}", self.kind, self.location)
+        if let Some((last, rest)) = self.expected.split_last() {
+            write!(f, "expected ")?;
+            for (i, &kind) in rest.iter().enumerate() {
+                self.fmt_kind(f, kind)?;
+                let separator = if i + 1 < rest.len() { ", " } else { " or " };
+                write!(f, "{separator}")?;
+            }
+            self.fmt_kind(f, *last)?;
+            write!(f, ", found ")?;
+        } else {
+            write!(f, "unexpected ")?;
+        }
+        self.fmt_kind(f, self.kind)?;
+        write!(f, " token {}", self.location)
     }
 }
 
 impl error::Error for QEntitiesUnexpectedTokenError {}
 
+/// An error describing a key or value within a q-entities file that exceeded its length limit.
+#[derive(Debug, Clone)]
+pub struct QEntitiesLengthError {
+    /// The error's kind, being either [`KeyTooLong`](QEntitiesParseErrorKind::KeyTooLong) or
+    /// [`ValueTooLong`](QEntitiesParseErrorKind::ValueTooLong).
+    kind: QEntitiesParseErrorKind,
+    /// The location at which the key or value began.
+    location: QEntitiesParserLocation,
+    /// The maximum allowed byte length that was exceeded.
+    limit: usize,
+}
+
+impl QEntitiesLengthError {
+    /// Creates a new length error.
+    #[inline]
+    fn new(kind: QEntitiesParseErrorKind, location: QEntitiesParserLocation, limit: usize) -> Self {
+        Self {
+            kind,
+            location,
+            limit,
+        }
+    }
+
+    /// Gets the kind of the error.
+    ///
+    /// This is always either [`KeyTooLong`](QEntitiesParseErrorKind::KeyTooLong) or
+    /// [`ValueTooLong`](QEntitiesParseErrorKind::ValueTooLong).
+    #[inline]
+    pub fn kind(&self) -> QEntitiesParseErrorKind {
+        self.kind
+    }
+
+    /// Gets the location at which the key or value began.
+    #[inline]
+    pub fn location(&self) -> &QEntitiesParserLocation {
+        &self.location
+    }
+
+    /// Gets the maximum allowed byte length that was exceeded.
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for QEntitiesLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.kind == QEntitiesParseErrorKind::KeyTooLong {
+            "key"
+        } else {
+            "value"
+        };
+        write!(f, "{kind} too long {}", self.location)
+    }
+}
+
+impl error::Error for QEntitiesLengthError {}
+
 /// The internal error enumeration for errors that can occur while parsing a q-entities file.
 #[derive(Debug)]
 enum ParseError {
@@ -96,7 +275,12 @@ enum ParseError {
     /// A C style comment was not terminated.
     UnterminatedCStyleComment(QEntitiesParserLocation),
     /// A quoted string was not terminated.
-    UnterminatedQuotedString(QEntitiesParserLocation),
+    UnterminatedQuotedString {
+        /// The location of the opening quote.
+        location: QEntitiesParserLocation,
+        /// The quote byte that opened the string.
+        quote: u8,
+    },
     /// An entity was not terminated.
     UnterminatedEntity(QEntitiesParserLocation),
     /// An escape sequence is invalid.
@@ -104,13 +288,25 @@ enum ParseError {
     /// An unexpected token was encountered.
     UnexpectedToken(QEntitiesUnexpectedTokenError),
     /// A key was too long.
-    KeyTooLong(QEntitiesParserLocation),
+    KeyTooLong(QEntitiesLengthError),
     /// A value was too long,
-    ValueTooLong(QEntitiesParserLocation),
+    ValueTooLong(QEntitiesLengthError),
     /// The file contained too many entities.
     TooManyEntities(QEntitiesParserLocation),
     /// An entity had too many key-value pairs.
     TooManyEntityKeyValues(QEntitiesParserLocation),
+    /// A string contained an illegal control byte.
+    IllegalControlByte(QEntitiesParserLocation),
+    /// The file is in a format that cannot be represented as q-entities.
+    UnsupportedFormat(QEntitiesParserLocation),
+    /// An entity had multiple key-values with the same key.
+    DuplicateKey(QEntitiesParserLocation),
+    /// A key was not followed by the key-value separator.
+    MissingSeparator(QEntitiesParserLocation),
+    /// A key was empty.
+    EmptyKey(QEntitiesParserLocation),
+    /// Content other than whitespace, comments, or another entity followed an entity.
+    TrailingContent(QEntitiesParserLocation),
 }
 
 impl From<io::Error> for ParseError {
@@ -131,6 +327,7 @@ impl From<QEntitiesUnexpectedTokenError> for ParseError {
 #[derive(Debug)]
 pub struct QEntitiesParseError {
     repr: Box<ParseError>,
+    context: Option<Box<[u8]>>,
 }
 
 /// A discriminant for a kind of error that can occur during parsing of a q-entities file.
@@ -138,6 +335,10 @@ pub struct QEntitiesParseError {
 #[non_exhaustive]
 pub enum QEntitiesParseErrorKind {
     /// An I/O error occured.
+    ///
+    /// This includes errors of kind [`io::ErrorKind::OutOfMemory`] which occur when the parsed
+    /// keys and values cannot be stored because their total size would exceed the maximum size of
+    /// an allocation. This is primarily a concern for targets with 32-bit pointers.
     Io,
     /// A C style comment was not terminated.
     UnterminatedCStyleComment,
@@ -157,9 +358,191 @@ pub enum QEntitiesParseErrorKind {
     TooManyEntities,
     /// An entity had too many key-value pairs.
     TooManyEntityKeyValues,
+    /// A string contained an illegal control byte.
+    IllegalControlByte,
+    /// The file began with the `<!--` header of a _KeyValues3_ file, which cannot be represented as
+    /// q-entities.
+    ///
+    /// This is reported under any parse options. Files in other formats, including _KeyValues3_
+    /// files without a header, are reported by whichever error their content first causes.
+    UnsupportedFormat,
+    /// An entity had multiple key-values with the same key.
+    ///
+    /// This only occurs when parsing with the [`Error`](QEntitiesDuplicateKeyPolicy::Error)
+    /// duplicate key policy, and is neither a syntax nor a limit error.
+    DuplicateKey,
+    /// A key was not followed by the
+    /// [key-value separator](QEntitiesParseOptions::key_value_separator).
+    MissingSeparator,
+    /// A key was empty.
+    ///
+    /// This only occurs when parsing with
+    /// [`reject_empty_keys()`](QEntitiesParseOptions::reject_empty_keys) enabled, and is neither a
+    /// syntax nor a limit error.
+    EmptyKey,
+    /// Content other than whitespace, comments, or another entity followed an entity.
+    ///
+    /// This only occurs when parsing with [`strict_eof()`](QEntitiesParseOptions::strict_eof)
+    /// enabled.
+    TrailingContent,
+}
+
+impl QEntitiesParseErrorKind {
+    /// Every kind of error, in declaration order.
+    ///
+    /// Kinds may be added to this list in future releases.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseErrorKind;
+    ///
+    /// let syntax_kinds = QEntitiesParseErrorKind::ALL
+    ///     .iter()
+    ///     .filter(|kind| kind.is_syntax())
+    ///     .map(|kind| kind.as_str())
+    ///     .collect::<Vec<_>>();
+    /// assert!(syntax_kinds.contains(&"unexpected_token"));
+    /// ```
+    pub const ALL: &'static [QEntitiesParseErrorKind] = &[
+        Self::Io,
+        Self::UnterminatedCStyleComment,
+        Self::UnterminatedQuotedString,
+        Self::UnterminatedEntity,
+        Self::InvalidEscapeSequence,
+        Self::UnexpectedToken,
+        Self::KeyTooLong,
+        Self::ValueTooLong,
+        Self::TooManyEntities,
+        Self::TooManyEntityKeyValues,
+        Self::IllegalControlByte,
+        Self::UnsupportedFormat,
+        Self::DuplicateKey,
+        Self::MissingSeparator,
+        Self::EmptyKey,
+        Self::TrailingContent,
+    ];
+
+    /// Gets the name of the kind.
+    ///
+    /// Names are stable across releases and are written in `snake_case`, making them suitable for
+    /// use as keys when logging or looking up localized messages.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseErrorKind;
+    ///
+    /// assert_eq!(QEntitiesParseErrorKind::Io.as_str(), "io");
+    /// assert_eq!(
+    ///     QEntitiesParseErrorKind::TooManyEntityKeyValues.as_str(),
+    ///     "too_many_entity_key_values",
+    /// );
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Io => "io",
+            Self::UnterminatedCStyleComment => "unterminated_c_style_comment",
+            Self::UnterminatedQuotedString => "unterminated_quoted_string",
+            Self::UnterminatedEntity => "unterminated_entity",
+            Self::InvalidEscapeSequence => "invalid_escape_sequence",
+            Self::UnexpectedToken => "unexpected_token",
+            Self::KeyTooLong => "key_too_long",
+            Self::ValueTooLong => "value_too_long",
+            Self::TooManyEntities => "too_many_entities",
+            Self::TooManyEntityKeyValues => "too_many_entity_key_values",
+            Self::IllegalControlByte => "illegal_control_byte",
+            Self::UnsupportedFormat => "unsupported_format",
+            Self::DuplicateKey => "duplicate_key",
+            Self::MissingSeparator => "missing_separator",
+            Self::EmptyKey => "empty_key",
+            Self::TrailingContent => "trailing_content",
+        }
+    }
+
+    /// Checks whether or not the kind is an I/O error.
+    ///
+    /// This is only the case for [`Io`](Self::Io). Such errors are caused by the environment
+    /// rather than by the parsed data.
+    #[inline]
+    pub fn is_io(&self) -> bool {
+        matches!(self, Self::Io)
+    }
+
+    /// Checks whether or not the kind is a syntax error.
+    ///
+    /// This is the case for the following kinds, which are caused by the parsed data not
+    /// adhering to the grammar described by the parse options:
+    /// * [`UnterminatedCStyleComment`](Self::UnterminatedCStyleComment)
+    /// * [`UnterminatedQuotedString`](Self::UnterminatedQuotedString)
+    /// * [`UnterminatedEntity`](Self::UnterminatedEntity)
+    /// * [`InvalidEscapeSequence`](Self::InvalidEscapeSequence)
+    /// * [`UnexpectedToken`](Self::UnexpectedToken)
+    /// * [`IllegalControlByte`](Self::IllegalControlByte)
+    /// * [`UnsupportedFormat`](Self::UnsupportedFormat)
+    /// * [`MissingSeparator`](Self::MissingSeparator)
+    /// * [`TrailingContent`](Self::TrailingContent)
+    #[inline]
+    pub fn is_syntax(&self) -> bool {
+        matches!(
+            self,
+            Self::UnterminatedCStyleComment
+                | Self::UnterminatedQuotedString
+                | Self::UnterminatedEntity
+                | Self::InvalidEscapeSequence
+                | Self::UnexpectedToken
+                | Self::IllegalControlByte
+                | Self::UnsupportedFormat
+                | Self::MissingSeparator
+                | Self::TrailingContent
+        )
+    }
+
+    /// Checks whether or not the kind is a limit error.
+    ///
+    /// This is the case for the following kinds, which are caused by the parsed data exceeding a
+    /// limit imposed by the parse options:
+    /// * [`KeyTooLong`](Self::KeyTooLong)
+    /// * [`ValueTooLong`](Self::ValueTooLong)
+    /// * [`TooManyEntities`](Self::TooManyEntities)
+    /// * [`TooManyEntityKeyValues`](Self::TooManyEntityKeyValues)
+    #[inline]
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            Self::KeyTooLong
+                | Self::ValueTooLong
+                | Self::TooManyEntities
+                | Self::TooManyEntityKeyValues
+        )
+    }
 }
 
 impl QEntitiesParseError {
+    /// Checks whether or not the error is an I/O error.
+    ///
+    /// See [`QEntitiesParseErrorKind::is_io()`] for details.
+    #[inline]
+    pub fn is_io(&self) -> bool {
+        self.kind().is_io()
+    }
+
+    /// Checks whether or not the error is a syntax error.
+    ///
+    /// See [`QEntitiesParseErrorKind::is_syntax()`] for details.
+    #[inline]
+    pub fn is_syntax(&self) -> bool {
+        self.kind().is_syntax()
+    }
+
+    /// Checks whether or not the error is a limit error.
+    ///
+    /// See [`QEntitiesParseErrorKind::is_limit()`] for details.
+    #[inline]
+    pub fn is_limit(&self) -> bool {
+        self.kind().is_limit()
+    }
+
     /// Gets the error's kind.
     #[inline]
     pub fn kind(&self) -> QEntitiesParseErrorKind {
@@ -182,6 +565,12 @@ impl QEntitiesParseError {
             ParseError::TooManyEntityKeyValues { .. } => {
                 QEntitiesParseErrorKind::TooManyEntityKeyValues
             }
+            ParseError::IllegalControlByte { .. } => QEntitiesParseErrorKind::IllegalControlByte,
+            ParseError::UnsupportedFormat { .. } => QEntitiesParseErrorKind::UnsupportedFormat,
+            ParseError::DuplicateKey { .. } => QEntitiesParseErrorKind::DuplicateKey,
+            ParseError::MissingSeparator { .. } => QEntitiesParseErrorKind::MissingSeparator,
+            ParseError::EmptyKey { .. } => QEntitiesParseErrorKind::EmptyKey,
+            ParseError::TrailingContent { .. } => QEntitiesParseErrorKind::TrailingContent,
         }
     }
 
@@ -191,16 +580,140 @@ impl QEntitiesParseError {
         match self.repr.as_ref() {
             ParseError::Io { .. } => None,
             ParseError::UnterminatedCStyleComment(location) => Some(location),
-            ParseError::UnterminatedQuotedString(location) => Some(location),
+            ParseError::UnterminatedQuotedString { location, .. } => Some(location),
             ParseError::UnterminatedEntity(location) => Some(location),
             ParseError::InvalidEscapeSequence(location) => Some(location),
             ParseError::UnexpectedToken(e) => Some(&e.location),
-            ParseError::KeyTooLong(location) => Some(&location),
-            ParseError::ValueTooLong(location) => Some(&location),
-            ParseError::TooManyEntities(location) => Some(&location),
-            ParseError::TooManyEntityKeyValues(location) => Some(&location),
+            ParseError::KeyTooLong(e) => Some(&e.location),
+            ParseError::ValueTooLong(e) => Some(&e.location),
+            ParseError::TooManyEntities(location) => Some(location),
+            ParseError::TooManyEntityKeyValues(location) => Some(location),
+            ParseError::IllegalControlByte(location) => Some(location),
+            ParseError::UnsupportedFormat(location) => Some(location),
+            ParseError::DuplicateKey(location) => Some(location),
+            ParseError::MissingSeparator(location) => Some(location),
+            ParseError::EmptyKey(location) => Some(location),
+            ParseError::TrailingContent(location) => Some(location),
+        }
+    }
+
+    /// Gets the byte offset at which the error occured within the q-entities file.
+    ///
+    /// This is a shortcut for the [`offset()`](QEntitiesParserLocation::offset) of the
+    /// [`location()`](Self::location), and so is [`None`] for [`Io`](QEntitiesParseErrorKind::Io)
+    /// errors.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .parse(&b"{ classname light }\n{ origin"[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.offset(), Some(20));
+    /// ```
+    #[inline]
+    pub fn offset(&self) -> Option<u64> {
+        self.location().map(|location| location.offset())
+    }
+
+    /// Gets the quote byte that opened the string of an
+    /// [`UnterminatedQuotedString`](QEntitiesParseErrorKind::UnterminatedQuotedString) error,
+    /// which is either a double quote (`"`) or, if
+    /// [`single_quoted_strings()`](QEntitiesParseOptions::single_quoted_strings) are enabled, a
+    /// single quote (`'`).
+    ///
+    /// This is [`None`] for errors of every other kind.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let parse_opts = QEntitiesParseOptions::new().with_single_quoted_strings(true);
+    ///
+    /// let e = parse_opts.parse(&b"{ classname \"light }"[..]).unwrap_err();
+    /// assert_eq!(e.quote(), Some(b'"'));
+    ///
+    /// let e = parse_opts.parse(&b"{ classname 'light\" }"[..]).unwrap_err();
+    /// assert_eq!(e.quote(), Some(b'\''));
+    /// assert_eq!(
+    ///     e.to_string(),
+    ///     "unterminated quoted string opened with `'` @12 line#1 column#13",
+    /// );
+    ///
+    /// let e = parse_opts.parse(&b"{ classname"[..]).unwrap_err();
+    /// assert_eq!(e.quote(), None);
+    /// ```
+    #[inline]
+    pub fn quote(&self) -> Option<u8> {
+        match self.repr.as_ref() {
+            ParseError::UnterminatedQuotedString { quote, .. } => Some(*quote),
+            _ => None,
         }
     }
+
+    /// Gets the bytes of the q-entities file that were most recently consumed by the parser before
+    /// the error occured.
+    ///
+    /// This is only available for errors returned by parsing with
+    /// [`capture_error_context()`](QEntitiesParseOptions::capture_error_context) enabled.
+    #[inline]
+    pub fn context(&self) -> Option<&[u8]> {
+        self.context.as_deref()
+    }
+
+    /// Creates a clone of the error if it is not an [`Io`](QEntitiesParseErrorKind::Io) error.
+    ///
+    /// The parse error type does not implement [`Clone`] because [`io::Error`] does not, so errors
+    /// of every other kind must be cloned through this function instead.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let e = QEntitiesParseOptions::new().parse(&b"{ classname"[..]).unwrap_err();
+    /// let clone = e.clone_non_io().unwrap();
+    /// assert_eq!(clone.kind(), e.kind());
+    /// assert_eq!(clone.location(), e.location());
+    /// ```
+    pub fn clone_non_io(&self) -> Option<QEntitiesParseError> {
+        let repr = match self.repr.as_ref() {
+            ParseError::Io(_) => return None,
+            ParseError::UnterminatedCStyleComment(location) => {
+                ParseError::UnterminatedCStyleComment(*location)
+            }
+            ParseError::UnterminatedQuotedString { location, quote } => {
+                ParseError::UnterminatedQuotedString {
+                    location: *location,
+                    quote: *quote,
+                }
+            }
+            ParseError::UnterminatedEntity(location) => ParseError::UnterminatedEntity(*location),
+            ParseError::InvalidEscapeSequence(location) => {
+                ParseError::InvalidEscapeSequence(*location)
+            }
+            ParseError::UnexpectedToken(e) => ParseError::UnexpectedToken(e.clone()),
+            ParseError::KeyTooLong(e) => ParseError::KeyTooLong(e.clone()),
+            ParseError::ValueTooLong(e) => ParseError::ValueTooLong(e.clone()),
+            ParseError::TooManyEntities(location) => ParseError::TooManyEntities(*location),
+            ParseError::TooManyEntityKeyValues(location) => {
+                ParseError::TooManyEntityKeyValues(*location)
+            }
+            ParseError::IllegalControlByte(location) => ParseError::IllegalControlByte(*location),
+            ParseError::UnsupportedFormat(location) => ParseError::UnsupportedFormat(*location),
+            ParseError::DuplicateKey(location) => ParseError::DuplicateKey(*location),
+            ParseError::MissingSeparator(location) => ParseError::MissingSeparator(*location),
+            ParseError::EmptyKey(location) => ParseError::EmptyKey(*location),
+            ParseError::TrailingContent(location) => ParseError::TrailingContent(*location),
+        };
+        Some(Self {
+            repr: Box::new(repr),
+            context: self.context.clone(),
+        })
+    }
 }
 
 impl fmt::Display for QEntitiesParseError {
@@ -210,8 +723,12 @@ impl fmt::Display for QEntitiesParseError {
             ParseError::UnterminatedCStyleComment(location) => {
                 write!(f, "unterminated c style comment {location}")
             }
-            ParseError::UnterminatedQuotedString(location) => {
-                write!(f, "unterminated quoted string {location}")
+            ParseError::UnterminatedQuotedString { location, quote } => {
+                let quote = char::from(*quote);
+                write!(
+                    f,
+                    "unterminated quoted string opened with `{quote}` {location}"
+                )
             }
             ParseError::UnterminatedEntity(location) => {
                 write!(f, "unterminated entity string {location}")
@@ -220,19 +737,38 @@ impl fmt::Display for QEntitiesParseError {
                 write!(f, "invalid escape sequence {location}")
             }
             ParseError::UnexpectedToken(e) => e.fmt(f),
-            ParseError::KeyTooLong(location) => {
-                write!(f, "key too long {location}")
-            }
-            ParseError::ValueTooLong(location) => {
-                write!(f, "key too long {location}")
-            }
+            ParseError::KeyTooLong(e) => e.fmt(f),
+            ParseError::ValueTooLong(e) => e.fmt(f),
             ParseError::TooManyEntities(location) => {
                 write!(f, "too many entities {location}")
             }
             ParseError::TooManyEntityKeyValues(location) => {
                 write!(f, "too many entity key-value pairs {location}")
             }
+            ParseError::IllegalControlByte(location) => {
+                write!(f, "illegal control byte {location}")
+            }
+            ParseError::UnsupportedFormat(location) => {
+                write!(f, "unsupported format {location}")
+            }
+            ParseError::DuplicateKey(location) => {
+                write!(f, "duplicate key {location}")
+            }
+            ParseError::MissingSeparator(location) => {
+                write!(f, "missing key-value separator {location}")
+            }
+            ParseError::EmptyKey(location) => {
+                write!(f, "empty key {location}")
+            }
+            ParseError::TrailingContent(location) => {
+                write!(f, "trailing content after entity {location}")
+            }
+        }?;
+
+        if let Some(context) = &self.context {
+            write!(f, " near {:?}", bstr::BStr::new(context))?;
         }
+        Ok(())
     }
 }
 
@@ -245,10 +781,16 @@ impl error::Error for QEntitiesParseError {
             ParseError::UnterminatedEntity { .. } => None,
             ParseError::InvalidEscapeSequence { .. } => None,
             ParseError::UnexpectedToken(e) => Some(e),
-            ParseError::KeyTooLong { .. } => None,
-            ParseError::ValueTooLong { .. } => None,
+            ParseError::KeyTooLong(e) => Some(e),
+            ParseError::ValueTooLong(e) => Some(e),
             ParseError::TooManyEntities { .. } => None,
             ParseError::TooManyEntityKeyValues { .. } => None,
+            ParseError::IllegalControlByte { .. } => None,
+            ParseError::UnsupportedFormat { .. } => None,
+            ParseError::DuplicateKey { .. } => None,
+            ParseError::MissingSeparator { .. } => None,
+            ParseError::EmptyKey { .. } => None,
+            ParseError::TrailingContent { .. } => None,
         }
     }
 }
@@ -258,6 +800,7 @@ impl From<ParseError> for QEntitiesParseError {
     fn from(value: ParseError) -> Self {
         Self {
             repr: Box::new(value),
+            context: None,
         }
     }
 }
@@ -267,6 +810,7 @@ impl From<io::Error> for QEntitiesParseError {
     fn from(value: io::Error) -> Self {
         Self {
             repr: Box::new(ParseError::from(value)),
+            context: None,
         }
     }
 }
@@ -276,6 +820,7 @@ impl From<QEntitiesUnexpectedTokenError> for QEntitiesParseError {
     fn from(value: QEntitiesUnexpectedTokenError) -> Self {
         Self {
             repr: Box::new(ParseError::from(value)),
+            context: None,
         }
     }
 }
@@ -361,10 +906,93 @@ impl<'a> TryFrom<&'a QEntitiesParseError> for &'a QEntitiesUnexpectedTokenError
     }
 }
 
+impl TryFrom<QEntitiesParseError> for QEntitiesLengthError {
+    type Error = QEntitiesParseErrorCastError;
+
+    #[inline]
+    fn try_from(value: QEntitiesParseError) -> Result<Self, Self::Error> {
+        match *value.repr {
+            ParseError::KeyTooLong(e) | ParseError::ValueTooLong(e) => Ok(e),
+            _ => Err(QEntitiesParseErrorCastError::new()),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a QEntitiesParseError> for &'a QEntitiesLengthError {
+    type Error = QEntitiesParseErrorCastError;
+
+    #[inline]
+    fn try_from(value: &'a QEntitiesParseError) -> Result<Self, Self::Error> {
+        match value.repr.as_ref() {
+            ParseError::KeyTooLong(e) | ParseError::ValueTooLong(e) => Ok(e),
+            _ => Err(QEntitiesParseErrorCastError::new()),
+        }
+    }
+}
+
+/// The kinds of warnings that can be collected while parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QEntitiesWarningKind {
+    /// An entity has no key-values.
+    EmptyEntity,
+    /// A key appears more than once within an entity.
+    ///
+    /// This is only collected when duplicate keys are either
+    /// [kept](QEntitiesDuplicateKeyPolicy::Keep) or
+    /// [replaced](QEntitiesDuplicateKeyPolicy::Replace), as they are otherwise an error.
+    DuplicateKey,
+}
+
+/// A suspicious but valid construct found while parsing a q-entities file.
+///
+/// Unlike a [`QEntitiesParseError`], a warning does not prevent the file from being parsed. See
+/// [`QEntitiesParseOptions::collect_warnings()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QEntitiesWarning {
+    /// The kind of warning.
+    kind: QEntitiesWarningKind,
+    /// Location of the construct that the warning is about.
+    location: QEntitiesParserLocation,
+}
+
+impl QEntitiesWarning {
+    /// Creates a new [`QEntitiesWarning`].
+    #[inline(always)]
+    fn new(kind: QEntitiesWarningKind, location: QEntitiesParserLocation) -> Self {
+        Self { kind, location }
+    }
+
+    /// Gets the kind of warning.
+    #[inline]
+    pub fn kind(&self) -> QEntitiesWarningKind {
+        self.kind
+    }
+
+    /// Gets the location of the construct that the warning is about.
+    ///
+    /// This is the location of the open brace (`{`) of an empty entity, or of the repeated key of
+    /// a duplicate key.
+    #[inline]
+    pub fn location(&self) -> &QEntitiesParserLocation {
+        &self.location
+    }
+}
+
+impl fmt::Display for QEntitiesWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = &self.location;
+        match self.kind {
+            QEntitiesWarningKind::EmptyEntity => write!(f, "empty entity {location}"),
+            QEntitiesWarningKind::DuplicateKey => write!(f, "duplicate key {location}"),
+        }
+    }
+}
+
 bitflags! {
     /// Bit-flags describing the options for parsing a q-entities file.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct QEntitiesParseFlags: u8 {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct QEntitiesParseFlags: u32 {
         /// Whether or not C++ style comments are enabled.
         const CPP_STYLE_COMMENTS = 0x01;
         /// Whether or not C style comments are enabled.
@@ -377,15 +1005,66 @@ bitflags! {
         const ESCAPE = 0x10;
         /// Whether or not double quotes can be escaped.
         const ESCAPE_DOUBLE_QUOTES = 0x20;
-
-        /// Flags that are controlled by [`QEntitiesParseEscapeOptions`].
-        const ESCAPE_OPTIONS = Self::ESCAPE.bits() | Self::ESCAPE_DOUBLE_QUOTES.bits();
+        /// Whether or not the offsets at which lines begin are recorded.
+        const RECORD_LINE_INDEX = 0x40;
+        /// Whether or not only spaces, tabs, carriage returns, and line feeds are whitespace.
+        const NARROW_WHITESPACE = 0x80;
+        /// Whether or not single quoted strings are enabled.
+        const SINGLE_QUOTED_STRINGS = 0x100;
+        /// Whether or not single quotes can be escaped.
+        const ESCAPE_SINGLE_QUOTES = 0x200;
+        /// Whether or not the most recently consumed bytes are captured as the context for errors.
+        const CAPTURE_ERROR_CONTEXT = 0x400;
+        /// Whether or not interning of keys and values is disabled.
+        const DISABLE_INTERNING = 0x800;
+        /// Whether or not braces (`{` and `}`) can be escaped.
+        const ESCAPE_BRACES = 0x1000;
+        /// Whether or not escape sequences are parsed within unquoted strings.
+        const ESCAPE_UNQUOTED = 0x2000;
+        /// Whether or not whitespace can be escaped.
+        const ESCAPE_WHITESPACE = 0x4000;
+        /// Whether or not control bytes that are not whitespace are rejected within strings.
+        const REJECT_CONTROL_BYTES = 0x8000;
+        /// Whether or not a close brace can terminate a key that has no value.
+        const ALLOW_VALUELESS_KEYS = 0x10000;
+        /// Whether or not a duplicate key within an entity results in an error.
+        const DUPLICATE_KEYS_ERROR = 0x20000;
+        /// Whether or not a duplicate key within an entity replaces the value of the prior key.
+        const DUPLICATE_KEYS_REPLACE = 0x40000;
+        /// Whether or not empty keys result in an error.
+        const REJECT_EMPTY_KEYS = 0x80000;
+        /// Whether or not a file that begins with a string is parsed as a single entity without
+        /// braces.
+        const IMPLICIT_TOP_LEVEL_ENTITY = 0x100000;
+        /// Whether or not content that follows the last entity is reported as trailing content.
+        const STRICT_EOF = 0x200000;
+        /// Whether or not warnings are collected.
+        const COLLECT_WARNINGS = 0x400000;
+        /// Whether or not unicode escape sequences (`\uNNNN`) are parsed.
+        const ESCAPE_UNICODE = 0x800000;
+        /// Whether or not a NUL byte (`\0`) is treated as the end of the reader.
+        const NUL_TERMINATES_INPUT = 0x1000000;
+        /// Whether or not comments preceding entities are retained.
+        const RETAIN_COMMENTS = 0x2000000;
+        /// Whether or not keys are converted to ASCII lowercase.
+        const LOWERCASE_KEYS = 0x4000000;
+        /// Whether or not parsed collections retain the options they were parsed with.
+        const RETAIN_PARSE_OPTIONS = 0x8000000;
+
+        /// Flags that are controlled by [`QEntitiesParseEscapeOptions`]. Every flag that can be
+        /// set by the escape options must be included within this mask.
+        const ESCAPE_OPTIONS = Self::ESCAPE.bits()
+            | Self::ESCAPE_DOUBLE_QUOTES.bits()
+            | Self::ESCAPE_SINGLE_QUOTES.bits()
+            | Self::ESCAPE_BRACES.bits()
+            | Self::ESCAPE_WHITESPACE.bits()
+            | Self::ESCAPE_UNICODE.bits();
     }
 }
 
 /// Options that describe the available escape sequences when parsing quoted strings within a
 /// q-entities file.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct QEntitiesParseEscapeOptions {
     /// Bit-flag options.
     flags: QEntitiesParseFlags,
@@ -448,35 +1127,271 @@ impl QEntitiesParseEscapeOptions {
         self.double_quotes(value);
         self
     }
-}
 
-impl Default for QEntitiesParseEscapeOptions {
-    #[inline(always)]
-    fn default() -> Self {
-        Self::new()
+    /// Returns whether or not double quotes (`"`) can be escaped, as changed by
+    /// [`double_quotes()`](Self::double_quotes).
+    #[inline]
+    pub fn double_quotes_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::ESCAPE_DOUBLE_QUOTES)
     }
-}
 
-/// Options that describe the how a q-entities file is parsed.
-///
-/// # Title Specific Presets
-/// Several functions are provided that create an options instance suitable for parsing q-entities
-/// found in a specific title. Users should understand that the intended purpose of these functions
-/// does not extend beyond this goal and as such failure to support the q-entities of their
-/// respective titles is considered a bug.
-///
-/// The options enabled by these functions are documented for the convenience of the user, but
-/// **users should not depend on the options enabled by these functions remaining consistent between
-/// non-major releases**. This caveat, though somewhat burdening to users, is necessary to enable
-/// fixing bugs in these functions outside of major releases.
-///
+    /// Changes whether or not single quotes (`'`) can be escaped.
+    ///
+    /// This is typically paired with
+    /// [`QEntitiesParseOptions::single_quoted_strings()`](QEntitiesParseOptions::single_quoted_strings).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseEscapeOptions, QEntitiesParseOptions};
+    ///
+    /// let src = br#"
+    /// {
+    /// classname worldspawn
+    /// message 'it\'s here'
+    /// }"#;
+    ///
+    /// let mut escape_options = QEntitiesParseEscapeOptions::new();
+    /// escape_options.single_quotes(true);
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .single_quoted_strings(true)
+    ///     .escape_options(Some(escape_options))
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 2);
+    ///
+    /// let (key, value) = entity.get(1).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"message");
+    /// assert_eq!(value, b"it's here");
+    /// ```
+    #[inline]
+    pub fn single_quotes(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::ESCAPE_SINGLE_QUOTES, value);
+        self
+    }
+
+    /// Same as [`single_quotes()`](Self::single_quotes) but takes `self` by value.
+    #[inline]
+    pub fn with_single_quotes(mut self, value: bool) -> Self {
+        self.single_quotes(value);
+        self
+    }
+
+    /// Returns whether or not single quotes (`'`) can be escaped, as changed by
+    /// [`single_quotes()`](Self::single_quotes).
+    #[inline]
+    pub fn single_quotes_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::ESCAPE_SINGLE_QUOTES)
+    }
+
+    /// Changes whether or not braces (`{` and `}`) can be escaped.
+    ///
+    /// The escapable bytes are those of the configured
+    /// [`entity_delimiters()`](QEntitiesParseOptions::entity_delimiters), which are braces by
+    /// default. Unlike other escape sequences, `\{` and `\}` are also honored within unquoted
+    /// strings, which allows literal braces to appear in unquoted strings even when
+    /// [`controls_terminate_unquoted_strings()`](QEntitiesParseOptions::controls_terminate_unquoted_strings)
+    /// is enabled. A back-slash within an unquoted string that is not followed by a brace remains
+    /// part of the string.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseEscapeOptions, QEntitiesParseOptions};
+    ///
+    /// let src = br#"{classname worldspawn message\{braced\}"quoted \{"}"#;
+    ///
+    /// let mut escape_options = QEntitiesParseEscapeOptions::new();
+    /// escape_options.braces(true);
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .controls_terminate_unquoted_strings(true)
+    ///     .escape_options(Some(escape_options))
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 2);
+    ///
+    /// let (key, value) = entity.get(1).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"message{braced}");
+    /// assert_eq!(value, b"quoted {");
+    /// ```
+    #[inline]
+    pub fn braces(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::ESCAPE_BRACES, value);
+        self
+    }
+
+    /// Same as [`braces()`](Self::braces) but takes `self` by value.
+    #[inline]
+    pub fn with_braces(mut self, value: bool) -> Self {
+        self.braces(value);
+        self
+    }
+
+    /// Returns whether or not braces can be escaped, as changed by [`braces()`](Self::braces).
+    #[inline]
+    pub fn braces_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::ESCAPE_BRACES)
+    }
+
+    /// Changes whether or not whitespace can be escaped.
+    ///
+    /// The escapable bytes are those of the configured
+    /// [`whitespace_set()`](QEntitiesParseOptions::whitespace_set). This is typically paired with
+    /// [`QEntitiesParseOptions::escape_unquoted()`] so that escaped whitespace does not terminate
+    /// an unquoted string.
+    #[inline]
+    pub fn whitespace(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::ESCAPE_WHITESPACE, value);
+        self
+    }
+
+    /// Same as [`whitespace()`](Self::whitespace) but takes `self` by value.
+    #[inline]
+    pub fn with_whitespace(mut self, value: bool) -> Self {
+        self.whitespace(value);
+        self
+    }
+
+    /// Returns whether or not whitespace can be escaped, as changed by
+    /// [`whitespace()`](Self::whitespace).
+    #[inline]
+    pub fn whitespace_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::ESCAPE_WHITESPACE)
+    }
+
+    /// Changes whether or not unicode escape sequences (`\uNNNN`) are parsed.
+    ///
+    /// A unicode escape sequence is a `u` followed by exactly four hexadecimal digits, which are
+    /// decoded as a code point of the basic multilingual plane and pushed as its UTF-8 encoding.
+    /// An escape sequence with fewer than four digits or that encodes a surrogate
+    /// (`\uD800` to `\uDFFF`) is an
+    /// [invalid escape sequence](QEntitiesParseErrorKind::InvalidEscapeSequence) at the location of
+    /// its back-slash.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{
+    ///     QEntitiesParseErrorKind, QEntitiesParseEscapeOptions, QEntitiesParseOptions,
+    /// };
+    ///
+    /// let parse_opts = QEntitiesParseOptions::new().with_escape_options(Some(
+    ///     QEntitiesParseEscapeOptions::new().with_unicode_escapes(true),
+    /// ));
+    ///
+    /// let entities = parse_opts
+    ///     .parse(&br#"{ message "Gr\u00FC\u00DFe \u2192 \u65E5" }"#[..])
+    ///     .unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.value_of(b"message"), Some("Grüße → 日".as_bytes()));
+    ///
+    /// let e = parse_opts
+    ///     .parse(&br#"{ message "\u12" }"#[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
+    /// assert_eq!(e.location().unwrap().column(), 12);
+    /// ```
+    #[inline]
+    pub fn unicode_escapes(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::ESCAPE_UNICODE, value);
+        self
+    }
+
+    /// Same as [`unicode_escapes()`](Self::unicode_escapes) but takes `self` by value.
+    #[inline]
+    pub fn with_unicode_escapes(mut self, value: bool) -> Self {
+        self.unicode_escapes(value);
+        self
+    }
+
+    /// Returns whether or not unicode escape sequences (`\uNNNN`) are parsed, as changed by
+    /// [`unicode_escapes()`](Self::unicode_escapes).
+    #[inline]
+    pub fn unicode_escapes_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::ESCAPE_UNICODE)
+    }
+}
+
+impl Default for QEntitiesParseEscapeOptions {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sets of bytes that can be considered whitespace when parsing a q-entities file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QEntitiesWhitespaceSet {
+    /// All bytes considered whitespace by [`u8::is_ascii_whitespace()`]. Namely space (`\x20`), tab
+    /// (`\x09`), line feed (`\x0a`), form feed (`\x0c`), and carriage return (`\x0d`).
+    #[default]
+    Ascii,
+    /// Only space (`\x20`), tab (`\x09`), line feed (`\x0a`), and carriage return (`\x0d`).
+    SpaceTabNewline,
+}
+
+/// The policies for handling an entity that has multiple key-values with the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QEntitiesDuplicateKeyPolicy {
+    /// Every key-value is kept in the order that it appears.
+    #[default]
+    Keep,
+    /// A [`DuplicateKey`](QEntitiesParseErrorKind::DuplicateKey) error is returned at the location
+    /// of the second key-value with the same key.
+    Error,
+    /// The value of a key-value replaces the value of the prior key-value with the same key, which
+    /// retains its position within the entity.
+    Replace,
+}
+
+/// Options that describe the how a q-entities file is parsed.
+///
+/// # Title Specific Presets
+/// Several functions are provided that create an options instance suitable for parsing q-entities
+/// found in a specific title. Users should understand that the intended purpose of these functions
+/// does not extend beyond this goal and as such failure to support the q-entities of their
+/// respective titles is considered a bug.
+///
+/// The options enabled by these functions are documented for the convenience of the user, but
+/// **users should not depend on the options enabled by these functions remaining consistent between
+/// non-major releases**. This caveat, though somewhat burdening to users, is necessary to enable
+/// fixing bugs in these functions outside of major releases.
+///
 /// The following functions are subject to the aforementioned details:
 /// * [`quake()`](Self::quake)
 /// * [`quake2()`](Self::quake2)
+/// * [`goldsrc()`](Self::goldsrc)
 /// * [`quake3()`](Self::quake3)
 /// * [`source_engine()`](Self::source_engine)
+/// * [`source2()`](Self::source2)
 /// * [`vtmb()`](Self::vtmb)
-#[derive(Clone)]
+///
+/// # Nesting
+/// Entities cannot be nested within one another, so the depth of braces is inherently bounded to
+/// one. An open brace (`{`) appearing within an entity is always reported as an
+/// [unexpected token](QEntitiesParseErrorKind::UnexpectedToken). The parser does not recurse and
+/// the memory it uses while parsing is bounded by the size of the produced collection plus the
+/// length of the longest key or value.
+///
+/// # Equality
+/// Options are equal only if every one of their options is equal, regardless of how they were
+/// built. This includes the options that cannot change the parsed collection, such as
+/// [`read_buffer_size()`](Self::read_buffer_size) and
+/// [`expected_chunk_count()`](Self::expected_chunk_count), such that equal options can be used as
+/// the key of a cache of parsed collections without ever conflating different options.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct QEntitiesParseOptions {
     /// Bit-flag options.
     flags: QEntitiesParseFlags,
@@ -488,6 +1403,16 @@ pub struct QEntitiesParseOptions {
     max_entities: usize,
     /// The maximum number of key-value pairs an entity is allowed.
     max_entity_kvs: usize,
+    /// The location of the first byte of the reader.
+    start_location: QEntitiesParserLocation,
+    /// The byte that must separate each key from its value, if any.
+    key_value_separator: Option<u8>,
+    /// The bytes that open and close an entity.
+    entity_delimiters: (u8, u8),
+    /// The number of distinct keys and values expected to be parsed.
+    expected_chunk_count: usize,
+    /// The maximum number of bytes read from the reader at a time.
+    read_buffer_size: usize,
 }
 
 impl QEntitiesParseOptions {
@@ -501,6 +1426,15 @@ impl QEntitiesParseOptions {
             max_value_length: usize::MAX,
             max_entities: usize::MAX,
             max_entity_kvs: usize::MAX,
+            start_location: QEntitiesParserLocation {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            key_value_separator: None,
+            entity_delimiters: (b'{', b'}'),
+            expected_chunk_count: 0,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
         }
     }
 
@@ -529,6 +1463,21 @@ impl QEntitiesParseOptions {
         Self::quake()
     }
 
+    /// [Title Specific Preset](Self#title-specific-presets) for parsing q-entities found in
+    /// _Half-Life_ and other titles using the _GoldSrc_ engine.
+    ///
+    /// Escape sequences are intentionally left disabled, since the `wad` key of a `worldspawn`
+    /// entity typically holds back-slash separated paths.
+    ///
+    /// # Current Release Options
+    /// This function enables the following options in the current release:
+    /// * C++ style comments
+    /// * Controls terminate unquoted strings
+    #[inline(always)]
+    pub fn goldsrc() -> Self {
+        Self::source_engine()
+    }
+
     /// [Title Specific Preset](Self#title-specific-presets) for parsing q-entities found in
     /// _Quake III: Arena_.
     ///
@@ -560,6 +1509,34 @@ impl QEntitiesParseOptions {
         }
     }
 
+    /// [Title Specific Preset](Self#title-specific-presets) for parsing q-entities found in
+    /// titles using the _Source 2_ engine.
+    ///
+    /// This is intended for entity lumps that have been exported as text. Entities stored as
+    /// _KeyValues3_ cannot be represented as q-entities, and as with any other options, a file
+    /// beginning with a _KeyValues3_ header (`<!--`) results in an
+    /// [`UnsupportedFormat`](QEntitiesParseErrorKind::UnsupportedFormat) error.
+    ///
+    /// # Current Release Options
+    /// This function enables the following options in the current release:
+    /// * C++ style comments
+    /// * Controls terminate unquoted strings
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+    ///
+    /// let src = b"<!-- kv3 encoding:text:version{e21c7f3c-8a33-41c5-9977-a76d3a32aa0d} -->\n{}";
+    ///
+    /// let e = QEntitiesParseOptions::source2().parse(&src[..]).unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::UnsupportedFormat);
+    /// ```
+    #[inline(always)]
+    pub fn source2() -> Self {
+        Self::source_engine()
+    }
+
     /// [Title Specific Preset](Self#title-specific-presets) for parsing q-entities found in
     /// _Vampire The Masquerade: Bloodlines_.
     ///
@@ -582,6 +1559,9 @@ impl QEntitiesParseOptions {
 
     /// Changes whether or not C++ style single-line comments are enabled.
     ///
+    /// Comments are only recognized between tokens and within unquoted strings, such that a `//`
+    /// within a quoted string is always part of the string.
+    ///
     /// # Examples
     /// Basic usage:
     /// ```
@@ -619,8 +1599,18 @@ impl QEntitiesParseOptions {
         self
     }
 
+    /// Returns whether or not C++ style comments (`// ...`) are parsed, as changed by
+    /// [`cpp_style_comments()`](Self::cpp_style_comments).
+    #[inline]
+    pub fn cpp_style_comments_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::CPP_STYLE_COMMENTS)
+    }
+
     /// Changes whether or not C style multi-line comments are enabled.
     ///
+    /// Comments are only recognized between tokens and within unquoted strings, such that a `/*`
+    /// or `*/` within a quoted string is always part of the string.
+    ///
     /// # Examples
     /// Basic usage:
     /// ```
@@ -661,6 +1651,13 @@ impl QEntitiesParseOptions {
         self
     }
 
+    /// Returns whether or not C style comments (`/* ... */`) are parsed, as changed by
+    /// [`c_style_comments()`](Self::c_style_comments).
+    #[inline]
+    pub fn c_style_comments_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::C_STYLE_COMMENTS)
+    }
+
     /// Changes whether or control bytes terminate unquoted strings.
     ///
     /// # Examples
@@ -705,6 +1702,14 @@ impl QEntitiesParseOptions {
         self
     }
 
+    /// Returns whether or not control tokens terminate unquoted strings, as changed by
+    /// [`controls_terminate_unquoted_strings()`](Self::controls_terminate_unquoted_strings).
+    #[inline]
+    pub fn controls_terminate_unquoted_strings_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::CONTROLS_TERMINATE_UNQUOTED_STRINGS)
+    }
+
     /// Changes whether or comments terminate unquoted strings.
     ///
     /// # Examples
@@ -746,6 +1751,68 @@ impl QEntitiesParseOptions {
         self
     }
 
+    /// Returns whether or not comments terminate unquoted strings, as changed by
+    /// [`comments_terminate_unquoted_strings()`](Self::comments_terminate_unquoted_strings).
+    #[inline]
+    pub fn comments_terminate_unquoted_strings_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::COMMENTS_TERMINATE_UNQUOTED_STRINGS)
+    }
+
+    /// Changes whether or not strings can be quoted using single quotes (`'`) in addition to double
+    /// quotes (`"`).
+    ///
+    /// A string opened with a single quote is only terminated by another single quote and vice
+    /// versa. When [`controls_terminate_unquoted_strings()`](Self::controls_terminate_unquoted_strings)
+    /// is enabled, single quotes additionally terminate unquoted strings.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = br#"{ 'classname' "worldspawn" "message" 'say "hello"' }"#;
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .single_quoted_strings(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 2);
+    ///
+    /// let (key_a, value_a) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// let (key_b, value_b) = entity.get(1).map(|kv| (kv.key(), kv.value())).unwrap();
+    ///
+    /// assert_eq!(key_a, b"classname");
+    /// assert_eq!(value_a, b"worldspawn");
+    ///
+    /// assert_eq!(key_b, b"message");
+    /// assert_eq!(value_b, b"say \"hello\"");
+    /// ```
+    #[inline]
+    pub fn single_quoted_strings(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::SINGLE_QUOTED_STRINGS, value);
+        self
+    }
+
+    /// Same as [`single_quoted_strings()`](Self::single_quoted_strings) but takes `self` by value.
+    #[inline]
+    pub fn with_single_quoted_strings(mut self, value: bool) -> Self {
+        self.single_quoted_strings(value);
+        self
+    }
+
+    /// Returns whether or not single quoted strings are parsed, as changed by
+    /// [`single_quoted_strings()`](Self::single_quoted_strings).
+    #[inline]
+    pub fn single_quoted_strings_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::SINGLE_QUOTED_STRINGS)
+    }
+
     /// Changes the escape sequence options use when parsing quoted strings.
     ///
     /// A value of [`Some`] always implies that a back-slash can escape another back-slash (`\\`).
@@ -785,9 +1852,12 @@ impl QEntitiesParseOptions {
     pub fn escape_options(&mut self, value: Option<QEntitiesParseEscapeOptions>) -> &mut Self {
         self.flags.remove(QEntitiesParseFlags::ESCAPE_OPTIONS);
         if let Some(escape_option_flags) = value.map(|value| value.flags) {
-            debug_assert!(escape_option_flags.contains(QEntitiesParseFlags::ESCAPE));
-            debug_assert!(!escape_option_flags.contains(!QEntitiesParseFlags::ESCAPE_OPTIONS));
-            self.flags.insert(escape_option_flags);
+            // Only the escape flags are taken so that no other option can be changed, even if the
+            // escape options were to carry other flags.
+            self.flags.insert(
+                escape_option_flags & QEntitiesParseFlags::ESCAPE_OPTIONS
+                    | QEntitiesParseFlags::ESCAPE,
+            );
         }
         self
     }
@@ -799,1507 +1869,6387 @@ impl QEntitiesParseOptions {
         self
     }
 
-    /// Changes the maximum allowed byte length of a parsed key.
-    ///
-    /// Using a value of [`None`] specifies that there should be no limit.
+    /// Returns the allowed escape sequences, or [`None`] if escape sequences are disabled, as
+    /// changed by [`escape_options()`](Self::escape_options).
     #[inline]
-    pub fn max_key_length(&mut self, value: Option<usize>) -> &mut Self {
-        self.max_key_length = value.unwrap_or(usize::MAX);
-        self
+    pub fn get_escape_options(&self) -> Option<QEntitiesParseEscapeOptions> {
+        self.flags
+            .contains(QEntitiesParseFlags::ESCAPE)
+            .then(|| QEntitiesParseEscapeOptions {
+                flags: self.flags & QEntitiesParseFlags::ESCAPE_OPTIONS,
+            })
     }
 
-    /// Same as [`max_key_length()`](Self::max_key_length) but takes `self` by value.
+    /// Changes whether or not the escape sequences allowed by the
+    /// [`escape_options()`](Self::escape_options) are also parsed within unquoted strings.
+    ///
+    /// This has no effect if escape sequences are disabled. A back-slash within an unquoted string
+    /// that does not begin an allowed escape sequence, including one that is followed by a
+    /// terminating byte or the end of the file, results in an
+    /// [`InvalidEscapeSequence`](QEntitiesParseErrorKind::InvalidEscapeSequence) error.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseEscapeOptions, QEntitiesParseOptions};
+    ///
+    /// let src = br"{ classname worldspawn wad wads\\my\ wad.wad }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .escape_options(Some(QEntitiesParseEscapeOptions::new().with_whitespace(true)))
+    ///     .escape_unquoted(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 2);
+    ///
+    /// let (key, value) = entity.get(1).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"wad");
+    /// assert_eq!(value, br"wads\my wad.wad");
+    /// ```
     #[inline]
-    pub fn with_max_key_length(mut self, value: Option<usize>) -> Self {
-        self.max_key_length(value);
+    pub fn escape_unquoted(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::ESCAPE_UNQUOTED, value);
         self
     }
 
-    /// Changes the maximum allowed byte length of a parsed value.
-    ///
-    /// Using a value of [`None`] specifies that there should be no limit.
+    /// Same as [`escape_unquoted()`](Self::escape_unquoted) but takes `self` by value.
     #[inline]
-    pub fn max_value_length(&mut self, value: Option<usize>) -> &mut Self {
-        self.max_value_length = value.unwrap_or(usize::MAX);
+    pub fn with_escape_unquoted(mut self, value: bool) -> Self {
+        self.escape_unquoted(value);
         self
     }
 
-    /// Same as [`max_value_length()`](Self::max_value_length) but takes `self` by value.
+    /// Returns whether or not escape sequences are parsed within unquoted strings, as changed by
+    /// [`escape_unquoted()`](Self::escape_unquoted).
     #[inline]
-    pub fn with_max_value_length(mut self, value: Option<usize>) -> Self {
-        self.max_value_length(value);
-        self
+    pub fn escape_unquoted_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::ESCAPE_UNQUOTED)
     }
 
-    /// Changes the maximum allowed entities of a parsed file.
+    /// Changes the set of bytes that are considered whitespace for the purpose of separating tokens.
     ///
-    /// Using a value of [`None`] specifies that there should be no limit.
-    #[inline]
-    pub fn max_entities(&mut self, value: Option<usize>) -> &mut Self {
-        self.max_entities = value.unwrap_or(usize::MAX);
-        self
-    }
-
-    /// Same as [`max_entities()`](Self::max_entities) but takes `self` by value.
+    /// Bytes that are not within the set are treated as any other byte and as such can appear
+    /// within unquoted strings.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseOptions, QEntitiesWhitespaceSet};
+    ///
+    /// let src = b"{ classname world\x0cspawn }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .whitespace_set(QEntitiesWhitespaceSet::SpaceTabNewline)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 1);
+    ///
+    /// let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"classname");
+    /// assert_eq!(value, b"world\x0cspawn");
+    /// ```
     #[inline]
-    pub fn with_max_entities(mut self, value: Option<usize>) -> Self {
-        self.max_entities(value);
+    pub fn whitespace_set(&mut self, value: QEntitiesWhitespaceSet) -> &mut Self {
+        self.flags.set(
+            QEntitiesParseFlags::NARROW_WHITESPACE,
+            value == QEntitiesWhitespaceSet::SpaceTabNewline,
+        );
         self
     }
 
-    /// Changes the maximum allowed key-value pairs a parsed entity may have.
-    ///
-    /// Using a value of [`None`] specifies that there should be no limit.
+    /// Same as [`whitespace_set()`](Self::whitespace_set) but takes `self` by value.
     #[inline]
-    pub fn max_entity_key_values(&mut self, value: Option<usize>) -> &mut Self {
-        self.max_entity_kvs = value.unwrap_or(usize::MAX);
+    pub fn with_whitespace_set(mut self, value: QEntitiesWhitespaceSet) -> Self {
+        self.whitespace_set(value);
         self
     }
 
-    /// Same as [`max_entity_key_values()`](Self::max_entity_key_values) but takes `self` by value.
+    /// Returns the set of bytes considered whitespace, as changed by
+    /// [`whitespace_set()`](Self::whitespace_set).
     #[inline]
-    pub fn with_max_entity_key_values(mut self, value: Option<usize>) -> Self {
-        self.max_entity_key_values(value);
-        self
+    pub fn get_whitespace_set(&self) -> QEntitiesWhitespaceSet {
+        if self.flags.contains(QEntitiesParseFlags::NARROW_WHITESPACE) {
+            QEntitiesWhitespaceSet::SpaceTabNewline
+        } else {
+            QEntitiesWhitespaceSet::Ascii
+        }
     }
 
-    /// Parse a reader as a q-entities file.
+    /// Changes whether or not the bytes most recently consumed by the parser are captured and
+    /// attached to errors as [context](QEntitiesParseError::context).
+    ///
+    /// When enabled the parser keeps a rolling buffer of up to the last 32 consumed bytes, which
+    /// are additionally included in the [`Display`](fmt::Display) output of errors.
     ///
     /// # Examples
     /// Basic usage:
     /// ```
     /// use qentities::parse::QEntitiesParseOptions;
     ///
-    /// let entities = QEntitiesParseOptions::new().parse(&b"{ classname worldspawn }"[..]).unwrap();
-    /// assert_eq!(entities.len(), 1);
-    ///
-    /// let entity = entities.get(0).unwrap();
-    /// assert_eq!(entity.len(), 1);
+    /// let src = b"{ classname worldspawn\n{";
     ///
-    /// let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
-    /// assert_eq!(key, b"classname");
-    /// assert_eq!(value, b"worldspawn");
+    /// let e = QEntitiesParseOptions::new()
+    ///     .capture_error_context(true)
+    ///     .parse(&src[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.context(), Some(&b"{ classname worldspawn\n{"[..]));
+    /// assert_eq!(
+    ///     e.to_string(),
+    ///     concat!(
+    ///         r#"expected "}", "quoted string" or "unquoted string", found "{" token "#,
+    ///         r#"@23 line#2 column#1 near "{ classname worldspawn\n{""#,
+    ///     ),
+    /// );
     /// ```
     #[inline]
-    pub fn parse<R: io::Read>(&self, reader: R) -> Result<QEntities, QEntitiesParseError> {
-        self.parse_with_hasher(reader, DefaultHashBuilder::default())
+    pub fn capture_error_context(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::CAPTURE_ERROR_CONTEXT, value);
+        self
     }
 
-    /// Parse a reader as a q-entities file using the given hasher.
+    /// Same as [`capture_error_context()`](Self::capture_error_context) but takes `self` by value.
     #[inline]
-    pub fn parse_with_hasher<R: io::Read, S: BuildHasher>(
-        &self,
-        reader: R,
-        hash_builder: S,
-    ) -> Result<QEntities, QEntitiesParseError> {
-        Parser::new(reader, self.clone()).parse(hash_builder)
+    pub fn with_capture_error_context(mut self, value: bool) -> Self {
+        self.capture_error_context(value);
+        self
     }
-}
 
-impl Default for QEntitiesParseOptions {
-    #[inline(always)]
-    fn default() -> Self {
-        Self::new()
+    /// Returns whether or not the bytes preceding an error are captured, as changed by
+    /// [`capture_error_context()`](Self::capture_error_context).
+    #[inline]
+    pub fn capture_error_context_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::CAPTURE_ERROR_CONTEXT)
     }
-}
-
-/// The kinds of tokens that can appear within a q-entities file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum QEntitiesTokenKind {
-    /// An open brace (`{`).
-    OpenBrace = b'{' as _,
-    /// A close brace (`}`).
-    CloseBrace = b'}' as _,
-    /// A quoted string (`"foo bar"`).
-    QuotedString = b'"' as _,
-    /// An unquoted string (`foo_bar`).
-    UnquotedString = 0,
-}
 
-impl fmt::Display for QEntitiesTokenKind {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::OpenBrace => write!(f, "{{"),
-            Self::CloseBrace => write!(f, "}}"),
-            Self::QuotedString => write!(f, "quoted string"),
-            Self::UnquotedString => write!(f, "unquoted string"),
-        }
+    /// Changes whether or not parsed keys and values are interned.
+    ///
+    /// Interning is enabled by default and stores each distinct key or value only once, at the
+    /// cost of hashing every parsed key and value. Disabling it trades memory for speed, which can
+    /// be worthwhile for one-shot parses of files with little repetition. The produced collection
+    /// behaves identically regardless of this option.
+    #[inline]
+    pub fn intern(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::DISABLE_INTERNING, !value);
+        self
     }
-}
 
-/// The kinds of sources strings can be parsed from within a q-entities file.
-#[derive(Debug, Clone, Copy)]
-enum StringSourceKind {
-    /// The source is a key.
-    Key,
-    /// The source is a value.
-    Value,
-}
+    /// Same as [`intern()`](Self::intern) but takes `self` by value.
+    #[inline]
+    pub fn with_intern(mut self, value: bool) -> Self {
+        self.intern(value);
+        self
+    }
 
-/// State that a [`PeekByte`] can be in.
-enum PeekByteState {
-    /// The byte is unavailable and a needs to be updated from the reader.
-    Spoiled,
-    /// The byte available and represents the most recent state of the reader.
-    Fresh,
-    /// The reader has indicated that no more bytes are available.
-    Unavailable,
-}
+    /// Returns whether or not keys and values are interned, as changed by
+    /// [`intern()`](Self::intern).
+    #[inline]
+    pub fn intern_enabled(&self) -> bool {
+        !self.flags.contains(QEntitiesParseFlags::DISABLE_INTERNING)
+    }
 
-/// Type that handles the abstraction of peeking bytes for [`Parser`].
-struct PeekByte {
-    state: PeekByteState,
-    byte: u8,
-}
+    /// Changes the number of distinct keys and values that a parse is expected to produce.
+    ///
+    /// This is only a hint which is used to pre-size the interning table of the produced
+    /// collection, avoiding the cost of growing it while parsing. The number of distinct keys and
+    /// values of a previously parsed collection is given by [`QEntities::chunk_count()`]. The
+    /// default hint is `0`, for which nothing is reserved up front.
+    ///
+    /// Note that the hint is reserved in full before parsing begins, and so should not be taken
+    /// from an untrusted source. A hint that exceeds the address space is disregarded, but any other
+    /// hint is allocated in full, and exhausting memory while doing so is not recoverable.
+    #[inline]
+    pub fn expected_chunk_count(&mut self, value: usize) -> &mut Self {
+        self.expected_chunk_count = value;
+        self
+    }
 
-impl fmt::Debug for PeekByte {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.state {
-            PeekByteState::Spoiled => write!(f, "Spoiled"),
-            PeekByteState::Fresh => write!(f, "Fresh({})", self.byte),
-            PeekByteState::Unavailable => write!(f, "Unavailable"),
-        }
+    /// Same as [`expected_chunk_count()`](Self::expected_chunk_count) but takes `self` by value.
+    #[inline]
+    pub fn with_expected_chunk_count(mut self, value: usize) -> Self {
+        self.expected_chunk_count(value);
+        self
     }
-}
 
-impl PeekByte {
-    /// Create a new empty peek-byte.
+    /// Returns the number of distinct keys and values expected to be parsed, as changed by
+    /// [`expected_chunk_count()`](Self::expected_chunk_count).
     #[inline]
-    pub fn new() -> Self {
-        Self {
-            state: PeekByteState::Spoiled,
-            byte: 0,
-        }
+    pub fn get_expected_chunk_count(&self) -> usize {
+        self.expected_chunk_count
     }
 
-    /// Assume that any previously peeked-byte has spoiled and perform a fresh read from the reader.
-    pub fn peek_from_spoiled<R: io::Read>(
-        &mut self,
-        reader: &mut R,
-    ) -> Result<Option<u8>, io::Error> {
-        if reader.read(slice::from_mut(&mut self.byte))? == 0 {
-            self.state = PeekByteState::Unavailable;
-            Ok(None)
-        } else {
-            self.state = PeekByteState::Fresh;
-            Ok(Some(self.byte))
-        }
+    /// Changes whether or not the offsets at which each line begins are recorded while parsing.
+    ///
+    /// The recorded offsets are made available through [`QEntities::line_index()`] and enable
+    /// mapping offsets back to locations with [`QEntities::offset_to_location()`].
+    #[inline]
+    pub fn record_line_index(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::RECORD_LINE_INDEX, value);
+        self
     }
 
-    /// Either return the previously peeked-byte or read it out of the provided reader.
-    pub fn peek_from<R: io::Read>(&mut self, reader: &mut R) -> Result<Option<u8>, io::Error> {
-        match self.state {
-            PeekByteState::Spoiled => self.peek_from_spoiled(reader),
-            PeekByteState::Fresh => Ok(Some(self.byte)),
-            PeekByteState::Unavailable => Ok(None),
-        }
+    /// Same as [`record_line_index()`](Self::record_line_index) but takes `self` by value.
+    #[inline]
+    pub fn with_record_line_index(mut self, value: bool) -> Self {
+        self.record_line_index(value);
+        self
     }
 
-    /// Attempt to take the inner byte and subsequently spoil it, or if the byte is already spoiled
-    /// read the next from the given reader.
-    pub fn take_from<R: io::Read>(&mut self, reader: &mut R) -> Result<Option<u8>, io::Error> {
-        match self.state {
-            PeekByteState::Spoiled => {
-                if reader.read(slice::from_mut(&mut self.byte))? == 0 {
-                    self.state = PeekByteState::Unavailable;
-                    Ok(None)
-                } else {
-                    Ok(Some(self.byte))
-                }
-            }
-            PeekByteState::Fresh => {
-                self.state = PeekByteState::Spoiled;
-                Ok(Some(self.byte))
-            }
-            PeekByteState::Unavailable => Ok(None),
-        }
+    /// Returns whether or not the offsets at which lines begin are recorded, as changed by
+    /// [`record_line_index()`](Self::record_line_index).
+    #[inline]
+    pub fn record_line_index_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::RECORD_LINE_INDEX)
     }
 
-    /// Assume that there exists a previously peeked byte that is still fresh and take it.
+    /// Changes whether or not keys can appear without a value at the end of an entity.
     ///
-    /// This is intended to be used in scenarios where the user knows that there is a freshly peeked
-    /// byte, but the compiler may have a difficult time proving such.
+    /// When enabled, a key that is immediately followed by the close brace (`}`) of its entity is
+    /// given an empty value. When disabled, the close brace is reported as an
+    /// [unexpected token](QEntitiesParseErrorKind::UnexpectedToken).
     ///
-    /// # Panics
-    /// In debug builds this function will panic if there does not actually exist a freshly peeked
-    /// byte, while in release builds this function will merely return an erroneous but initialized
-    /// result.
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname func_detail nodraw }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .allow_valueless_keys(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 2);
+    ///
+    /// let (key, value) = entity.get(1).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"nodraw");
+    /// assert_eq!(value, b"");
+    /// ```
     #[inline]
-    #[must_use]
-    pub fn take_fresh(&mut self) -> u8 {
-        debug_assert!(matches!(self.state, PeekByteState::Fresh));
-        self.state = PeekByteState::Spoiled;
-        self.byte
+    pub fn allow_valueless_keys(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::ALLOW_VALUELESS_KEYS, value);
+        self
     }
-}
-
-/// State for parsing the Quake entities format from an [`io::Read`].
-///
-/// Note that this encapsulates the concepts of both a lexer and parser. These concepts are
-/// encapsulated into a single type primarily to avoid needing to parse out entire byte-chunks in
-/// contexts where the apperance of a byte-chunk is always an error.
-struct Parser<R: io::Read> {
-    /// The inner reader from which bytes are read.
-    reader: R,
-    /// The byte peeked from the reader.
-    peek_byte: PeekByte,
-    /// The parser's current location within the reader.
-    location: QEntitiesParserLocation,
-    /// options used for parsing.
-    options: QEntitiesParseOptions,
-}
 
-impl<R: io::Read> Parser<R> {
-    /// Create a new parser for a reader.
+    /// Same as [`allow_valueless_keys()`](Self::allow_valueless_keys) but takes `self` by value.
     #[inline]
-    fn new(reader: R, options: QEntitiesParseOptions) -> Self {
-        Self {
-            reader,
-            peek_byte: PeekByte::new(),
-            location: QEntitiesParserLocation {
-                offset: 0,
-                line: 1,
-                column: 1,
-            },
-            options,
-        }
+    pub fn with_allow_valueless_keys(mut self, value: bool) -> Self {
+        self.allow_valueless_keys(value);
+        self
     }
 
-    /// Peek the next unconsumed byte within the reader.
-    #[inline(always)]
-    fn peek_byte(&mut self) -> Result<Option<u8>, io::Error> {
-        self.peek_byte.peek_from(&mut self.reader)
+    /// Returns whether or not keys at the end of an entity are allowed to have no value, as changed
+    /// by [`allow_valueless_keys()`](Self::allow_valueless_keys).
+    #[inline]
+    pub fn allow_valueless_keys_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::ALLOW_VALUELESS_KEYS)
     }
 
-    /// Attempt to read the next byte.
+    /// Changes how an entity that has multiple key-values with the same key is handled.
     ///
-    /// This will implicitly move the location of the parser forward upon success.
-    fn next_byte(&mut self) -> Result<Option<u8>, io::Error> {
-        let res = self.peek_byte.take_from(&mut self.reader)?;
-        if let Some(byte) = res {
-            self.advance_location(byte);
-        }
-        Ok(res)
-    }
-
-    /// Identical behavior to [`next_byte()`](Self::next_byte()) except that this function makes the
-    /// assumption that a previous peek was successful and returns the byte from that operation.
+    /// Keys are compared byte-wise. With any policy other than
+    /// [`Keep`](QEntitiesDuplicateKeyPolicy::Keep), the parser tracks the keys of the entity being
+    /// parsed and only passes its key-values to a [`QEntitiesVisitor`] once the entity has been
+    /// terminated.
     ///
-    /// # Panics
-    /// This function can panic under all the same circumstances that [`PeekByte::take_fresh()`] may
-    /// panic under.
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{
+    ///     QEntitiesDuplicateKeyPolicy, QEntitiesParseErrorKind, QEntitiesParseOptions,
+    /// };
+    ///
+    /// let src = b"{ classname light light 200 origin \"0 0 0\" light 300 }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Replace)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert!(entities.get(0).unwrap().pairs().eq([
+    ///     (&b"classname"[..], &b"light"[..]),
+    ///     (&b"light"[..], &b"300"[..]),
+    ///     (&b"origin"[..], &b"0 0 0"[..]),
+    /// ]));
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error)
+    ///     .parse(&src[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::DuplicateKey);
+    /// assert_eq!(e.location().unwrap().column(), 44);
+    /// ```
     #[inline]
-    #[must_use]
-    fn next_byte_fresh(&mut self) -> u8 {
-        let byte = self.peek_byte.take_fresh();
-        self.advance_location(byte);
-        byte
+    pub fn duplicate_key_policy(&mut self, value: QEntitiesDuplicateKeyPolicy) -> &mut Self {
+        self.flags.set(
+            QEntitiesParseFlags::DUPLICATE_KEYS_ERROR,
+            value == QEntitiesDuplicateKeyPolicy::Error,
+        );
+        self.flags.set(
+            QEntitiesParseFlags::DUPLICATE_KEYS_REPLACE,
+            value == QEntitiesDuplicateKeyPolicy::Replace,
+        );
+        self
     }
 
-    /// Advance the parser's location dependent upon the input byte.
-    fn advance_location(&mut self, byte: u8) {
-        self.location.offset += 1;
-        match byte {
-            b'\n' | b'\r' => {
-                self.location.line += 1;
-                self.location.column = 1;
-            }
-            _ => {
-                self.location.column += 1;
-            }
-        }
+    /// Same as [`duplicate_key_policy()`](Self::duplicate_key_policy) but takes `self` by value.
+    #[inline]
+    pub fn with_duplicate_key_policy(mut self, value: QEntitiesDuplicateKeyPolicy) -> Self {
+        self.duplicate_key_policy(value);
+        self
     }
 
-    /// Consumes bytes until the first new-line or EOF is encountered.
-    fn skip_cpp_style_comment(&mut self) -> Result<(), QEntitiesParseError> {
-        while let Some(byte) = self.next_byte()? {
-            if matches!(byte, b'\n' | b'\r') {
-                break;
-            }
+    /// Returns the policy applied to duplicate keys, as changed by
+    /// [`duplicate_key_policy()`](Self::duplicate_key_policy).
+    #[inline]
+    pub fn get_duplicate_key_policy(&self) -> QEntitiesDuplicateKeyPolicy {
+        if self
+            .flags
+            .contains(QEntitiesParseFlags::DUPLICATE_KEYS_ERROR)
+        {
+            QEntitiesDuplicateKeyPolicy::Error
+        } else if self
+            .flags
+            .contains(QEntitiesParseFlags::DUPLICATE_KEYS_REPLACE)
+        {
+            QEntitiesDuplicateKeyPolicy::Replace
+        } else {
+            QEntitiesDuplicateKeyPolicy::Keep
         }
-        Ok(())
     }
 
-    /// Consumes bytes until the pattern `*/` is encountered.
+    /// Changes whether or not a file that begins with a string is parsed as a single implicit
+    /// entity without braces.
     ///
-    /// If no `*/` pattern is encountered before the EOF then an error is returned.
-    fn skip_c_style_comment(&mut self) -> Result<(), QEntitiesParseError> {
-        // Compute the start location so that it can be returned if no termination pattern is
-        // encountered.
-        let start_loc = QEntitiesParserLocation {
-            offset: self.location.offset - 2,
-            line: self.location.line,
-            column: self.location.column - 2,
-        };
-
-        while let Some(byte) = self.next_byte()? {
-            if byte == b'*' && matches!(self.peek_byte()?, Some(b'/')) {
-                let _ = self.next_byte_fresh();
-                return Ok(());
-            }
-        }
-
-        Err(ParseError::UnterminatedCStyleComment(start_loc).into())
-    }
-
-    /// Consumes bytes until a byte that is neither whitespace nor part of a comment is encountered
-    /// and returns that byte as well as its location.
+    /// When enabled and the first token of a file is a quoted or unquoted string rather than an
+    /// open brace (`{`), the key-values of the entire file are parsed as the key-values of a single
+    /// entity that is terminated by the end of the file. As entities cannot be nested, braces
+    /// appearing within such a file are reported as
+    /// [unexpected tokens](QEntitiesParseErrorKind::UnexpectedToken). Files that begin with an open
+    /// brace are parsed as usual.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"classname worldspawn\nwad mywad.wad\n";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .implicit_top_level_entity(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    /// assert!(entities.get(0).unwrap().pairs().eq([
+    ///     (&b"classname"[..], &b"worldspawn"[..]),
+    ///     (&b"wad"[..], &b"mywad.wad"[..]),
+    /// ]));
+    /// ```
+    #[inline]
+    pub fn implicit_top_level_entity(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::IMPLICIT_TOP_LEVEL_ENTITY, value);
+        self
+    }
+
+    /// Same as [`implicit_top_level_entity()`](Self::implicit_top_level_entity) but takes `self` by
+    /// value.
+    #[inline]
+    pub fn with_implicit_top_level_entity(mut self, value: bool) -> Self {
+        self.implicit_top_level_entity(value);
+        self
+    }
+
+    /// Returns whether or not a file without braces is parsed as a single implicit entity, as
+    /// changed by [`implicit_top_level_entity()`](Self::implicit_top_level_entity).
+    #[inline]
+    pub fn implicit_top_level_entity_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::IMPLICIT_TOP_LEVEL_ENTITY)
+    }
+
+    /// Changes whether or not content following the last entity is reported as
+    /// [trailing content](QEntitiesParseErrorKind::TrailingContent).
+    ///
+    /// Only whitespace and comments may follow the close brace (`}`) of the last entity. Without
+    /// this option any other token is reported as an
+    /// [unexpected token](QEntitiesParseErrorKind::UnexpectedToken), which does not distinguish a
+    /// stray token at the end of a file from a malformed entity. With this option enabled, a token
+    /// other than the open brace (`{`) of another entity that follows an entity is reported as
+    /// trailing content instead. An open brace still begins another entity, which is reported as
+    /// [unterminated](QEntitiesParseErrorKind::UnterminatedEntity) if the file ends within it.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+    ///
+    /// let parse_opts = QEntitiesParseOptions::quake().with_strict_eof(true);
+    ///
+    /// let entities = parse_opts
+    ///     .parse(&b"{ classname worldspawn }\n// done\n"[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let e = parse_opts
+    ///     .parse(&b"{ classname worldspawn }\n}\n"[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::TrailingContent);
+    /// assert_eq!(e.location().unwrap().line(), 2);
+    /// ```
+    #[inline]
+    pub fn strict_eof(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::STRICT_EOF, value);
+        self
+    }
+
+    /// Same as [`strict_eof()`](Self::strict_eof) but takes `self` by value.
+    #[inline]
+    pub fn with_strict_eof(mut self, value: bool) -> Self {
+        self.strict_eof(value);
+        self
+    }
+
+    /// Returns whether or not content following the last entity is rejected, as changed by
+    /// [`strict_eof()`](Self::strict_eof).
+    #[inline]
+    pub fn strict_eof_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::STRICT_EOF)
+    }
+
+    /// Changes whether or not a NUL byte (`\0`) is treated as the end of the reader.
+    ///
+    /// This is useful for parsing the entity lumps of BSP files, which are often NUL-terminated,
+    /// from a buffer that may extend beyond the lump. When enabled, parsing stops at the first NUL
+    /// byte exactly as if the reader had ended there, and nothing following it is read. A NUL byte
+    /// within a quoted string therefore results in an
+    /// [unterminated quoted string](QEntitiesParseErrorKind::UnterminatedQuotedString) error, and
+    /// content following a NUL byte is never reported as
+    /// [trailing content](Self::strict_eof). As a NUL byte is never part of a key or value, it is
+    /// not rejected by [`reject_control_bytes()`](Self::reject_control_bytes).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn }\0\xcd\xcd{ garbage";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .nul_terminates_input(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    /// ```
+    #[inline]
+    pub fn nul_terminates_input(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::NUL_TERMINATES_INPUT, value);
+        self
+    }
+
+    /// Same as [`nul_terminates_input()`](Self::nul_terminates_input) but takes `self` by value.
+    #[inline]
+    pub fn with_nul_terminates_input(mut self, value: bool) -> Self {
+        self.nul_terminates_input(value);
+        self
+    }
+
+    /// Returns whether or not a NUL byte is treated as the end of the reader, as changed by
+    /// [`nul_terminates_input()`](Self::nul_terminates_input).
+    #[inline]
+    pub fn nul_terminates_input_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::NUL_TERMINATES_INPUT)
+    }
+
+    /// Changes whether or not the comments preceding each entity are retained while parsing.
+    ///
+    /// Retained comments are associated with the entity that follows them and are made available
+    /// through [`QEntityRef::leading_comments()`], while comments following the last entity are
+    /// made available through [`QEntities::trailing_comments()`]. Comments within an entity are
+    /// not retained. The text of a comment excludes its delimiters (`//`, `/*`, and `*/`) as well
+    /// as the new-line terminating a C++ style comment, but is otherwise retained exactly.
+    ///
+    /// Comments are only retained by the means of parsing that construct a collection.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"// spawn point\n{ classname info_player_start }\n/* end */";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .cpp_style_comments(true)
+    ///     .c_style_comments(true)
+    ///     .retain_comments(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// let entity = entities.get(0).unwrap();
+    /// assert!(entity.leading_comments().eq([&b" spawn point"[..]]));
+    /// assert!(entities.trailing_comments().eq([&b" end "[..]]));
+    /// ```
+    ///
+    /// [`QEntityRef::leading_comments()`]: crate::QEntityRef::leading_comments
+    #[inline]
+    pub fn retain_comments(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::RETAIN_COMMENTS, value);
+        self
+    }
+
+    /// Same as [`retain_comments()`](Self::retain_comments) but takes `self` by value.
+    #[inline]
+    pub fn with_retain_comments(mut self, value: bool) -> Self {
+        self.retain_comments(value);
+        self
+    }
+
+    /// Returns whether or not the comments preceding each entity are retained, as changed by
+    /// [`retain_comments()`](Self::retain_comments).
+    #[inline]
+    pub fn retain_comments_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::RETAIN_COMMENTS)
+    }
+
+    /// Changes whether or not keys are converted to ASCII lowercase while parsing.
+    ///
+    /// Many engines treat keys case-insensitively. When enabled, the ASCII uppercase letters of
+    /// each key are converted to lowercase before the key is stored, such that
+    /// [`QEntityKeyValueRef::key()`] returns the lowercase form and keys that differ only in case
+    /// are interned as the same bytes. Keys that differ only in case are also considered the same
+    /// key by the [`duplicate_key_policy()`](Self::duplicate_key_policy) and by warnings. Values
+    /// are never changed.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ Classname worldspawn } { CLASSNAME light }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .lowercase_keys(true)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// let kv = entities.key_value(1).unwrap();
+    /// assert_eq!(kv.key(), b"classname");
+    /// assert_eq!(kv.value(), b"light");
+    /// assert_eq!(entities.chunk_count(), 3);
+    /// ```
+    ///
+    /// [`QEntityKeyValueRef::key()`]: crate::QEntityKeyValueRef::key
+    #[inline]
+    pub fn lowercase_keys(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::LOWERCASE_KEYS, value);
+        self
+    }
+
+    /// Same as [`lowercase_keys()`](Self::lowercase_keys) but takes `self` by value.
+    #[inline]
+    pub fn with_lowercase_keys(mut self, value: bool) -> Self {
+        self.lowercase_keys(value);
+        self
+    }
+
+    /// Returns whether or not keys are converted to ASCII lowercase, as changed by
+    /// [`lowercase_keys()`](Self::lowercase_keys).
+    #[inline]
+    pub fn lowercase_keys_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::LOWERCASE_KEYS)
+    }
+
+    /// Changes whether or not parsed collections retain a copy of the options they were parsed
+    /// with, which is made available through [`QEntities::parse_options()`].
+    ///
+    /// This is disabled by default, since retaining the options allocates a copy of them for every
+    /// parsed collection.
+    #[inline]
+    pub fn retain_parse_options(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::RETAIN_PARSE_OPTIONS, value);
+        self
+    }
+
+    /// Same as [`retain_parse_options()`](Self::retain_parse_options) but takes `self` by value.
+    #[inline]
+    pub fn with_retain_parse_options(mut self, value: bool) -> Self {
+        self.retain_parse_options(value);
+        self
+    }
+
+    /// Returns whether or not parsed collections retain the options they were parsed with, as
+    /// changed by [`retain_parse_options()`](Self::retain_parse_options).
+    #[inline]
+    pub fn retain_parse_options_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::RETAIN_PARSE_OPTIONS)
+    }
+
+    /// Changes whether or not warnings about suspicious but valid constructs are collected.
+    ///
+    /// Collected warnings are returned by [`parse_with_warnings()`](Self::parse_with_warnings),
+    /// and are discarded by every other means of parsing. The kinds of warnings that are collected
+    /// are described by [`QEntitiesWarningKind`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseOptions, QEntitiesWarningKind};
+    ///
+    /// let src = b"{ classname worldspawn }\n{ }\n{ classname light classname info_null }";
+    ///
+    /// let (entities, warnings) = QEntitiesParseOptions::new()
+    ///     .with_collect_warnings(true)
+    ///     .parse_with_warnings(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 3);
+    /// assert_eq!(warnings.len(), 2);
+    /// assert_eq!(warnings[0].kind(), QEntitiesWarningKind::EmptyEntity);
+    /// assert_eq!(warnings[0].location().line(), 2);
+    /// assert_eq!(warnings[1].kind(), QEntitiesWarningKind::DuplicateKey);
+    /// assert_eq!(warnings[1].location().column(), 19);
+    /// ```
+    #[inline]
+    pub fn collect_warnings(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesParseFlags::COLLECT_WARNINGS, value);
+        self
+    }
+
+    /// Same as [`collect_warnings()`](Self::collect_warnings) but takes `self` by value.
+    #[inline]
+    pub fn with_collect_warnings(mut self, value: bool) -> Self {
+        self.collect_warnings(value);
+        self
+    }
+
+    /// Returns whether or not warnings are collected, as changed by
+    /// [`collect_warnings()`](Self::collect_warnings).
+    #[inline]
+    pub fn collect_warnings_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::COLLECT_WARNINGS)
+    }
+
+    /// Changes the byte that must separate each key from its value, such as `=` or `:`.
+    ///
+    /// When set, the next significant byte after a key must be the separator, otherwise a
+    /// [`MissingSeparator`](QEntitiesParseErrorKind::MissingSeparator) error is returned at its
+    /// location. Whitespace and comments around the separator are skipped, and the separator
+    /// terminates unquoted keys. Using a value of [`None`] specifies that keys and values are
+    /// separated by whitespace alone.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+    ///
+    /// let src = b"{ classname = light\n origin: \"0 0 0\" }";
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .key_value_separator(Some(b'='))
+    ///     .parse(&src[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::MissingSeparator);
+    /// assert_eq!(e.location().unwrap().line(), 2);
+    ///
+    /// let src = b"{ classname: light\n origin : \"0 0 0\" }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .key_value_separator(Some(b':'))
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert!(entities.get(0).unwrap().pairs().eq([
+    ///     (&b"classname"[..], &b"light"[..]),
+    ///     (&b"origin"[..], &b"0 0 0"[..]),
+    /// ]));
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if the separator is a double-quote, one of the
+    /// [entity delimiters](Self::entity_delimiters), or a byte considered whitespace by
+    /// [`u8::is_ascii_whitespace()`].
+    #[inline]
+    pub fn key_value_separator(&mut self, value: Option<u8>) -> &mut Self {
+        if let Some(separator) = value {
+            assert!(
+                separator != b'"'
+                    && separator != self.entity_delimiters.0
+                    && separator != self.entity_delimiters.1
+                    && !separator.is_ascii_whitespace(),
+                "invalid key-value separator: {:?}",
+                separator.escape_ascii().to_string(),
+            );
+        }
+        self.key_value_separator = value;
+        self
+    }
+
+    /// Same as [`key_value_separator()`](Self::key_value_separator) but takes `self` by value.
+    #[inline]
+    pub fn with_key_value_separator(mut self, value: Option<u8>) -> Self {
+        self.key_value_separator(value);
+        self
+    }
+
+    /// Returns the byte that must separate each key from its value, if any, as changed by
+    /// [`key_value_separator()`](Self::key_value_separator).
+    #[inline]
+    pub fn get_key_value_separator(&self) -> Option<u8> {
+        self.key_value_separator
+    }
+
+    /// Changes the bytes that open and close an entity, which are `{` and `}` by default.
+    ///
+    /// The given bytes take the place of the open and close braces throughout the grammar: they are
+    /// parsed as [`OpenBrace`](QEntitiesTokenKind::OpenBrace) and
+    /// [`CloseBrace`](QEntitiesTokenKind::CloseBrace) tokens, they terminate unquoted strings when
+    /// [`controls_terminate_unquoted_strings()`](Self::controls_terminate_unquoted_strings) is
+    /// enabled, and they are the bytes escaped by [`QEntitiesParseEscapeOptions::braces()`]. When
+    /// other bytes are used, `{` and `}` are ordinary bytes that begin unquoted strings.
+    ///
+    /// Unexpected token errors display the tokens by the given bytes.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"[ classname worldspawn ]\n[ classname light message {lit} ]";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .entity_delimiters(b'[', b']')
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 2);
+    /// assert_eq!(entities.get(1).unwrap().value_of(b"message"), Some(&b"{lit}"[..]));
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if either byte is the
+    /// [key-value separator](Self::key_value_separator).
+    #[inline]
+    pub fn entity_delimiters(&mut self, open: u8, close: u8) -> &mut Self {
+        assert!(
+            !matches!(
+                self.key_value_separator,
+                Some(separator) if separator == open || separator == close
+            ),
+            "entity delimiters must differ from the key-value separator",
+        );
+        self.entity_delimiters = (open, close);
+        self
+    }
+
+    /// Same as [`entity_delimiters()`](Self::entity_delimiters) but takes `self` by value.
+    #[inline]
+    pub fn with_entity_delimiters(mut self, open: u8, close: u8) -> Self {
+        self.entity_delimiters(open, close);
+        self
+    }
+
+    /// Returns the bytes that open and close an entity, as changed by
+    /// [`entity_delimiters()`](Self::entity_delimiters).
+    #[inline]
+    pub fn get_entity_delimiters(&self) -> (u8, u8) {
+        self.entity_delimiters
+    }
+
+    /// Changes whether or not control bytes are rejected within keys and values.
+    ///
+    /// When enabled, any byte less than `0x20` that is not whitespace according to the
+    /// [`whitespace_set()`](Self::whitespace_set) results in an
+    /// [`IllegalControlByte`](QEntitiesParseErrorKind::IllegalControlByte) error when it appears
+    /// within a quoted or unquoted string. When disabled, such bytes are stored as-is.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+    ///
+    /// let src = b"{ classname \"world\0spawn\" }";
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .reject_control_bytes(true)
+    ///     .parse(&src[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::IllegalControlByte);
+    /// assert_eq!(e.location().unwrap().offset(), 18);
+    /// ```
+    #[inline]
+    pub fn reject_control_bytes(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::REJECT_CONTROL_BYTES, value);
+        self
+    }
+
+    /// Same as [`reject_control_bytes()`](Self::reject_control_bytes) but takes `self` by value.
+    #[inline]
+    pub fn with_reject_control_bytes(mut self, value: bool) -> Self {
+        self.reject_control_bytes(value);
+        self
+    }
+
+    /// Returns whether or not control bytes are rejected within keys and values, as changed by
+    /// [`reject_control_bytes()`](Self::reject_control_bytes).
+    #[inline]
+    pub fn reject_control_bytes_enabled(&self) -> bool {
+        self.flags
+            .contains(QEntitiesParseFlags::REJECT_CONTROL_BYTES)
+    }
+
+    /// Changes whether or not empty keys are rejected.
+    ///
+    /// When enabled, a key that is empty, such as the quoted string `""`, results in an
+    /// [`EmptyKey`](QEntitiesParseErrorKind::EmptyKey) error at the location of the key. Empty
+    /// values are always allowed.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseErrorKind, QEntitiesParseOptions};
+    ///
+    /// let src = b"{ classname worldspawn \"\" value }";
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    /// assert_eq!(entities.get(0).unwrap().value_of(b""), Some(&b"value"[..]));
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .reject_empty_keys(true)
+    ///     .parse(&src[..])
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), QEntitiesParseErrorKind::EmptyKey);
+    /// assert_eq!(e.location().unwrap().column(), 24);
+    /// ```
+    #[inline]
+    pub fn reject_empty_keys(&mut self, value: bool) -> &mut Self {
+        self.flags
+            .set(QEntitiesParseFlags::REJECT_EMPTY_KEYS, value);
+        self
+    }
+
+    /// Same as [`reject_empty_keys()`](Self::reject_empty_keys) but takes `self` by value.
+    #[inline]
+    pub fn with_reject_empty_keys(mut self, value: bool) -> Self {
+        self.reject_empty_keys(value);
+        self
+    }
+
+    /// Returns whether or not empty keys are rejected, as changed by
+    /// [`reject_empty_keys()`](Self::reject_empty_keys).
+    #[inline]
+    pub fn reject_empty_keys_enabled(&self) -> bool {
+        self.flags.contains(QEntitiesParseFlags::REJECT_EMPTY_KEYS)
+    }
+
+    /// Changes the maximum allowed byte length of a parsed key.
+    ///
+    /// Using a value of [`None`] specifies that there should be no limit.
+    #[inline]
+    pub fn max_key_length(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_key_length = value.unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Same as [`max_key_length()`](Self::max_key_length) but takes `self` by value.
+    #[inline]
+    pub fn with_max_key_length(mut self, value: Option<usize>) -> Self {
+        self.max_key_length(value);
+        self
+    }
+
+    /// Returns the maximum allowed byte length of a parsed key, or [`None`] if there is no limit,
+    /// as changed by [`max_key_length()`](Self::max_key_length).
+    #[inline]
+    pub fn get_max_key_length(&self) -> Option<usize> {
+        (self.max_key_length != usize::MAX).then_some(self.max_key_length)
+    }
+
+    /// Changes the maximum allowed byte length of a parsed value.
+    ///
+    /// Using a value of [`None`] specifies that there should be no limit.
+    #[inline]
+    pub fn max_value_length(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_value_length = value.unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Same as [`max_value_length()`](Self::max_value_length) but takes `self` by value.
+    #[inline]
+    pub fn with_max_value_length(mut self, value: Option<usize>) -> Self {
+        self.max_value_length(value);
+        self
+    }
+
+    /// Returns the maximum allowed byte length of a parsed value, or [`None`] if there is no limit,
+    /// as changed by [`max_value_length()`](Self::max_value_length).
+    #[inline]
+    pub fn get_max_value_length(&self) -> Option<usize> {
+        (self.max_value_length != usize::MAX).then_some(self.max_value_length)
+    }
+
+    /// Changes the maximum allowed entities of a parsed file.
+    ///
+    /// Using a value of [`None`] specifies that there should be no limit.
+    #[inline]
+    pub fn max_entities(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_entities = value.unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Same as [`max_entities()`](Self::max_entities) but takes `self` by value.
+    #[inline]
+    pub fn with_max_entities(mut self, value: Option<usize>) -> Self {
+        self.max_entities(value);
+        self
+    }
+
+    /// Returns the maximum allowed entities of a parsed file, or [`None`] if there is no limit, as
+    /// changed by [`max_entities()`](Self::max_entities).
+    #[inline]
+    pub fn get_max_entities(&self) -> Option<usize> {
+        (self.max_entities != usize::MAX).then_some(self.max_entities)
+    }
+
+    /// Changes the maximum allowed key-value pairs a parsed entity may have.
+    ///
+    /// Using a value of [`None`] specifies that there should be no limit.
+    #[inline]
+    pub fn max_entity_key_values(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_entity_kvs = value.unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Same as [`max_entity_key_values()`](Self::max_entity_key_values) but takes `self` by value.
+    #[inline]
+    pub fn with_max_entity_key_values(mut self, value: Option<usize>) -> Self {
+        self.max_entity_key_values(value);
+        self
+    }
+
+    /// Returns the maximum allowed key-value pairs of a parsed entity, or [`None`] if there is no
+    /// limit, as changed by [`max_entity_key_values()`](Self::max_entity_key_values).
+    #[inline]
+    pub fn get_max_entity_key_values(&self) -> Option<usize> {
+        (self.max_entity_kvs != usize::MAX).then_some(self.max_entity_kvs)
+    }
+
+    /// Changes the maximum number of bytes that are read from the parsed reader at a time.
+    ///
+    /// Bytes are read from the reader into an internal buffer of this size, which is allocated once
+    /// per parse. The default size is 8 KiB. Smaller sizes bound the memory used while parsing at
+    /// the cost of reading from the reader more often, and the minimum size is `1`, for which the
+    /// reader is read one byte at a time. A size of `0` is treated as `1`.
+    ///
+    /// The size of the buffer has no effect upon the result of parsing.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn }";
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .read_buffer_size(16)
+    ///     .parse(&src[..])
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    /// ```
+    #[inline]
+    pub fn read_buffer_size(&mut self, value: usize) -> &mut Self {
+        self.read_buffer_size = value;
+        self
+    }
+
+    /// Same as [`read_buffer_size()`](Self::read_buffer_size) but takes `self` by value.
+    #[inline]
+    pub fn with_read_buffer_size(mut self, value: usize) -> Self {
+        self.read_buffer_size(value);
+        self
+    }
+
+    /// Returns the maximum number of bytes read from the parsed reader at a time, as changed by
+    /// [`read_buffer_size()`](Self::read_buffer_size).
+    #[inline]
+    pub fn get_read_buffer_size(&self) -> usize {
+        self.read_buffer_size
+    }
+
+    /// Changes the location of the first byte of the parsed reader.
+    ///
+    /// This is useful when parsing a portion of a larger file, such that reported locations are
+    /// relative to the larger file rather than to the portion. The default start location is offset
+    /// `0`, line `1`, column `1`.
+    ///
+    /// The [`line_index()`](QEntities::line_index) of a parsed collection is unaffected, and
+    /// always describes the lines of the parsed reader itself.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseOptions, QEntitiesParserLocation};
+    ///
+    /// let src = b"{ classname worldspawn }\n{ classname light\n}}";
+    ///
+    /// let e = QEntitiesParseOptions::new()
+    ///     .start_location(QEntitiesParserLocation::new(100, 10, 5))
+    ///     .parse(&src[25..])
+    ///     .unwrap_err();
+    /// let location = e.location().unwrap();
+    /// assert_eq!((location.offset(), location.line(), location.column()), (119, 11, 2));
+    /// ```
+    #[inline]
+    pub fn start_location(&mut self, value: QEntitiesParserLocation) -> &mut Self {
+        self.start_location = value;
+        self
+    }
+
+    /// Same as [`start_location()`](Self::start_location) but takes `self` by value.
+    #[inline]
+    pub fn with_start_location(mut self, value: QEntitiesParserLocation) -> Self {
+        self.start_location(value);
+        self
+    }
+
+    /// Returns the location of the first byte of the parsed reader, as changed by
+    /// [`start_location()`](Self::start_location).
+    #[inline]
+    pub fn get_start_location(&self) -> QEntitiesParserLocation {
+        self.start_location
+    }
+
+    /// Parse a reader as a q-entities file.
+    ///
+    /// The reader is read through an internal buffer, so it does not need to be buffered itself.
+    /// Readers behind a `&mut dyn io::Read` can be parsed directly, and share a single
+    /// instantiation of the parser regardless of their concrete types.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let entities = QEntitiesParseOptions::new().parse(&b"{ classname worldspawn }"[..]).unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let entity = entities.get(0).unwrap();
+    /// assert_eq!(entity.len(), 1);
+    ///
+    /// let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
+    /// assert_eq!(key, b"classname");
+    /// assert_eq!(value, b"worldspawn");
+    /// ```
+    ///
+    /// Parsing a dynamically dispatched reader:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use std::io;
+    ///
+    /// let mut reader: Box<dyn io::Read> = Box::new(&b"{ classname worldspawn }"[..]);
+    ///
+    /// let entities = QEntitiesParseOptions::new()
+    ///     .parse(&mut *reader as &mut dyn io::Read)
+    ///     .unwrap();
+    /// assert_eq!(entities.len(), 1);
+    /// ```
+    #[inline]
+    pub fn parse<R: io::Read>(&self, reader: R) -> Result<QEntities, QEntitiesParseError> {
+        self.parse_with_hasher(reader, DefaultHashBuilder::default())
+    }
+
+    /// Parse bytes held in memory as a q-entities file.
+    ///
+    /// This is a convenience for [`parse()`](Self::parse) that accepts anything that can be viewed
+    /// as bytes, such as a [`Vec<u8>`], a [`String`], or a string literal, without needing to
+    /// slice it first.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let options = QEntitiesParseOptions::new();
+    /// let entities = options.parse_ref("{ classname worldspawn }").unwrap();
+    /// assert_eq!(entities.len(), 1);
+    ///
+    /// let src: Vec<u8> = b"{ classname light } { classname light }".to_vec();
+    /// let entities = options.parse_ref(&src).unwrap();
+    /// assert_eq!(entities.len(), 2);
+    /// ```
+    #[inline]
+    pub fn parse_ref<T: AsRef<[u8]>>(&self, data: T) -> Result<QEntities, QEntitiesParseError> {
+        self.parse(data.as_ref())
+    }
+
+    /// Parse the file at a path as a q-entities file.
+    ///
+    /// The file is read in chunks of the [read buffer size](Self::read_buffer_size). Any error that
+    /// occurs while opening the file is returned as an [`Io`](QEntitiesParseErrorKind::Io) error.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```no_run
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let entities = QEntitiesParseOptions::quake().parse_path("maps/e1m1.ent").unwrap();
+    /// for entity in entities.iter() {
+    ///     println!("{entity:?}");
+    /// }
+    /// ```
+    pub fn parse_path<P: AsRef<Path>>(&self, path: P) -> Result<QEntities, QEntitiesParseError> {
+        let file = fs::File::open(path)?;
+        self.parse(file)
+    }
+
+    /// Parse a reader as a q-entities file into an owned map of the key-values for each entity.
+    ///
+    /// If an entity has multiple key-values with the same key, then the value of the last such
+    /// key-value is retained within the map. See [`QEntities::into_owned_maps()`].
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn } { classname light light 100 light 300 }";
+    ///
+    /// let maps = QEntitiesParseOptions::new().parse_maps(&src[..]).unwrap();
+    /// assert_eq!(maps.len(), 2);
+    /// assert_eq!(maps[0][&b"classname"[..]], b"worldspawn");
+    /// assert_eq!(maps[1][&b"light"[..]], b"300");
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn parse_maps<R: io::Read>(
+        &self,
+        reader: R,
+    ) -> Result<Vec<HashMap<Vec<u8>, Vec<u8>>>, QEntitiesParseError> {
+        self.parse(reader)
+            .map(|entities| entities.into_owned_maps().collect())
+    }
+
+    /// Parse a reader as a q-entities file using the given hasher.
+    #[inline]
+    pub fn parse_with_hasher<R: io::Read, S: BuildHasher>(
+        &self,
+        reader: R,
+        hash_builder: S,
+    ) -> Result<QEntities, QEntitiesParseError> {
+        self.parse_peekable(BufferedReader::new(reader, self), hash_builder)
+            .map(|(entities, _)| entities)
+    }
+
+    /// Parse a [byte source](QEntitiesByteSource) as a q-entities file.
+    ///
+    /// This allows bytes to be parsed from sources that are not readers, such as ring buffers or
+    /// memory maps, without first copying them into a reader. Unlike [`parse()`](Self::parse), the
+    /// bytes are not read through an internal buffer, so the
+    /// [read buffer size](Self::read_buffer_size) has no effect.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesByteSource, QEntitiesParseOptions};
+    /// use std::collections::VecDeque;
+    /// use std::io;
+    ///
+    /// /// A source that yields the bytes of a queue.
+    /// struct QueueSource(VecDeque<u8>);
+    ///
+    /// impl QEntitiesByteSource for QueueSource {
+    ///     fn next_byte(&mut self) -> io::Result<Option<u8>> {
+    ///         Ok(self.0.pop_front())
+    ///     }
+    /// }
+    ///
+    /// let source = QueueSource(b"{ classname worldspawn }".iter().copied().collect());
+    /// let entities = QEntitiesParseOptions::new().parse_source(source).unwrap();
+    /// assert_eq!(entities.len(), 1);
+    /// ```
+    #[inline]
+    pub fn parse_source<B: QEntitiesByteSource>(
+        &self,
+        source: B,
+    ) -> Result<QEntities, QEntitiesParseError> {
+        self.parse_peekable(
+            PeekableByteSource::new(source),
+            DefaultHashBuilder::default(),
+        )
+        .map(|(entities, _)| entities)
+    }
+
+    /// Creates a builder for a collection parsed with these options using the given hasher.
+    fn builder<S: BuildHasher>(&self, hash_builder: S) -> QEntitiesBuilder<ByteChunksBuilder<S>> {
+        QEntitiesBuilder::with_chunk_capacity_intern_and_hasher(
+            self.expected_chunk_count,
+            !self.flags.contains(QEntitiesParseFlags::DISABLE_INTERNING),
+            hash_builder,
+        )
+    }
+
+    /// Parse a peekable source of bytes as a q-entities file using the given hasher, returning the
+    /// collection along with the warnings that were collected while parsing.
+    fn parse_peekable<P: PeekSource, S: BuildHasher>(
+        &self,
+        source: P,
+        hash_builder: S,
+    ) -> Result<(QEntities, Vec<QEntitiesWarning>), QEntitiesParseError> {
+        let mut builder = self.builder(hash_builder);
+        let mut parser = Parser::new(source, self);
+        parser
+            .parse(&mut builder)
+            .map_err(|e| parser.with_error_context(e))?;
+        Ok(parser.finish(builder))
+    }
+
+    /// Parse a reader as a q-entities file, returning the collection along with the warnings that
+    /// were collected while parsing.
+    ///
+    /// Warnings are only collected if [`collect_warnings()`](Self::collect_warnings) is enabled,
+    /// otherwise the returned warnings are always empty. Warnings are ordered by their location.
+    pub fn parse_with_warnings<R: io::Read>(
+        &self,
+        reader: R,
+    ) -> Result<(QEntities, Vec<QEntitiesWarning>), QEntitiesParseError> {
+        self.parse_peekable(
+            BufferedReader::new(reader, self),
+            DefaultHashBuilder::default(),
+        )
+    }
+
+    /// Parse a reader as a sequence of q-entities files that are separated by a separator byte.
+    ///
+    /// Each document is parsed as though it were the entire reader, except that a separator byte
+    /// encountered outside of a quoted string or comment terminates it in the same way as the EOF.
+    /// The returned iterator yields each document in turn until the EOF is reached or an error is
+    /// returned, after which it yields nothing more. A separator at the end of the reader does not
+    /// begin another document, such that a trailing separator is ignored.
+    ///
+    /// Locations reported by errors, and the [`line_index()`](QEntities::line_index) of each
+    /// document, are relative to the start of the reader rather than the start of the document.
+    /// The line index of each document only covers the lines from the one on which the document
+    /// begins.
+    ///
+    /// The separator should not be a byte that is otherwise significant to the grammar, such as
+    /// whitespace, a brace, or a quote.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let src = b"{ classname worldspawn }\0{ classname light }{ classname light }\0";
+    ///
+    /// let documents = QEntitiesParseOptions::new()
+    ///     .parse_multi(&src[..], b'\0')
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(documents.len(), 2);
+    /// assert_eq!(documents[0].len(), 1);
+    /// assert_eq!(documents[1].len(), 2);
+    /// ```
+    pub fn parse_multi<R: io::Read>(
+        &self,
+        reader: R,
+        separator: u8,
+    ) -> impl Iterator<Item = Result<QEntities, QEntitiesParseError>> {
+        self.parse_multi_with_warnings(reader, separator)
+            .map(|result| result.map(|(entities, _)| entities))
+    }
+
+    /// Parse a reader as a sequence of q-entities files that are separated by a separator byte,
+    /// yielding each document along with the warnings that were collected while parsing it.
+    ///
+    /// See [`parse_multi()`](Self::parse_multi) and
+    /// [`parse_with_warnings()`](Self::parse_with_warnings).
+    pub fn parse_multi_with_warnings<R: io::Read>(
+        &self,
+        reader: R,
+        separator: u8,
+    ) -> impl Iterator<Item = Result<(QEntities, Vec<QEntitiesWarning>), QEntitiesParseError>> {
+        MultiParser {
+            parts: Some(ParserParts::new(BufferedReader::new(reader, self), self)),
+            options: self.clone(),
+            separator,
+            state: MultiParserState::First,
+        }
+    }
+
+    /// Parse a reader as a q-entities file, storing its keys and values within an arena that is
+    /// shared with other collections.
+    ///
+    /// Keys and values are always interned within the arena regardless of
+    /// [`intern()`](Self::intern), and those already present within the arena are not stored again.
+    /// See [`QEntitiesArena`] for how the returned collection relates to the arena.
+    ///
+    /// If an error is returned, then the keys and values that were parsed before the error remain
+    /// stored within the arena.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::arena::QEntitiesArena;
+    /// use qentities::parse::QEntitiesParseOptions;
+    ///
+    /// let mut arena = QEntitiesArena::new();
+    /// let srcs = [
+    ///     &b"{ classname worldspawn wad base.wad }"[..],
+    ///     b"{ classname worldspawn wad base.wad message \"hello\" }",
+    /// ];
+    ///
+    /// let collections = srcs
+    ///     .iter()
+    ///     .map(|src| QEntitiesParseOptions::new().parse_in_arena(*src, &mut arena))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(collections.len(), 2);
+    /// assert_eq!(arena.chunk_count(), 6);
+    /// ```
+    pub fn parse_in_arena<R: io::Read>(
+        &self,
+        reader: R,
+        arena: &mut QEntitiesArena,
+    ) -> Result<QEntities, QEntitiesParseError> {
+        let mut builder = QEntitiesBuilder::in_arena(&mut arena.byte_chunks);
+        let mut parser = Parser::new(BufferedReader::new(reader, self), self);
+        parser
+            .parse(&mut builder)
+            .map_err(|e| parser.with_error_context(e))?;
+        Ok(parser.finish(builder).0)
+    }
+
+    /// Parse a reader as a q-entities file, passing each entity and key-value to a visitor as it
+    /// is parsed rather than constructing a collection.
+    ///
+    /// The visitor receives the entities and key-values that precede an error before the error is
+    /// returned. Any error returned by the visitor stops parsing and is returned as an
+    /// [`Io`](QEntitiesParseErrorKind::Io) error.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::{QEntitiesParseOptions, QEntitiesVisitor};
+    /// use std::io;
+    ///
+    /// /// Counts the key-values of every entity.
+    /// #[derive(Default)]
+    /// struct KeyValueCounter(Vec<usize>);
+    ///
+    /// impl QEntitiesVisitor for KeyValueCounter {
+    ///     fn begin_entity(&mut self) -> io::Result<()> {
+    ///         self.0.push(0);
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn key_value(&mut self, _key: &[u8], _value: &[u8]) -> io::Result<()> {
+    ///         *self.0.last_mut().unwrap() += 1;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let src = b"{ classname worldspawn wad a.wad } { classname light }";
+    ///
+    /// let mut counter = KeyValueCounter::default();
+    /// QEntitiesParseOptions::new()
+    ///     .parse_visitor(&src[..], &mut counter)
+    ///     .unwrap();
+    /// assert_eq!(counter.0, [2, 1]);
+    /// ```
+    pub fn parse_visitor<R: io::Read, V: QEntitiesVisitor + ?Sized>(
+        &self,
+        reader: R,
+        visitor: &mut V,
+    ) -> Result<(), QEntitiesParseError> {
+        let mut parser = Parser::new(BufferedReader::new(reader, self), self);
+        parser
+            .parse(visitor)
+            .map_err(|e| parser.with_error_context(e))
+    }
+}
+
+/// State of a [`MultiParser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MultiParserState {
+    /// No document has been parsed yet.
+    First,
+    /// The previous document was terminated by the separator.
+    Separated,
+    /// The EOF or an error has been reached.
+    Done,
+}
+
+/// Iterator over the documents of a reader that are parsed by
+/// [`QEntitiesParseOptions::parse_multi()`].
+///
+/// Each document is parsed by a new [`Parser`] that resumes from the parts of the parser of the
+/// previous document.
+struct MultiParser<R: io::Read> {
+    /// The parts of the parser of the previous document, or [`None`] if an error was reached.
+    parts: Option<ParserParts<BufferedReader<R>>>,
+    /// Options used for parsing each document.
+    options: QEntitiesParseOptions,
+    /// The byte that separates consecutive documents.
+    separator: u8,
+    /// The state of the iterator.
+    state: MultiParserState,
+}
+
+impl<R: io::Read> Iterator for MultiParser<R> {
+    type Item = Result<(QEntities, Vec<QEntitiesWarning>), QEntitiesParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == MultiParserState::Done {
+            return None;
+        }
+        let mut parser = Parser::from_parts(self.parts.take()?, &self.options);
+        parser.document_separator = Some(self.separator);
+
+        let mut builder = self.options.builder(DefaultHashBuilder::default());
+        let start_offset = parser.location.offset;
+        if let Err(e) = parser.parse(&mut builder) {
+            self.state = MultiParserState::Done;
+            return Some(Err(parser.with_error_context(e)));
+        }
+
+        let (entities, warnings) = parser.finish(builder);
+        if parser.at_document_separator {
+            self.state = MultiParserState::Separated;
+        } else {
+            // A separator at the end of the reader does not begin another document.
+            let trailing = self.state == MultiParserState::Separated
+                && entities.is_empty()
+                && parser.location.offset == start_offset;
+            self.state = MultiParserState::Done;
+            if trailing {
+                return None;
+            }
+        }
+
+        self.parts = Some(parser.into_parts());
+        Some(Ok((entities, warnings)))
+    }
+}
+
+/// A visitor of the entities and key-values of a q-entities file as they are parsed.
+///
+/// See [`QEntitiesParseOptions::parse_visitor()`].
+pub trait QEntitiesVisitor {
+    /// Visits the beginning of an entity.
+    #[inline]
+    fn begin_entity(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Visits a key-value of the most recently begun entity.
+    fn key_value(&mut self, key: &[u8], value: &[u8]) -> io::Result<()>;
+
+    /// Visits the end of the most recently begun entity.
+    #[inline]
+    fn end_entity(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<B: ByteChunksSink> QEntitiesVisitor for QEntitiesBuilder<B> {
+    #[inline]
+    fn begin_entity(&mut self) -> io::Result<()> {
+        self.push_entity();
+        Ok(())
+    }
+
+    #[inline]
+    fn key_value(&mut self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.push_key_value(key, value)
+            .map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))
+    }
+}
+
+// Options own no heap memory.
+#[cfg(feature = "deepsize")]
+deepsize::known_deep_size!(0; QEntitiesParseOptions);
+
+impl Default for QEntitiesParseOptions {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kinds of tokens that can appear within a q-entities file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QEntitiesTokenKind {
+    /// An open brace (`{`), or the configured byte that opens an entity.
+    ///
+    /// See [`QEntitiesParseOptions::entity_delimiters()`].
+    OpenBrace = b'{' as _,
+    /// A close brace (`}`), or the configured byte that closes an entity.
+    ///
+    /// See [`QEntitiesParseOptions::entity_delimiters()`].
+    CloseBrace = b'}' as _,
+    /// A quoted string (`"foo bar"`).
+    QuotedString = b'"' as _,
+    /// An unquoted string (`foo_bar`).
+    UnquotedString = 0,
+}
+
+impl QEntitiesTokenKind {
+    /// Gets the kind of token that begins with the given byte.
+    ///
+    /// Any byte that does not begin another kind of token begins an unquoted string. Note that this
+    /// does not account for parse options, such as single quotes beginning quoted strings when
+    /// [`single_quoted_strings()`](QEntitiesParseOptions::single_quoted_strings) is enabled, or the
+    /// configured [`entity_delimiters()`](QEntitiesParseOptions::entity_delimiters).
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesTokenKind;
+    ///
+    /// assert_eq!(QEntitiesTokenKind::from_head_byte(b'{'), QEntitiesTokenKind::OpenBrace);
+    /// assert_eq!(QEntitiesTokenKind::from_head_byte(b'"'), QEntitiesTokenKind::QuotedString);
+    /// assert_eq!(QEntitiesTokenKind::from_head_byte(b'a'), QEntitiesTokenKind::UnquotedString);
+    /// ```
+    #[inline]
+    pub fn from_head_byte(byte: u8) -> Self {
+        match byte {
+            b'{' => Self::OpenBrace,
+            b'}' => Self::CloseBrace,
+            b'"' => Self::QuotedString,
+            _ => Self::UnquotedString,
+        }
+    }
+
+    /// Gets the byte that begins the kind of token, if there is a single such byte.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesTokenKind;
+    ///
+    /// assert_eq!(QEntitiesTokenKind::CloseBrace.as_opening_byte(), Some(b'}'));
+    /// assert_eq!(QEntitiesTokenKind::UnquotedString.as_opening_byte(), None);
+    /// ```
+    #[inline]
+    pub fn as_opening_byte(self) -> Option<u8> {
+        match self {
+            Self::UnquotedString => None,
+            _ => Some(self as u8),
+        }
+    }
+}
+
+impl fmt::Display for QEntitiesTokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenBrace => write!(f, "{{"),
+            Self::CloseBrace => write!(f, "}}"),
+            Self::QuotedString => write!(f, "quoted string"),
+            Self::UnquotedString => write!(f, "unquoted string"),
+        }
+    }
+}
+
+/// The kinds of sources strings can be parsed from within a q-entities file.
+#[derive(Debug, Clone, Copy)]
+enum StringSourceKind {
+    /// The source is a key.
+    Key,
+    /// The source is a value.
+    Value,
+}
+
+/// The bytes that an escape sequence is decoded as.
+#[derive(Debug, Clone, Copy)]
+struct EscapedBytes {
+    /// Buffer holding the bytes.
+    buf: [u8; 4],
+    /// The number of bytes at the start of the buffer.
+    len: usize,
+}
+
+impl EscapedBytes {
+    /// Create escaped bytes consisting of a single byte.
+    #[inline]
+    fn byte(byte: u8) -> Self {
+        Self {
+            buf: [byte, 0, 0, 0],
+            len: 1,
+        }
+    }
+
+    /// Create escaped bytes consisting of the UTF-8 encoding of a character.
+    #[inline]
+    fn char(c: char) -> Self {
+        let mut buf = [0; 4];
+        let len = c.encode_utf8(&mut buf).len();
+        Self { buf, len }
+    }
+
+    /// Gets the escaped bytes as a slice.
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// A source of the bytes of a q-entities file that can be parsed with
+/// [`QEntitiesParseOptions::parse_source()`].
+///
+/// Byte sources are an alternative to readers for sources of bytes that are not readers, and that
+/// are read one byte at a time rather than into a buffer. Readers are instead parsed with
+/// [`QEntitiesParseOptions::parse()`], which reads them through an internal buffer.
+///
+/// The source is implemented for byte slices, which are parsed without being copied.
+///
+/// Errors of the source are [`io::Error`]s so that they can be reported in the same way as errors
+/// of readers. This ties the trait to `std`, so it does not yet allow the parser to be used without
+/// `std`.
+pub trait QEntitiesByteSource {
+    /// Reads the next byte of the source, or returns [`None`] if the source has no more bytes.
+    ///
+    /// Once [`None`] has been returned, a parser does not read from the source again. An error is
+    /// reported as an [`Io`](QEntitiesParseErrorKind::Io) error.
+    fn next_byte(&mut self) -> io::Result<Option<u8>>;
+}
+
+impl QEntitiesByteSource for &[u8] {
+    #[inline]
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.split_first().map(|(&byte, rest)| {
+            *self = rest;
+            byte
+        }))
+    }
+}
+
+impl<B: QEntitiesByteSource + ?Sized> QEntitiesByteSource for &mut B {
+    #[inline]
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        (**self).next_byte()
+    }
+}
+
+/// A source of bytes for [`Parser`] whose next byte can be peeked before it is consumed.
+trait PeekSource {
+    /// Returns the next unconsumed byte without consuming it.
+    fn peek(&mut self) -> Result<Option<u8>, io::Error>;
+
+    /// Attempts to consume the next byte.
+    fn take(&mut self) -> Result<Option<u8>, io::Error>;
+
+    /// Consumes a byte that was previously peeked.
+    ///
+    /// # Panics
+    /// In debug builds this function will panic if there does not actually exist a peeked byte,
+    /// while in release builds this function will merely return an erroneous but initialized
+    /// result.
+    #[must_use]
+    fn take_fresh(&mut self) -> u8;
+}
+
+/// A reader whose bytes are read through a [`ReadBuffer`].
+#[derive(Debug)]
+struct BufferedReader<R> {
+    /// The inner reader from which bytes are read.
+    reader: R,
+    /// Buffer of the bytes read from the reader.
+    read_buffer: ReadBuffer,
+}
+
+impl<R: io::Read> BufferedReader<R> {
+    /// Creates a new buffered reader whose buffer is sized by the given options.
+    #[inline]
+    fn new(reader: R, options: &QEntitiesParseOptions) -> Self {
+        Self {
+            reader,
+            read_buffer: ReadBuffer::new(options.read_buffer_size),
+        }
+    }
+}
+
+impl<R: io::Read> PeekSource for BufferedReader<R> {
+    #[inline(always)]
+    fn peek(&mut self) -> Result<Option<u8>, io::Error> {
+        self.read_buffer.peek_from(&mut self.reader)
+    }
+
+    #[inline(always)]
+    fn take(&mut self) -> Result<Option<u8>, io::Error> {
+        self.read_buffer.take_from(&mut self.reader)
+    }
+
+    #[inline(always)]
+    fn take_fresh(&mut self) -> u8 {
+        self.read_buffer.take_fresh()
+    }
+}
+
+/// A [byte source](QEntitiesByteSource) whose next byte is held once it has been peeked.
+#[derive(Debug)]
+struct PeekableByteSource<B> {
+    /// The inner source from which bytes are read.
+    source: B,
+    /// The byte that was peeked but not yet consumed, if any.
+    peeked: Option<u8>,
+    /// Whether or not the source has indicated that no more bytes are available.
+    eof: bool,
+}
+
+impl<B: QEntitiesByteSource> PeekableByteSource<B> {
+    /// Creates a new peekable source for which nothing has been peeked.
+    #[inline]
+    fn new(source: B) -> Self {
+        Self {
+            source,
+            peeked: None,
+            eof: false,
+        }
+    }
+}
+
+impl<B: QEntitiesByteSource> PeekSource for PeekableByteSource<B> {
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>, io::Error> {
+        if self.peeked.is_none() && !self.eof {
+            self.peeked = self.source.next_byte()?;
+            self.eof = self.peeked.is_none();
+        }
+        Ok(self.peeked)
+    }
+
+    #[inline]
+    fn take(&mut self) -> Result<Option<u8>, io::Error> {
+        self.peek()?;
+        Ok(self.peeked.take())
+    }
+
+    #[inline]
+    fn take_fresh(&mut self) -> u8 {
+        debug_assert!(self.peeked.is_some());
+        self.peeked.take().unwrap_or(0)
+    }
+}
+
+/// Type that handles buffering the bytes read from a reader for [`Parser`], such that the next
+/// byte can be peeked before it is consumed.
+struct ReadBuffer {
+    /// Buffer holding the bytes most recently read from the reader.
+    buf: Box<[u8]>,
+    /// Position of the next unconsumed byte within the buffer.
+    pos: usize,
+    /// The number of bytes at the start of the buffer that were read from the reader.
+    filled: usize,
+    /// Whether or not the reader has indicated that no more bytes are available.
+    eof: bool,
+}
+
+impl fmt::Debug for ReadBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadBuffer")
+            .field("capacity", &self.buf.len())
+            .field("pos", &self.pos)
+            .field("filled", &self.filled)
+            .field("eof", &self.eof)
+            .finish()
+    }
+}
+
+impl ReadBuffer {
+    /// Create a new empty read buffer that reads up to `capacity` bytes at a time.
+    ///
+    /// A capacity of zero is treated as a capacity of one.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            eof: false,
+        }
+    }
+
+    /// Either return the next unconsumed byte of the buffer or refill the buffer from the reader.
+    #[inline]
+    pub fn peek_from<R: io::Read>(&mut self, reader: &mut R) -> Result<Option<u8>, io::Error> {
+        if self.pos == self.filled {
+            if self.eof {
+                return Ok(None);
+            }
+            self.filled = reader.read(&mut self.buf)?;
+            self.pos = 0;
+            if self.filled == 0 {
+                self.eof = true;
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buf[self.pos]))
+    }
+
+    /// Attempt to consume the next byte, refilling the buffer from the given reader if needed.
+    #[inline]
+    pub fn take_from<R: io::Read>(&mut self, reader: &mut R) -> Result<Option<u8>, io::Error> {
+        let res = self.peek_from(reader)?;
+        if res.is_some() {
+            self.pos += 1;
+        }
+        Ok(res)
+    }
+
+    /// Assume that there exists a previously peeked byte that has not yet been consumed and
+    /// consume it.
+    ///
+    /// This is intended to be used in scenarios where the user knows that there is a peeked byte,
+    /// but the compiler may have a difficult time proving such.
+    ///
+    /// # Panics
+    /// In debug builds this function will panic if there does not actually exist a peeked byte,
+    /// while in release builds this function will merely return an erroneous but initialized
+    /// result.
+    #[inline]
+    #[must_use]
+    pub fn take_fresh(&mut self) -> u8 {
+        debug_assert!(self.pos < self.filled);
+        let byte = self.buf.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        byte
+    }
+}
+
+/// The default maximum number of bytes that are read from a reader at a time.
+const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// The maximum number of recently consumed bytes that are captured as the context for an error.
+const ERROR_CONTEXT_LENGTH: usize = 32;
+
+/// The parts of a [`Parser`] that describe its position within a reader.
+///
+/// A parser can be taken apart into its parts with [`Parser::into_parts()`] after stopping at a
+/// boundary within the reader, such as a document separator, and a new parser can then resume
+/// from that position with [`Parser::from_parts()`]. Bytes that were read from the reader but not
+/// yet consumed remain buffered within the source, so no bytes are lost between the parsers.
+struct ParserParts<P: PeekSource> {
+    /// The source from which bytes are read.
+    source: P,
+    /// The location within the reader.
+    location: QEntitiesParserLocation,
+    /// The offsets at which each line begins, if they are being recorded.
+    line_starts: Vec<u64>,
+    /// The number of the line that begins at the first of the line starts.
+    first_line: u64,
+    /// Whether or not the most recently consumed byte was a carriage return.
+    after_carriage_return: bool,
+}
+
+impl<P: PeekSource> ParserParts<P> {
+    /// Create the parts of a parser positioned at the start of a source.
+    #[inline]
+    fn new(source: P, options: &QEntitiesParseOptions) -> Self {
+        Self {
+            source,
+            location: options.start_location,
+            line_starts: vec![0],
+            first_line: 1,
+            after_carriage_return: false,
+        }
+    }
+}
+
+/// State for parsing the Quake entities format from a [`PeekSource`].
+///
+/// Note that this encapsulates the concepts of both a lexer and parser. These concepts are
+/// encapsulated into a single type primarily to avoid needing to parse out entire byte-chunks in
+/// contexts where the apperance of a byte-chunk is always an error.
+///
+/// The options are borrowed rather than owned so that parsing many small readers with the same
+/// options does not copy the options for each reader.
+struct Parser<'a, P: PeekSource> {
+    /// The source from which bytes are read.
+    source: P,
+    /// The parser's current location within the reader.
+    location: QEntitiesParserLocation,
+    /// options used for parsing.
+    options: &'a QEntitiesParseOptions,
+    /// The offsets at which each line begins, if they are being recorded.
+    line_starts: Vec<u64>,
+    /// The number of the line that begins at the first of the line starts.
+    first_line: u64,
+    /// The most recently consumed bytes, if they are being captured.
+    recent_bytes: VecDeque<u8>,
+    /// Whether or not the most recently consumed byte was a carriage return.
+    after_carriage_return: bool,
+    /// The byte that separates consecutive documents within the reader, if any.
+    document_separator: Option<u8>,
+    /// Whether or not the most recent parse was terminated by the document separator rather than
+    /// the EOF.
+    at_document_separator: bool,
+    /// The warnings collected while parsing, if they are being collected.
+    warnings: Vec<QEntitiesWarning>,
+    /// The bytes of the retained comments, if comments are being retained.
+    comment_bytes: Vec<u8>,
+    /// The offset within the comment bytes at which each retained comment ends.
+    comment_ends: Vec<usize>,
+    /// The number of retained comments that precede each entity.
+    entity_comment_ends: Vec<usize>,
+}
+
+impl<'a, P: PeekSource> Parser<'a, P> {
+    /// Create a new parser for a source.
+    #[inline]
+    fn new(source: P, options: &'a QEntitiesParseOptions) -> Self {
+        Self::from_parts(ParserParts::new(source, options), options)
+    }
+
+    /// Create a new parser that resumes from the parts of another parser.
+    ///
+    /// The source, location, and line starts of the parts are retained, while the remaining state of
+    /// the parser begins anew. The read buffer size and start location of the options are
+    /// disregarded.
+    fn from_parts(parts: ParserParts<P>, options: &'a QEntitiesParseOptions) -> Self {
+        let ParserParts {
+            source,
+            location,
+            line_starts,
+            first_line,
+            after_carriage_return,
+        } = parts;
+        Self {
+            source,
+            location,
+            options,
+            line_starts,
+            first_line,
+            recent_bytes: VecDeque::new(),
+            after_carriage_return,
+            document_separator: None,
+            at_document_separator: false,
+            warnings: Vec::new(),
+            comment_bytes: Vec::new(),
+            comment_ends: Vec::new(),
+            entity_comment_ends: Vec::new(),
+        }
+    }
+
+    /// Take the parser apart into the parts describing its position within the reader, such that
+    /// another parser can resume from that position with [`from_parts()`](Self::from_parts).
+    fn into_parts(self) -> ParserParts<P> {
+        ParserParts {
+            source: self.source,
+            location: self.location,
+            line_starts: self.line_starts,
+            first_line: self.first_line,
+            after_carriage_return: self.after_carriage_return,
+        }
+    }
+
+    /// Constructs the collection built from what the parser has parsed, along with what the parser
+    /// recorded alongside it, and takes the warnings that were collected.
+    fn finish<B: ByteChunksSink>(
+        &mut self,
+        builder: QEntitiesBuilder<B>,
+    ) -> (QEntities, Vec<QEntitiesWarning>) {
+        let mut entities = builder.finish();
+        entities.line_index = self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::RECORD_LINE_INDEX)
+            .then(|| self.take_line_index());
+        entities.comments = self.take_comments();
+        entities.parse_options = self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::RETAIN_PARSE_OPTIONS)
+            .then(|| Box::new(self.options.clone()));
+        (entities, core::mem::take(&mut self.warnings))
+    }
+
+    /// Takes the recorded line starts in the form stored by a collection.
+    ///
+    /// The start of the current line is kept, such that a parser that continues through further
+    /// documents begins the line index of the next document with the line on which it begins.
+    fn take_line_index(&mut self) -> QEntitiesLineIndex {
+        let current_line_start = self.line_starts.last().copied().unwrap_or(0);
+        let line_starts = core::mem::replace(&mut self.line_starts, vec![current_line_start]);
+        let first_line = self.first_line;
+        self.first_line = first_line.saturating_add(line_starts.len().saturating_sub(1) as u64);
+        QEntitiesLineIndex {
+            first_line,
+            line_starts: line_starts.into(),
+            end: self.location.offset - self.options.start_location.offset,
+        }
+    }
+
+    /// Takes the retained comments in the form stored by a collection if comments are being
+    /// retained.
+    fn take_comments(&mut self) -> Option<Box<QEntitiesComments>> {
+        self.retains_comments().then(|| {
+            Box::new(QEntitiesComments {
+                bytes: core::mem::take(&mut self.comment_bytes).into(),
+                ends: core::mem::take(&mut self.comment_ends).into(),
+                entity_ends: core::mem::take(&mut self.entity_comment_ends).into(),
+            })
+        })
+    }
+
+    /// Checks whether or not comments are being retained.
+    #[inline(always)]
+    fn retains_comments(&self) -> bool {
+        self.options
+            .flags
+            .contains(QEntitiesParseFlags::RETAIN_COMMENTS)
+    }
+
+    /// Associates the comments retained since the previous entity with an entity that is beginning.
+    #[inline]
+    fn begin_entity_comments(&mut self) {
+        if self.retains_comments() {
+            self.entity_comment_ends.push(self.comment_ends.len());
+        }
+    }
+
+    /// Discards the comments retained within an entity that is ending.
+    #[inline]
+    fn end_entity_comments(&mut self) {
+        if let Some(&count) = self.entity_comment_ends.last() {
+            self.comment_ends.truncate(count);
+            self.comment_bytes
+                .truncate(self.comment_ends.last().copied().unwrap_or(0));
+        }
+    }
+
+    /// Attaches the most recently consumed bytes to an error as its context if they are being
+    /// captured.
+    fn with_error_context(&self, mut e: QEntitiesParseError) -> QEntitiesParseError {
+        if self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::CAPTURE_ERROR_CONTEXT)
+        {
+            e.context = Some(self.recent_bytes.iter().copied().collect());
+        }
+        e
+    }
+
+    /// Peek the next unconsumed byte within the reader.
+    ///
+    /// A NUL byte is reported as the end of the reader if it terminates the input.
+    #[inline(always)]
+    fn peek_byte(&mut self) -> Result<Option<u8>, io::Error> {
+        let res = self.source.peek()?;
+        Ok(res.filter(|&byte| {
+            byte != 0
+                || !self
+                    .options
+                    .flags
+                    .contains(QEntitiesParseFlags::NUL_TERMINATES_INPUT)
+        }))
+    }
+
+    /// Attempt to read the next byte.
+    ///
+    /// This will implicitly move the location of the parser forward upon success. A NUL byte that
+    /// terminates the input is never consumed.
+    fn next_byte(&mut self) -> Result<Option<u8>, io::Error> {
+        if self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::NUL_TERMINATES_INPUT)
+        {
+            return Ok(self.peek_byte()?.map(|_| self.next_byte_fresh()));
+        }
+
+        let res = self.source.take()?;
+        if let Some(byte) = res {
+            self.advance_location(byte);
+        }
+        Ok(res)
+    }
+
+    /// Identical behavior to [`next_byte()`](Self::next_byte()) except that this function makes the
+    /// assumption that a previous peek was successful and returns the byte from that operation.
+    ///
+    /// # Panics
+    /// This function can panic under all the same circumstances that [`PeekSource::take_fresh()`]
+    /// may panic under.
+    #[inline]
+    #[must_use]
+    fn next_byte_fresh(&mut self) -> u8 {
+        let byte = self.source.take_fresh();
+        self.advance_location(byte);
+        byte
+    }
+
+    /// Advance the parser's location dependent upon the input byte.
+    fn advance_location(&mut self, byte: u8) {
+        if self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::CAPTURE_ERROR_CONTEXT)
+        {
+            if self.recent_bytes.len() == ERROR_CONTEXT_LENGTH {
+                self.recent_bytes.pop_front();
+            }
+            self.recent_bytes.push_back(byte);
+        }
+
+        // Locations saturate rather than overflow, which can only occur for start locations near
+        // the maximum offset, line, or column.
+        self.location.offset = self.location.offset.saturating_add(1);
+        match byte {
+            // A line feed following a carriage return completes a CRLF line ending, so rather than
+            // beginning another line it moves the start of the line begun by the carriage return.
+            b'\n' if self.after_carriage_return => {
+                if self
+                    .options
+                    .flags
+                    .contains(QEntitiesParseFlags::RECORD_LINE_INDEX)
+                {
+                    if let Some(line_start) = self.line_starts.last_mut() {
+                        *line_start = self.location.offset - self.options.start_location.offset;
+                    }
+                }
+            }
+            b'\n' | b'\r' => {
+                self.location.line = self.location.line.saturating_add(1);
+                self.location.column = 1;
+                if self
+                    .options
+                    .flags
+                    .contains(QEntitiesParseFlags::RECORD_LINE_INDEX)
+                {
+                    self.line_starts
+                        .push(self.location.offset - self.options.start_location.offset);
+                }
+            }
+            _ => {
+                self.location.column = self.location.column.saturating_add(1);
+            }
+        }
+        self.after_carriage_return = byte == b'\r';
+    }
+
+    /// Checks whether or not a byte is whitespace according to the configured whitespace set.
+    #[inline]
+    fn is_whitespace(&self, byte: u8) -> bool {
+        if self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::NARROW_WHITESPACE)
+        {
+            matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+        } else {
+            byte.is_ascii_whitespace()
+        }
+    }
+
+    /// Consumes bytes until the first new-line or EOF is encountered.
+    ///
+    /// The bytes preceding the new-line are retained if comments are being retained.
+    fn skip_cpp_style_comment(&mut self) -> Result<(), QEntitiesParseError> {
+        let retain = self.retains_comments();
+        while let Some(byte) = self.next_byte()? {
+            if matches!(byte, b'\n' | b'\r') {
+                break;
+            }
+            if retain {
+                self.comment_bytes.push(byte);
+            }
+        }
+        if retain {
+            self.comment_ends.push(self.comment_bytes.len());
+        }
+        Ok(())
+    }
+
+    /// Consumes bytes until the pattern `*/` is encountered.
+    ///
+    /// If no `*/` pattern is encountered before the EOF then an error is returned. The bytes
+    /// preceding the pattern are retained if comments are being retained.
+    fn skip_c_style_comment(&mut self) -> Result<(), QEntitiesParseError> {
+        // Compute the start location so that it can be returned if no termination pattern is
+        // encountered.
+        let start_loc = self.location.retreat_columns(2);
+
+        let retain = self.retains_comments();
+        while let Some(byte) = self.next_byte()? {
+            if byte == b'*' && matches!(self.peek_byte()?, Some(b'/')) {
+                let _ = self.next_byte_fresh();
+                if retain {
+                    self.comment_ends.push(self.comment_bytes.len());
+                }
+                return Ok(());
+            }
+            if retain {
+                self.comment_bytes.push(byte);
+            }
+        }
+
+        Err(ParseError::UnterminatedCStyleComment(start_loc).into())
+    }
+
+    /// Consumes bytes until a byte that is neither whitespace nor part of a comment is encountered
+    /// and returns that byte as well as its location.
     fn next_significant_byte(
         &mut self,
-    ) -> Result<Option<(u8, QEntitiesParserLocation)>, QEntitiesParseError> {
+    ) -> Result<Option<(u8, QEntitiesParserLocation)>, QEntitiesParseError> {
+        while let Some(byte) = self.peek_byte()? {
+            let token_loc = self.location;
+            let _ = self.next_byte_fresh();
+            match byte {
+                // Discard whitespace.
+                _ if self.is_whitespace(byte) => (),
+
+                // `/` may be part of a comment.
+                b'/' => match self.peek_byte()? {
+                    // `//` is a C++ style comment.
+                    Some(b'/')
+                        if self
+                            .options
+                            .flags
+                            .contains(QEntitiesParseFlags::CPP_STYLE_COMMENTS) =>
+                    {
+                        let _ = self.next_byte_fresh();
+                        self.skip_cpp_style_comment()?;
+                    }
+
+                    // `/*` is a C style comment.
+                    Some(b'*')
+                        if self
+                            .options
+                            .flags
+                            .contains(QEntitiesParseFlags::C_STYLE_COMMENTS) =>
+                    {
+                        let _ = self.next_byte_fresh();
+                        self.skip_c_style_comment()?;
+                    }
+
+                    // All other patterns are not comments.
+                    _ => return Ok(Some((byte, token_loc))),
+                },
+
+                // Everything else is a significant byte.
+                _ => {
+                    return Ok(Some((byte, token_loc)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether or not a byte opens or closes an entity.
+    #[inline]
+    fn is_entity_delimiter(&self, byte: u8) -> bool {
+        let (open, close) = self.options.entity_delimiters;
+        byte == open || byte == close
+    }
+
+    /// Consumes bytes for as long as they match the given pattern, returning whether or not every
+    /// byte of the pattern was matched.
+    fn next_bytes_match(&mut self, pattern: &[u8]) -> Result<bool, QEntitiesParseError> {
+        for &expected_byte in pattern {
+            if self.peek_byte()? != Some(expected_byte) {
+                return Ok(false);
+            }
+            let _ = self.next_byte_fresh();
+        }
+        Ok(true)
+    }
+
+    /// Returns an error if the most recently consumed byte is an illegal control byte within a
+    /// string.
+    fn check_control_byte(&self, byte: u8) -> Result<(), QEntitiesParseError> {
+        if byte < 0x20
+            && !self.is_whitespace(byte)
+            && self
+                .options
+                .flags
+                .contains(QEntitiesParseFlags::REJECT_CONTROL_BYTES)
+        {
+            Err(ParseError::IllegalControlByte(self.location.retreat_columns(1)).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gets the maximum length for a string's source kind.
+    fn string_source_max_length(&self, kind: StringSourceKind) -> usize {
+        match kind {
+            StringSourceKind::Key => self.options.max_key_length,
+            StringSourceKind::Value => self.options.max_value_length,
+        }
+    }
+
+    /// Helper to attempt pushing a byte to a buffer while additionally returning an error if doing
+    /// so would cause the buffer to exceeed a length limit.
+    fn push_string_buf(
+        kind: StringSourceKind,
+        buf: &mut Vec<u8>,
+        byte: u8,
+        max_length: usize,
+        start_location: QEntitiesParserLocation,
+    ) -> Result<(), QEntitiesParseError> {
+        if buf.len() < max_length {
+            buf.push(byte);
+            Ok(())
+        } else {
+            Err(match kind {
+                StringSourceKind::Key => ParseError::KeyTooLong(QEntitiesLengthError::new(
+                    QEntitiesParseErrorKind::KeyTooLong,
+                    start_location,
+                    max_length,
+                )),
+                StringSourceKind::Value => ParseError::ValueTooLong(QEntitiesLengthError::new(
+                    QEntitiesParseErrorKind::ValueTooLong,
+                    start_location,
+                    max_length,
+                )),
+            }
+            .into())
+        }
+    }
+
+    /// Attempts to consume the bytes following an already consumed `\`, returning the escaped
+    /// bytes if they form an escape sequence allowed by the escape options. Nothing is consumed if
+    /// the bytes do not form an allowed escape sequence, except for malformed unicode escape
+    /// sequences which are an error.
+    fn parse_escape_sequence(&mut self) -> Result<Option<EscapedBytes>, QEntitiesParseError> {
+        let flags = self.options.flags;
+        let escape_byte = match self.peek_byte()? {
+            Some(b'u') if flags.contains(QEntitiesParseFlags::ESCAPE_UNICODE) => {
+                let back_slash_location = self.location.retreat_columns(1);
+                let _ = self.next_byte_fresh();
+
+                let mut code_point = 0;
+                for _ in 0..4 {
+                    match self
+                        .peek_byte()?
+                        .and_then(|byte| char::from(byte).to_digit(16))
+                    {
+                        Some(digit) => {
+                            let _ = self.next_byte_fresh();
+                            code_point = code_point << 4 | digit;
+                        }
+                        None => {
+                            return Err(
+                                ParseError::InvalidEscapeSequence(back_slash_location).into()
+                            )
+                        }
+                    }
+                }
+
+                // Surrogates are not valid code points on their own.
+                return match char::from_u32(code_point) {
+                    Some(c) => Ok(Some(EscapedBytes::char(c))),
+                    None => Err(ParseError::InvalidEscapeSequence(back_slash_location).into()),
+                };
+            }
+            Some(escape_byte @ b'\\') => escape_byte,
+            Some(escape_byte @ b'"')
+                if flags.contains(QEntitiesParseFlags::ESCAPE_DOUBLE_QUOTES) =>
+            {
+                escape_byte
+            }
+            Some(escape_byte @ b'\'')
+                if flags.contains(QEntitiesParseFlags::ESCAPE_SINGLE_QUOTES) =>
+            {
+                escape_byte
+            }
+            Some(escape_byte)
+                if flags.contains(QEntitiesParseFlags::ESCAPE_BRACES)
+                    && self.is_entity_delimiter(escape_byte) =>
+            {
+                escape_byte
+            }
+            Some(escape_byte)
+                if flags.contains(QEntitiesParseFlags::ESCAPE_WHITESPACE)
+                    && self.is_whitespace(escape_byte) =>
+            {
+                escape_byte
+            }
+            _ => return Ok(None),
+        };
+        let _ = self.next_byte_fresh();
+        Ok(Some(EscapedBytes::byte(escape_byte)))
+    }
+
+    /// Handles an already consumed `\` within an unquoted string by pushing the escaped byte to the
+    /// given buffer.
+    ///
+    /// If escape sequences are not parsed within unquoted strings, then only braces can be escaped
+    /// and the back-slash itself is pushed when not followed by a brace.
+    fn parse_unquoted_escape_sequence(
+        &mut self,
+        source_kind: StringSourceKind,
+        buf: &mut Vec<u8>,
+        max_length: usize,
+        start_location: QEntitiesParserLocation,
+    ) -> Result<(), QEntitiesParseError> {
+        let escaped = if self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::ESCAPE_UNQUOTED)
+        {
+            match self.parse_escape_sequence()? {
+                Some(escaped) => escaped,
+                None => {
+                    return Err(
+                        ParseError::InvalidEscapeSequence(self.location.retreat_columns(1)).into(),
+                    )
+                }
+            }
+        } else {
+            match self.peek_byte()? {
+                Some(escape_byte) if self.is_entity_delimiter(escape_byte) => {
+                    let _ = self.next_byte_fresh();
+                    EscapedBytes::byte(escape_byte)
+                }
+                _ => EscapedBytes::byte(b'\\'),
+            }
+        };
+        for &byte in escaped.as_slice() {
+            Self::push_string_buf(source_kind, buf, byte, max_length, start_location)?;
+        }
+        Ok(())
+    }
+
+    /// Reads bytes from the inner reader into given buffer until a terminating quote byte matching
+    /// the opening quote byte is encountered.
+    fn parse_quoted_string(
+        &mut self,
+        source_kind: StringSourceKind,
+        quote: u8,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), QEntitiesParseError> {
+        buf.clear();
+
+        let max_length = self.string_source_max_length(source_kind);
+
+        // Compute the start location so that it can be returned if an error is encountered.
+        let start_location = self.location.retreat_columns(1);
+
+        while let Some(byte) = self.next_byte()? {
+            match byte {
+                // The opening quote terminates the string.
+                _ if byte == quote => {
+                    return Ok(());
+                }
+
+                // `\` can be used to escape other bytes.
+                b'\\' if self.options.flags.contains(QEntitiesParseFlags::ESCAPE) => {
+                    match self.parse_escape_sequence()? {
+                        Some(escaped) => {
+                            for &byte in escaped.as_slice() {
+                                Self::push_string_buf(
+                                    source_kind,
+                                    buf,
+                                    byte,
+                                    max_length,
+                                    start_location,
+                                )?;
+                            }
+                        }
+                        None => {
+                            return Err(ParseError::InvalidEscapeSequence(
+                                self.location.retreat_columns(1),
+                            )
+                            .into())
+                        }
+                    }
+                }
+
+                // All other bytes are part of the string.
+                _ => {
+                    self.check_control_byte(byte)?;
+                    Self::push_string_buf(source_kind, buf, byte, max_length, start_location)?;
+                }
+            }
+        }
+
+        Err(ParseError::UnterminatedQuotedString {
+            location: start_location,
+            quote,
+        }
+        .into())
+    }
+
+    /// Reads bytes from the inner reader into given bufer until some terminating byte is
+    /// encountered.
+    fn parse_unquoted_string(
+        &mut self,
+        source_kind: StringSourceKind,
+        head_byte: u8,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), QEntitiesParseError> {
+        buf.clear();
+
+        let max_length = self.string_source_max_length(source_kind);
+
+        // Compute the start location so that it can be returned if an error is encountered.
+        let start_location = self.location.retreat_columns(1);
+
+        let flags = self.options.flags;
+        let escape = flags.contains(QEntitiesParseFlags::ESCAPE_BRACES)
+            || flags.contains(QEntitiesParseFlags::ESCAPE | QEntitiesParseFlags::ESCAPE_UNQUOTED);
+
+        if head_byte == b'\\' && escape {
+            self.parse_unquoted_escape_sequence(source_kind, buf, max_length, start_location)?;
+        } else {
+            self.check_control_byte(head_byte)?;
+            Self::push_string_buf(source_kind, buf, head_byte, max_length, start_location)?;
+        }
+
         while let Some(byte) = self.peek_byte()? {
-            let token_loc = self.location;
-            let _ = self.next_byte_fresh();
             match byte {
-                // Discard whitespace.
-                _ if byte.is_ascii_whitespace() => (),
+                // Consume whitespace since it is not significant.
+                _ if self.is_whitespace(byte) => {
+                    let _ = self.next_byte_fresh();
+                    break;
+                }
+
+                // The document separator terminates strings so that it can be re-parsed.
+                _ if self.document_separator == Some(byte) => {
+                    break;
+                }
+
+                // The key-value separator terminates keys so that it can be re-parsed.
+                _ if matches!(source_kind, StringSourceKind::Key)
+                    && self.options.key_value_separator == Some(byte) =>
+                {
+                    break;
+                }
+
+                // `\` can be used to escape other bytes.
+                b'\\' if escape => {
+                    let _ = self.next_byte_fresh();
+                    self.parse_unquoted_escape_sequence(
+                        source_kind,
+                        buf,
+                        max_length,
+                        start_location,
+                    )?;
+                }
+
+                // Explicit control bytes just break so that they can be re-parsed.
+                _ if (byte == b'"' || self.is_entity_delimiter(byte))
+                    && self
+                        .options
+                        .flags
+                        .contains(QEntitiesParseFlags::CONTROLS_TERMINATE_UNQUOTED_STRINGS) =>
+                {
+                    break;
+                }
+                b'\''
+                    if self.options.flags.contains(
+                        QEntitiesParseFlags::CONTROLS_TERMINATE_UNQUOTED_STRINGS
+                            | QEntitiesParseFlags::SINGLE_QUOTED_STRINGS,
+                    ) =>
+                {
+                    break;
+                }
+
+                // `/` is special because it can be a comment. If it is a comment then we'll consume
+                // the comment and break, but otherwise the `/` is part of the string.
+                b'/' if self
+                    .options
+                    .flags
+                    .contains(QEntitiesParseFlags::COMMENTS_TERMINATE_UNQUOTED_STRINGS) =>
+                {
+                    let _ = self.next_byte_fresh();
+                    match self.peek_byte()? {
+                        // `//` is a C++ style comment.
+                        Some(b'/')
+                            if self
+                                .options
+                                .flags
+                                .contains(QEntitiesParseFlags::CPP_STYLE_COMMENTS) =>
+                        {
+                            let _ = self.next_byte_fresh();
+                            self.skip_cpp_style_comment()?;
+                            break;
+                        }
+
+                        // `/*` is a C style comment.
+                        Some(b'*')
+                            if self
+                                .options
+                                .flags
+                                .contains(QEntitiesParseFlags::C_STYLE_COMMENTS) =>
+                        {
+                            let _ = self.next_byte_fresh();
+                            self.skip_c_style_comment()?;
+                            break;
+                        }
+
+                        // All other patterns are not comments. Note that the second byte is not
+                        // consumed because it may whitespace or a control byte.
+                        _ => {
+                            Self::push_string_buf(
+                                source_kind,
+                                buf,
+                                byte,
+                                max_length,
+                                start_location,
+                            )?;
+                        }
+                    }
+                }
+
+                // All other bytes are part of the string.
+                _ => {
+                    let _ = self.next_byte_fresh();
+                    self.check_control_byte(byte)?;
+                    Self::push_string_buf(source_kind, buf, byte, max_length, start_location)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse<V: QEntitiesVisitor + ?Sized>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<(), QEntitiesParseError> {
+        /// State the parser can be in.
+        #[derive(Debug, Clone, Copy)]
+        #[allow(clippy::enum_variant_names)]
+        enum ParseState {
+            /// The parser is searching for the next entity.
+            NextEntity,
+            /// The parser is searching for a key.
+            NextKey,
+            /// The parser is searching for the separator between a key and its value.
+            NextSeparator,
+            /// The parser is searching for a value.
+            NextValue,
+        }
+
+        // The state that follows a key.
+        let after_key_state = match self.options.key_value_separator {
+            Some(_) => ParseState::NextSeparator,
+            None => ParseState::NextValue,
+        };
+        let allow_valueless_keys = self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::ALLOW_VALUELESS_KEYS);
+
+        // The kinds of tokens that are valid in each state, used for reporting unexpected tokens.
+        // A separator is an unquoted string, and so is not expected in its own right.
+        const EXPECTED_ENTITY: &[QEntitiesTokenKind] = &[QEntitiesTokenKind::OpenBrace];
+        const EXPECTED_KEY: &[QEntitiesTokenKind] = &[
+            QEntitiesTokenKind::CloseBrace,
+            QEntitiesTokenKind::QuotedString,
+            QEntitiesTokenKind::UnquotedString,
+        ];
+
+        // Whether or not the key-values of the file are being parsed as an implicit entity.
+        let mut implicit_entity = false;
+
+        // Location at which the last entity began. This is used to return an error if the EOF is
+        // reached while still parsing an entity.
+        let mut entity_start_loc = QEntitiesParserLocation {
+            offset: 0,
+            line: 0,
+            column: 0,
+        };
+
+        // Counts of the entities and of the last entity's key-values, used for enforcing limits.
+        let mut entities_length = 0;
+        let mut entity_kvs_length = 0;
+
+        // Scratch buffers which are used to store keys and values. They are cleared rather than
+        // replaced before each string so that their capacity is reused across key-values.
+        let mut key_scratch = Vec::new();
+        let mut value_scratch = Vec::new();
+
+        // Location of the last key, used for reporting duplicate keys.
+        let mut key_location = entity_start_loc;
+
+        // Key-values of the current entity which are buffered if duplicate keys are not kept, along
+        // with a map from their keys to their indices within the buffer.
+        let keep_duplicate_keys = !self.options.flags.intersects(
+            QEntitiesParseFlags::DUPLICATE_KEYS_ERROR | QEntitiesParseFlags::DUPLICATE_KEYS_REPLACE,
+        );
+        let mut entity_kvs: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut entity_keys: HashMap<Vec<u8>, usize> = HashMap::new();
+
+        // If warnings are being collected, then the keys of the current entity are tracked within
+        // the same map when duplicate keys are kept.
+        let collect_warnings = self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::COLLECT_WARNINGS);
+        let lowercase_keys = self
+            .options
+            .flags
+            .contains(QEntitiesParseFlags::LOWERCASE_KEYS);
+
+        /// Passes the buffered key-values of the current entity to the visitor.
+        fn visit_buffered<V: QEntitiesVisitor + ?Sized>(
+            visitor: &mut V,
+            entity_kvs: &mut Vec<(Vec<u8>, Vec<u8>)>,
+            entity_keys: &mut HashMap<Vec<u8>, usize>,
+        ) -> io::Result<()> {
+            entity_keys.clear();
+            for (key, value) in entity_kvs.drain(..) {
+                visitor.key_value(&key, &value)?;
+            }
+            Ok(())
+        }
+
+        let mut state = ParseState::NextEntity;
+        self.at_document_separator = false;
+        while let Some((token_head_byte, token_location)) = self.next_significant_byte()? {
+            // The document separator terminates the document as though it were the EOF.
+            if self.document_separator == Some(token_head_byte) {
+                self.at_document_separator = true;
+                break;
+            }
+
+            let token_kind = match token_head_byte {
+                _ if token_head_byte == self.options.entity_delimiters.0 => {
+                    QEntitiesTokenKind::OpenBrace
+                }
+                _ if token_head_byte == self.options.entity_delimiters.1 => {
+                    QEntitiesTokenKind::CloseBrace
+                }
+                // Braces are ordinary bytes when other bytes delimit entities.
+                b'{' | b'}' => QEntitiesTokenKind::UnquotedString,
+                b'\''
+                    if self
+                        .options
+                        .flags
+                        .contains(QEntitiesParseFlags::SINGLE_QUOTED_STRINGS) =>
+                {
+                    QEntitiesTokenKind::QuotedString
+                }
+                _ => QEntitiesTokenKind::from_head_byte(token_head_byte),
+            };
+
+            // The first string of a file lacking braces begins an implicit entity.
+            if let (
+                ParseState::NextEntity,
+                QEntitiesTokenKind::QuotedString | QEntitiesTokenKind::UnquotedString,
+                0,
+            ) = (state, token_kind, entities_length)
+            {
+                if self
+                    .options
+                    .flags
+                    .contains(QEntitiesParseFlags::IMPLICIT_TOP_LEVEL_ENTITY)
+                {
+                    entity_start_loc = token_location;
+                    if self.options.max_entities == 0 {
+                        return Err(ParseError::TooManyEntities(entity_start_loc).into());
+                    }
+                    entities_length += 1;
+                    entity_kvs_length = 0;
+                    implicit_entity = true;
+                    self.begin_entity_comments();
+                    visitor.begin_entity()?;
+                    state = ParseState::NextKey;
+                }
+            }
+
+            // A close brace in place of the separator terminates a valueless key.
+            if let (ParseState::NextSeparator, QEntitiesTokenKind::CloseBrace, true, false) =
+                (state, token_kind, allow_valueless_keys, implicit_entity)
+            {
+                state = ParseState::NextValue;
+            }
+
+            state = match state {
+                ParseState::NextEntity => match token_kind {
+                    QEntitiesTokenKind::OpenBrace => {
+                        entity_start_loc = token_location;
+
+                        if entities_length < self.options.max_entities {
+                            entities_length += 1;
+                            entity_kvs_length = 0;
+                            self.begin_entity_comments();
+                            visitor.begin_entity()?;
+                        } else {
+                            return Err(ParseError::TooManyEntities(entity_start_loc).into());
+                        }
+
+                        ParseState::NextKey
+                    }
+
+                    // A leading `<!--` is the header of a KeyValues3 file, but following an entity
+                    // it is no different from any other unquoted string.
+                    QEntitiesTokenKind::UnquotedString
+                        if entities_length == 0
+                            && token_head_byte == b'<'
+                            && self.next_bytes_match(b"!--")? =>
+                    {
+                        return Err(ParseError::UnsupportedFormat(token_location).into());
+                    }
+
+                    // Anything else following an entity is trailing content.
+                    _ if entities_length > 0
+                        && self.options.flags.contains(QEntitiesParseFlags::STRICT_EOF) =>
+                    {
+                        return Err(ParseError::TrailingContent(token_location).into());
+                    }
+
+                    _ => {
+                        return Err(QEntitiesUnexpectedTokenError::new(
+                            token_kind,
+                            token_location,
+                            EXPECTED_ENTITY,
+                            self.options.entity_delimiters,
+                        )
+                        .into())
+                    }
+                },
+
+                ParseState::NextKey => match token_kind {
+                    QEntitiesTokenKind::CloseBrace if !implicit_entity => {
+                        if collect_warnings && entity_kvs_length == 0 {
+                            self.warnings.push(QEntitiesWarning::new(
+                                QEntitiesWarningKind::EmptyEntity,
+                                entity_start_loc,
+                            ));
+                        }
+                        self.end_entity_comments();
+                        visit_buffered(visitor, &mut entity_kvs, &mut entity_keys)?;
+                        visitor.end_entity()?;
+                        ParseState::NextEntity
+                    }
+
+                    QEntitiesTokenKind::QuotedString => {
+                        if entity_kvs_length < self.options.max_entity_kvs {
+                            key_location = token_location;
+                            self.parse_quoted_string(
+                                StringSourceKind::Key,
+                                token_head_byte,
+                                &mut key_scratch,
+                            )?;
+
+                            // Only quoted keys can be empty.
+                            if key_scratch.is_empty()
+                                && self
+                                    .options
+                                    .flags
+                                    .contains(QEntitiesParseFlags::REJECT_EMPTY_KEYS)
+                            {
+                                return Err(ParseError::EmptyKey(token_location).into());
+                            }
+
+                            after_key_state
+                        } else {
+                            return Err(ParseError::TooManyEntityKeyValues(token_location).into());
+                        }
+                    }
+
+                    QEntitiesTokenKind::UnquotedString => {
+                        if entity_kvs_length < self.options.max_entity_kvs {
+                            key_location = token_location;
+                            self.parse_unquoted_string(
+                                StringSourceKind::Key,
+                                token_head_byte,
+                                &mut key_scratch,
+                            )?;
+                            after_key_state
+                        } else {
+                            return Err(ParseError::TooManyEntityKeyValues(token_location).into());
+                        }
+                    }
+
+                    _ => {
+                        let expected = if implicit_entity {
+                            &EXPECTED_KEY[1..]
+                        } else {
+                            EXPECTED_KEY
+                        };
+                        return Err(QEntitiesUnexpectedTokenError::new(
+                            token_kind,
+                            token_location,
+                            expected,
+                            self.options.entity_delimiters,
+                        )
+                        .into());
+                    }
+                },
+
+                ParseState::NextSeparator => {
+                    if Some(token_head_byte) == self.options.key_value_separator {
+                        ParseState::NextValue
+                    } else {
+                        return Err(ParseError::MissingSeparator(token_location).into());
+                    }
+                }
+
+                ParseState::NextValue => {
+                    let next_state = match token_kind {
+                        QEntitiesTokenKind::QuotedString => {
+                            self.parse_quoted_string(
+                                StringSourceKind::Value,
+                                token_head_byte,
+                                &mut value_scratch,
+                            )?;
+                            ParseState::NextKey
+                        }
+
+                        QEntitiesTokenKind::UnquotedString => {
+                            self.parse_unquoted_string(
+                                StringSourceKind::Value,
+                                token_head_byte,
+                                &mut value_scratch,
+                            )?;
+                            ParseState::NextKey
+                        }
+
+                        // A close brace gives the key an empty value and terminates the entity.
+                        QEntitiesTokenKind::CloseBrace
+                            if allow_valueless_keys && !implicit_entity =>
+                        {
+                            value_scratch.clear();
+                            ParseState::NextEntity
+                        }
+
+                        _ => {
+                            let expected = if allow_valueless_keys && !implicit_entity {
+                                EXPECTED_KEY
+                            } else {
+                                &EXPECTED_KEY[1..]
+                            };
+                            return Err(QEntitiesUnexpectedTokenError::new(
+                                token_kind,
+                                token_location,
+                                expected,
+                                self.options.entity_delimiters,
+                            )
+                            .into());
+                        }
+                    };
+
+                    // Keys are lowercased before being compared against the other keys of the
+                    // entity so that keys differing only in case are duplicates.
+                    if lowercase_keys {
+                        key_scratch.make_ascii_lowercase();
+                    }
+
+                    entity_kvs_length += 1;
+                    if keep_duplicate_keys {
+                        if collect_warnings && entity_keys.insert(key_scratch.clone(), 0).is_some()
+                        {
+                            self.warnings.push(QEntitiesWarning::new(
+                                QEntitiesWarningKind::DuplicateKey,
+                                key_location,
+                            ));
+                        }
+                        visitor.key_value(&key_scratch, &value_scratch)?;
+                    } else if let Some(&kv_index) = entity_keys.get(&key_scratch) {
+                        if self
+                            .options
+                            .flags
+                            .contains(QEntitiesParseFlags::DUPLICATE_KEYS_ERROR)
+                        {
+                            return Err(ParseError::DuplicateKey(key_location).into());
+                        }
+                        if collect_warnings {
+                            self.warnings.push(QEntitiesWarning::new(
+                                QEntitiesWarningKind::DuplicateKey,
+                                key_location,
+                            ));
+                        }
+                        entity_kvs[kv_index].1.clone_from(&value_scratch);
+                    } else {
+                        entity_keys.insert(key_scratch.clone(), entity_kvs.len());
+                        entity_kvs.push((key_scratch.clone(), value_scratch.clone()));
+                    }
+
+                    if let ParseState::NextEntity = next_state {
+                        self.end_entity_comments();
+                        visit_buffered(visitor, &mut entity_kvs, &mut entity_keys)?;
+                        visitor.end_entity()?;
+                    }
+
+                    next_state
+                }
+            };
+        }
+
+        match state {
+            ParseState::NextEntity => Ok(()),
+
+            // An implicit entity is terminated by the end of the file.
+            ParseState::NextKey if implicit_entity => {
+                self.end_entity_comments();
+                visit_buffered(visitor, &mut entity_kvs, &mut entity_keys)?;
+                visitor.end_entity()?;
+                Ok(())
+            }
+
+            _ => Err(ParseError::UnterminatedEntity(entity_start_loc).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bstr::BStr;
+
+    /// Sample q-entities file from _Vampire The Masquerade: Bloodlines_.
+    #[rustfmt::skip]
+    const VTMB_DATA: &[u8] =
+br#"// vtmb
+{
+"world_maxs" "4096 4096 4096"
+"world_mins" "-4096 -4096 -4096"
+"classname" "worldspawn"
+"skyname" "thesky"
+"sounds" "1"
+"MaxRange" "1337"
+"fogcolor" "255 255 255"
+"fogcolor2" "255 255 255"
+"fogdir" "0 1 0"
+"fogstart" "123.0"
+"fogend" "456.0"
+"wetness_fadetarget" "0.11"
+"wetness_fadein" "2.3"
+"wetness_fadeout" "5.4"
+"levelscript" "thescript"
+"safearea" "2"
+"nosferatu_tolerrant" "1"
+}
+{
+"classname" "logic_relay"
+"StartDisabled" "0"
+"targetname" "relay_a"
+"spawnflags" "1"
+"OnTrigger" ",,,0,-1,ScriptFn(\"arg_a\", \"arg_b\"),"
+"origin" "1 2 3"
+}
+{
+"classname" "logic_relay"
+"StartDisabled" "0"
+"targetname" "relay_b"
+"spawnflags" "1"
+"OnTrigger" ",,,0,-1,ScriptFn(\"a\", \"b\", \"c\"),"
+"origin" "4 5 6"
+}"#;
+
+    /// Variant for an expected error in [`ExpectedError`].
+    #[derive(Clone, Copy)]
+    enum ExpectedErrorVariant {
+        SimpleKind(QEntitiesParseErrorKind),
+        UnexpectedToken(QEntitiesTokenKind),
+    }
+
+    /// Helper for asserting that an error occured as expected.
+    struct ExpectedError<'a> {
+        src: &'a [u8],
+        kind: ExpectedErrorVariant,
+        location: QEntitiesParserLocation,
+    }
+
+    impl ExpectedError<'_> {
+        /// Asserts that the expected error occured while parsing with the given parse options.
+        fn test(&self, parse_opts: &QEntitiesParseOptions) {
+            match parse_opts.parse(self.src) {
+                Ok(_) => panic!(
+                    "parsing of {:?} unexpectedly succeeded",
+                    BStr::new(self.src),
+                ),
+                Err(e) => {
+                    match self.kind {
+                        ExpectedErrorVariant::SimpleKind(k) => assert_eq!(
+                            e.kind(),
+                            k,
+                            "parsing of {:?} returned unexpected error kind",
+                            BStr::new(self.src),
+                        ),
+                        ExpectedErrorVariant::UnexpectedToken(tk) => {
+                            assert_eq!(
+                                e.kind(),
+                                QEntitiesParseErrorKind::UnexpectedToken,
+                                "parsing of {:?} returned unexpected error kind",
+                                BStr::new(self.src),
+                            );
+                            let tke = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+                            assert_eq!(
+                                tke.kind(),
+                                tk,
+                                "parsing of {:?} returned unexpected token kind",
+                                BStr::new(self.src),
+                            );
+                        }
+                    };
+                    assert_eq!(
+                        e.location().expect("error kind must carry a location"),
+                        &self.location,
+                        "parsing of {:?} returned unexpected error location",
+                        BStr::new(self.src),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unterminated_c_style_comments() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(
+                    QEntitiesParseErrorKind::UnterminatedCStyleComment,
+                ),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new().with_c_style_comments(true);
+        [
+            expected_error(
+                br#"/*"#,
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"\n/*",
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                br#"/**"#,
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                br#"/***"#,
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                br#"{"k"/*"v"}"#,
+                QEntitiesParserLocation {
+                    offset: 4,
+                    line: 1,
+                    column: 5,
+                },
+            ),
+            expected_error(
+                br#"{"k" "v"}/*"#,
+                QEntitiesParserLocation {
+                    offset: 9,
+                    line: 1,
+                    column: 10,
+                },
+            ),
+            expected_error(
+                br#"{ k /* v }"#,
+                QEntitiesParserLocation {
+                    offset: 4,
+                    line: 1,
+                    column: 5,
+                },
+            ),
+            expected_error(
+                br#"{ k v }/*"#,
+                QEntitiesParserLocation {
+                    offset: 7,
+                    line: 1,
+                    column: 8,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn unterminated_quoted_strings() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(
+                    QEntitiesParseErrorKind::UnterminatedQuotedString,
+                ),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new();
+        [
+            expected_error(
+                br#"{""#,
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 1,
+                    column: 2,
+                },
+            ),
+            expected_error(
+                br#"{"key" ""#,
+                QEntitiesParserLocation {
+                    offset: 7,
+                    line: 1,
+                    column: 8,
+                },
+            ),
+            expected_error(
+                b"{k\n\"",
+                QEntitiesParserLocation {
+                    offset: 3,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"{k \"v}",
+                QEntitiesParserLocation {
+                    offset: 3,
+                    line: 1,
+                    column: 4,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn unterminated_entities() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::UnterminatedEntity),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new();
+        [
+            expected_error(
+                b"{",
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"\n{",
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"{ k v }{",
+                QEntitiesParserLocation {
+                    offset: 7,
+                    line: 1,
+                    column: 8,
+                },
+            ),
+            expected_error(
+                b"{ k v }\n{",
+                QEntitiesParserLocation {
+                    offset: 8,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn invalid_escape_sequences() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(
+                    QEntitiesParseErrorKind::InvalidEscapeSequence,
+                ),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_escape_options(Some(QEntitiesParseEscapeOptions::new()));
+        [
+            expected_error(
+                br#"{"\x" "value"}"#,
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 1,
+                    column: 3,
+                },
+            ),
+            expected_error(
+                b"{k\n\"\\x\"}",
+                QEntitiesParserLocation {
+                    offset: 4,
+                    line: 2,
+                    column: 2,
+                },
+            ),
+            expected_error(
+                b"{\"\\\x00\" \"\"}",
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 1,
+                    column: 3,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn nested_entities() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::OpenBrace),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new();
+        [
+            expected_error(
+                b"{{",
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 1,
+                    column: 2,
+                },
+            ),
+            expected_error(
+                b"\n{{",
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 2,
+                    column: 2,
+                },
+            ),
+            expected_error(
+                b"{\n{",
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"\n{\n{",
+                QEntitiesParserLocation {
+                    offset: 3,
+                    line: 3,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"{ k v { k v } k v }",
+                QEntitiesParserLocation {
+                    offset: 6,
+                    line: 1,
+                    column: 7,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn unpaired_close_braces() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::CloseBrace),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new();
+        [
+            expected_error(
+                b"}",
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"\n}",
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                b"{}}",
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 1,
+                    column: 3,
+                },
+            ),
+            expected_error(
+                b"{ k v }}",
+                QEntitiesParserLocation {
+                    offset: 7,
+                    line: 1,
+                    column: 8,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn exterior_key_values() {
+        fn expected_error(
+            kind: QEntitiesTokenKind,
+            src: &[u8],
+            location: QEntitiesParserLocation,
+        ) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::UnexpectedToken(kind),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new();
+        [
+            expected_error(
+                QEntitiesTokenKind::QuotedString,
+                b"\"k\" \"v\"",
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                QEntitiesTokenKind::UnquotedString,
+                b"k v",
+                QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            ),
+            expected_error(
+                QEntitiesTokenKind::QuotedString,
+                b"{ k v }\n\"k\" \"v\"",
+                QEntitiesParserLocation {
+                    offset: 8,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn overlong_keys() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::KeyTooLong),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new().with_max_key_length(Some(4));
+        [
+            expected_error(
+                br#"{"12345" ""}"#,
+                QEntitiesParserLocation {
+                    offset: 1,
+                    line: 1,
+                    column: 2,
+                },
+            ),
+            expected_error(
+                br#"{"1234" "1234" "12345" "1234" }"#,
+                QEntitiesParserLocation {
+                    offset: 15,
+                    line: 1,
+                    column: 16,
+                },
+            ),
+            expected_error(
+                b"{\n12345 1234 }",
+                QEntitiesParserLocation {
+                    offset: 2,
+                    line: 2,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn overlong_values() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::ValueTooLong),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new().with_max_value_length(Some(4));
+        [
+            expected_error(
+                br#"{"" "12345"}"#,
+                QEntitiesParserLocation {
+                    offset: 4,
+                    line: 1,
+                    column: 5,
+                },
+            ),
+            expected_error(
+                br#"{"1234" "1234" "1234" "12345" }"#,
+                QEntitiesParserLocation {
+                    offset: 22,
+                    line: 1,
+                    column: 23,
+                },
+            ),
+            expected_error(
+                b"{\n1234\n12345 }",
+                QEntitiesParserLocation {
+                    offset: 7,
+                    line: 3,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn too_many_entities() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::TooManyEntities),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new().with_max_entities(Some(4));
+        [
+            expected_error(
+                b"{}{}{}{}{}",
+                QEntitiesParserLocation {
+                    offset: 8,
+                    line: 1,
+                    column: 9,
+                },
+            ),
+            expected_error(
+                b"{ k v }{ k v }{ k v }{ k v }{ k v }",
+                QEntitiesParserLocation {
+                    offset: 28,
+                    line: 1,
+                    column: 29,
+                },
+            ),
+            expected_error(
+                b"{}\n{}\n{}\n{}\n{}",
+                QEntitiesParserLocation {
+                    offset: 12,
+                    line: 5,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
+
+    #[test]
+    fn too_many_entity_kvs() {
+        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError<'_> {
+            ExpectedError {
+                src,
+                kind: ExpectedErrorVariant::SimpleKind(
+                    QEntitiesParseErrorKind::TooManyEntityKeyValues,
+                ),
+                location,
+            }
+        }
+
+        let parse_opts = QEntitiesParseOptions::new().with_max_entity_key_values(Some(4));
+        [
+            expected_error(
+                b"{ k v k v k v k v k v }",
+                QEntitiesParserLocation {
+                    offset: 18,
+                    line: 1,
+                    column: 19,
+                },
+            ),
+            expected_error(
+                br#"{ k v k v k v k v "k" "v" }"#,
+                QEntitiesParserLocation {
+                    offset: 18,
+                    line: 1,
+                    column: 19,
+                },
+            ),
+            expected_error(
+                b"{ k v k v k v k v }{ k v k v k v k v k v }",
+                QEntitiesParserLocation {
+                    offset: 37,
+                    line: 1,
+                    column: 38,
+                },
+            ),
+            expected_error(
+                b"{\nk v\nk v\nk v\nk v\nk v\n}",
+                QEntitiesParserLocation {
+                    offset: 18,
+                    line: 6,
+                    column: 1,
+                },
+            ),
+        ]
+        .iter()
+        .for_each(|ee| ee.test(&parse_opts));
+    }
 
-                // `/` may be part of a comment.
-                b'/' => match self.peek_byte()? {
-                    // `//` is a C++ style comment.
-                    Some(b'/')
-                        if self
-                            .options
-                            .flags
-                            .contains(QEntitiesParseFlags::CPP_STYLE_COMMENTS) =>
-                    {
-                        let _ = self.next_byte_fresh();
-                        self.skip_cpp_style_comment()?;
-                    }
+    #[test]
+    fn line_index() {
+        let data = b"{\n\tclassname worldspawn\n}\r{ k v }\n\n{\nk v }";
+
+        let entities = QEntitiesParseOptions::new().parse(&data[..]).unwrap();
+        assert_eq!(entities.line_index(), None);
+        assert_eq!(entities.offset_to_location(0), None);
+
+        let entities = QEntitiesParseOptions::new()
+            .with_record_line_index(true)
+            .parse(&data[..])
+            .unwrap();
+        assert_eq!(entities.line_index(), Some(&[0, 2, 24, 26, 34, 35, 37][..]));
+        assert!(entities.offset_to_location(42).is_some());
+        assert_eq!(entities.offset_to_location(43), None);
+        assert_eq!(entities.offset_to_location(u64::MAX), None);
+
+        [
+            QEntitiesParserLocation {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            QEntitiesParserLocation {
+                offset: 1,
+                line: 1,
+                column: 2,
+            },
+            QEntitiesParserLocation {
+                offset: 3,
+                line: 2,
+                column: 2,
+            },
+            QEntitiesParserLocation {
+                offset: 24,
+                line: 3,
+                column: 1,
+            },
+            QEntitiesParserLocation {
+                offset: 28,
+                line: 4,
+                column: 3,
+            },
+            QEntitiesParserLocation {
+                offset: 34,
+                line: 5,
+                column: 1,
+            },
+            QEntitiesParserLocation {
+                offset: 39,
+                line: 7,
+                column: 3,
+            },
+        ]
+        .iter()
+        .for_each(|location| {
+            assert_eq!(
+                entities.offset_to_location(location.offset).as_ref(),
+                Some(location),
+                "offset {} mapped to unexpected location",
+                location.offset,
+            )
+        });
+    }
+
+    #[test]
+    fn comments() {
+        #[rustfmt::skip]
+        let data =
+br#"// first line
+{ k0 v0 //
+}{ k1 /*c*/ v1 } /***/
+//c
+{ /* c */ k2 v2 }//c
+{ k3//c
+v3/**c**/}
+{ k4/**c**/v4//
+}//"#;
+
+        let entities = QEntitiesParseOptions::new()
+            .cpp_style_comments(true)
+            .c_style_comments(true)
+            .comments_terminate_unquoted_strings(true)
+            .parse(&data[..])
+            .unwrap();
+
+        assert_eq!(entities.len(), 5);
+        for (index, entity) in entities.iter().enumerate() {
+            assert_eq!(entity.len(), 1);
+            let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
+            assert_eq!(key, format!("k{}", index).into_bytes());
+            assert_eq!(value, format!("v{}", index).into_bytes());
+        }
+    }
+
+    #[test]
+    fn goldsrc_entities() {
+        #[rustfmt::skip]
+        let data =
+br#"{
+"wad" "\half-life\valve\halflife.wad;\half-life\valve\liquids.wad;\half-life\valve\xeno.wad"
+"chaptertitle" "C1A0TITLE"
+"message" "Black Mesa Inbound"
+"mapversion" "220"
+"skyname" "desert"
+"sounds" "1"
+"MaxRange" "4096"
+"classname" "worldspawn"
+}
+// player start
+{
+"origin" "-2016 -1904 -172"
+"angles" "0 90 0"
+"classname" "info_player_start"
+}"#;
+
+        let expected_entities: &[&[(&[u8], &[u8])]] = &[
+            &[
+                (
+                    b"wad",
+                    br"\half-life\valve\halflife.wad;\half-life\valve\liquids.wad;\half-life\valve\xeno.wad",
+                ),
+                (b"chaptertitle", b"C1A0TITLE"),
+                (b"message", b"Black Mesa Inbound"),
+                (b"mapversion", b"220"),
+                (b"skyname", b"desert"),
+                (b"sounds", b"1"),
+                (b"MaxRange", b"4096"),
+                (b"classname", b"worldspawn"),
+            ],
+            &[
+                (b"origin", b"-2016 -1904 -172"),
+                (b"angles", b"0 90 0"),
+                (b"classname", b"info_player_start"),
+            ],
+        ];
+
+        let entities = QEntitiesParseOptions::goldsrc().parse(&data[..]).unwrap();
+        assert_eq!(entities.len(), expected_entities.len());
+        entities
+            .iter()
+            .zip(expected_entities.iter())
+            .for_each(|(entity, expected_kvs)| {
+                assert!(entity.pairs().eq(expected_kvs.iter().copied()));
+            });
+    }
+
+    #[test]
+    fn vtmb_entities() {
+        let data = VTMB_DATA;
+
+        let expected_entities: &[&[(&[u8], &[u8])]] = &[
+            &[
+                (b"world_maxs", b"4096 4096 4096"),
+                (b"world_mins", b"-4096 -4096 -4096"),
+                (b"classname", b"worldspawn"),
+                (b"skyname", b"thesky"),
+                (b"sounds", b"1"),
+                (b"MaxRange", b"1337"),
+                (b"fogcolor", b"255 255 255"),
+                (b"fogcolor2", b"255 255 255"),
+                (b"fogdir", b"0 1 0"),
+                (b"fogstart", b"123.0"),
+                (b"fogend", b"456.0"),
+                (b"wetness_fadetarget", b"0.11"),
+                (b"wetness_fadein", b"2.3"),
+                (b"wetness_fadeout", b"5.4"),
+                (b"levelscript", b"thescript"),
+                (b"safearea", b"2"),
+                (b"nosferatu_tolerrant", b"1"),
+            ],
+            &[
+                (b"classname", b"logic_relay"),
+                (b"StartDisabled", b"0"),
+                (b"targetname", b"relay_a"),
+                (b"spawnflags", b"1"),
+                (b"OnTrigger", b",,,0,-1,ScriptFn(\"arg_a\", \"arg_b\"),"),
+                (b"origin", b"1 2 3"),
+            ],
+            &[
+                (b"classname", b"logic_relay"),
+                (b"StartDisabled", b"0"),
+                (b"targetname", b"relay_b"),
+                (b"spawnflags", b"1"),
+                (b"OnTrigger", b",,,0,-1,ScriptFn(\"a\", \"b\", \"c\"),"),
+                (b"origin", b"4 5 6"),
+            ],
+        ];
+
+        let entities = QEntitiesParseOptions::vtmb().parse(data).unwrap();
+        assert_eq!(
+            entities.len(),
+            expected_entities.len(),
+            "entities length mismatch",
+        );
+
+        for (entity_index, (entity, expected_entity)) in entities
+            .iter()
+            .zip(expected_entities.iter().copied())
+            .enumerate()
+        {
+            assert_eq!(
+                entity.len(),
+                expected_entity.len(),
+                "entity #{entity_index} length mismatch",
+            );
+
+            for (kv_index, ((k, v), (expected_k, expected_v))) in entity
+                .iter()
+                .map(|kv| (kv.key(), kv.value()))
+                .zip(expected_entity.iter().copied())
+                .enumerate()
+            {
+                assert_eq!(
+                    k,
+                    expected_k,
+                    "key #{kv_index} in entity #{entity_index} mismatch: {:?} != {:?}",
+                    String::from_utf8_lossy(k),
+                    String::from_utf8_lossy(expected_k),
+                );
+                assert_eq!(
+                    v,
+                    expected_v,
+                    "value #{kv_index} in entity #{entity_index} mismatch: {:?} != {:?}",
+                    String::from_utf8_lossy(v),
+                    String::from_utf8_lossy(expected_v),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn vtmb_classname_column() {
+        let entities = QEntitiesParseOptions::vtmb().parse(VTMB_DATA).unwrap();
+        let classnames: Vec<_> = entities.iter_by_key(b"classname").collect();
+        assert_eq!(
+            classnames,
+            [
+                (0, &b"worldspawn"[..]),
+                (1, &b"logic_relay"[..]),
+                (2, &b"logic_relay"[..]),
+            ],
+        );
+
+        let levelscripts: Vec<_> = entities.iter_by_key(b"levelscript").collect();
+        assert_eq!(levelscripts, [(0, &b"thescript"[..])]);
+
+        assert_eq!(entities.iter_by_key(b"missing").count(), 0);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ classname worldspawn wad mywad.wad }{}{ k \"{ v }\" \"\" \"\" }"[..])
+            .unwrap();
+
+        let displayed = entities.to_string();
+        assert_eq!(
+            displayed,
+            "{\n\"classname\" \"worldspawn\"\n\"wad\" \"mywad.wad\"\n}\n{\n}\n{\n\"k\" \"{ v }\"\n\"\" \"\"\n}\n",
+        );
+
+        let reparsed = QEntitiesParseOptions::new()
+            .parse(displayed.as_bytes())
+            .unwrap();
+        assert_eq!(reparsed.len(), entities.len());
+        for (entity, reparsed_entity) in entities.iter().zip(reparsed.iter()) {
+            assert!(entity
+                .iter()
+                .map(|kv| (kv.key(), kv.value()))
+                .eq(reparsed_entity.iter().map(|kv| (kv.key(), kv.value()))));
+        }
+
+        // The output is the same as that of the writer.
+        let mut written = Vec::new();
+        crate::write::QEntitiesWriteOptions::new()
+            .write(&entities, &mut written)
+            .unwrap();
+        assert_eq!(displayed.as_bytes(), written);
+    }
+
+    #[test]
+    fn display_lossy() {
+        // Bytes that are not valid UTF-8 are replaced, so the reparsed value differs.
+        let entities = QEntitiesParseOptions::new()
+            .parse(&b"{ message \"caf\xe9\" }"[..])
+            .unwrap();
+        let displayed = entities.to_string();
+        assert_eq!(displayed, "{\n\"message\" \"caf\u{fffd}\"\n}\n");
+        let reparsed = QEntitiesParseOptions::new()
+            .parse(displayed.as_bytes())
+            .unwrap();
+        assert_eq!(
+            reparsed.get(0).unwrap().value_of(b"message"),
+            Some("caf\u{fffd}".as_bytes())
+        );
+
+        // Double-quotes are written unescaped, so the output does not parse back.
+        let entities = QEntitiesParseOptions::new()
+            .with_single_quoted_strings(true)
+            .parse(&b"{ message 'say \"hi\"' }"[..])
+            .unwrap();
+        let displayed = entities.to_string();
+        assert_eq!(displayed, "{\n\"message\" \"say \"hi\"\"\n}\n");
+        assert!(QEntitiesParseOptions::new()
+            .parse(displayed.as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn whitespace_sets() {
+        let data = b"{\x0cclassname\x0cworldspawn\x0c}{ k\x0cv\x0c k \x0cv }";
+
+        let entities = QEntitiesParseOptions::new()
+            .with_whitespace_set(QEntitiesWhitespaceSet::Ascii)
+            .parse(&data[..])
+            .unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([(&b"classname"[..], &b"worldspawn"[..])]));
+        assert!(entities
+            .get(1)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([(&b"k"[..], &b"v"[..]), (&b"k"[..], &b"v"[..])]));
+
+        let entities = QEntitiesParseOptions::new()
+            .with_whitespace_set(QEntitiesWhitespaceSet::SpaceTabNewline)
+            .parse(&data[..])
+            .unwrap();
+        assert_eq!(entities.len(), 1);
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([
+                (
+                    &b"\x0cclassname\x0cworldspawn\x0c}{"[..],
+                    &b"k\x0cv\x0c"[..],
+                ),
+                (&b"k"[..], &b"\x0cv"[..]),
+            ]));
+    }
+
+    #[test]
+    fn single_quoted_strings() {
+        let data = br#"{ 'classname' "worldspawn" "message" 'say "hi"' }
+{ "classname" 'info_note' 'text' "it's" k'v 'v' }"#;
+
+        let entities = QEntitiesParseOptions::new()
+            .single_quoted_strings(true)
+            .parse(&data[..])
+            .unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([
+                (&b"classname"[..], &b"worldspawn"[..]),
+                (&b"message"[..], &b"say \"hi\""[..]),
+            ]));
+        assert!(entities
+            .get(1)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([
+                (&b"classname"[..], &b"info_note"[..]),
+                (&b"text"[..], &b"it's"[..]),
+                (&b"k'v"[..], &b"v"[..]),
+            ]));
 
-                    // `/*` is a C style comment.
-                    Some(b'*')
-                        if self
-                            .options
-                            .flags
-                            .contains(QEntitiesParseFlags::C_STYLE_COMMENTS) =>
-                    {
-                        let _ = self.next_byte_fresh();
-                        self.skip_c_style_comment()?;
-                    }
+        let entities = QEntitiesParseOptions::new()
+            .single_quoted_strings(true)
+            .controls_terminate_unquoted_strings(true)
+            .escape_options(Some(
+                QEntitiesParseEscapeOptions::new()
+                    .with_double_quotes(true)
+                    .with_single_quotes(true),
+            ))
+            .parse(&br#"{ k'v' 'it\'s' "\"\'" }"#[..])
+            .unwrap();
+        assert_eq!(entities.len(), 1);
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([(&b"k"[..], &b"v"[..]), (&b"it's"[..], &b"\"'"[..])]));
 
-                    // All other patterns are not comments.
-                    _ => return Ok(Some((byte, token_loc))),
-                },
+        let entities = QEntitiesParseOptions::new()
+            .parse(&br#"{ 'k' 'v' }"#[..])
+            .unwrap();
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .iter()
+            .map(|kv| (kv.key(), kv.value()))
+            .eq([(&b"'k'"[..], &b"'v'"[..])]));
 
-                // Everything else is a significant byte.
-                _ => {
-                    return Ok(Some((byte, token_loc)));
-                }
-            }
+        ExpectedError {
+            src: b"{ 'k\" }",
+            kind: ExpectedErrorVariant::SimpleKind(
+                QEntitiesParseErrorKind::UnterminatedQuotedString,
+            ),
+            location: QEntitiesParserLocation {
+                offset: 2,
+                line: 1,
+                column: 3,
+            },
         }
-        Ok(None)
+        .test(&QEntitiesParseOptions::new().with_single_quoted_strings(true));
     }
 
-    /// Gets the maximum length for a string's source kind.
-    fn string_source_max_length(&self, kind: StringSourceKind) -> usize {
-        match kind {
-            StringSourceKind::Key => self.options.max_key_length,
-            StringSourceKind::Value => self.options.max_value_length,
-        }
-    }
+    #[test]
+    fn error_categories() {
+        let parse_opts = QEntitiesParseOptions::new().with_max_key_length(Some(4));
 
-    /// Helper to attempt pushing a byte to a buffer while additionally returning an error if doing
-    /// so would cause the buffer to exceeed a length limit.
-    fn push_string_buf(
-        kind: StringSourceKind,
-        buf: &mut Vec<u8>,
-        byte: u8,
-        max_length: usize,
-        start_location: QEntitiesParserLocation,
-    ) -> Result<(), QEntitiesParseError> {
-        if buf.len() < max_length {
-            buf.push(byte);
-            Ok(())
-        } else {
-            Err(match kind {
-                StringSourceKind::Key => ParseError::KeyTooLong(start_location),
-                StringSourceKind::Value => ParseError::ValueTooLong(start_location),
+        let e = parse_opts.parse(&b"{ k v"[..]).unwrap_err();
+        assert!(!e.is_io());
+        assert!(e.is_syntax());
+        assert!(!e.is_limit());
+
+        let e = parse_opts.parse(&b"{ 12345 v }"[..]).unwrap_err();
+        assert!(!e.is_io());
+        assert!(!e.is_syntax());
+        assert!(e.is_limit());
+
+        struct FailingReader;
+        impl io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::Other.into())
             }
-            .into())
         }
+
+        let e = parse_opts.parse(FailingReader).unwrap_err();
+        assert!(e.is_io());
+        assert!(!e.is_syntax());
+        assert!(!e.is_limit());
     }
 
-    /// Reads bytes from the inner reader into given buffer until a terminating `"` byte is
-    /// encountered.
-    fn parse_quoted_string(
-        &mut self,
-        source_kind: StringSourceKind,
-        buf: &mut Vec<u8>,
-    ) -> Result<(), QEntitiesParseError> {
-        buf.clear();
+    #[test]
+    fn error_context() {
+        let data = b"{ classname worldspawn }\n{ classname light\n\"origin\" \"0 0 0\"\n}\n{ k \"v";
 
-        let max_length = self.string_source_max_length(source_kind);
+        let e = QEntitiesParseOptions::new().parse(&data[..]).unwrap_err();
+        assert_eq!(e.context(), None);
+        assert_eq!(
+            e.to_string(),
+            "unterminated quoted string opened with `\"` @66 line#5 column#5",
+        );
 
-        // Compute the start location so that it can be returned if an error is encountered.
-        let start_location = QEntitiesParserLocation {
-            offset: self.location.offset - 1,
-            line: self.location.line,
-            column: self.location.column - 1,
-        };
+        let e = QEntitiesParseOptions::new()
+            .with_capture_error_context(true)
+            .parse(&data[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedQuotedString);
+        assert_eq!(
+            e.context(),
+            Some(&b" light\n\"origin\" \"0 0 0\"\n}\n{ k \"v"[..])
+        );
+        assert_eq!(
+            e.to_string(),
+            "unterminated quoted string opened with `\"` @66 line#5 column#5 near \" light\\n\\\"origin\\\" \\\"0 0 0\\\"\\n}\\n{ k \\\"v\"",
+        );
 
-        while let Some(byte) = self.next_byte()? {
-            match byte {
-                // `"` terminates the string.
-                b'"' => {
-                    return Ok(());
-                }
+        let e = QEntitiesParseOptions::new()
+            .with_capture_error_context(true)
+            .parse(&b"}"[..])
+            .unwrap_err();
+        assert_eq!(e.context(), Some(&b"}"[..]));
+    }
 
-                // `\` can be used to escape other bytes.
-                b'\\' if self.options.flags.contains(QEntitiesParseFlags::ESCAPE) => match self
-                    .peek_byte()?
-                {
-                    Some(escape_byte @ b'\\') => {
-                        let _ = self.next_byte_fresh();
-                        Self::push_string_buf(
-                            source_kind,
-                            buf,
-                            escape_byte,
-                            max_length,
-                            start_location,
-                        )?;
-                    }
-                    Some(escape_byte @ b'"')
-                        if self
-                            .options
-                            .flags
-                            .contains(QEntitiesParseFlags::ESCAPE_DOUBLE_QUOTES) =>
-                    {
-                        let _ = self.next_byte_fresh();
-                        Self::push_string_buf(
-                            source_kind,
-                            buf,
-                            escape_byte,
-                            max_length,
-                            start_location,
-                        )?;
-                    }
-                    _ => {
-                        return Err(ParseError::InvalidEscapeSequence(QEntitiesParserLocation {
-                            offset: self.location.offset - 1,
-                            line: self.location.line,
-                            column: self.location.column - 1,
-                        })
-                        .into())
-                    }
+    #[test]
+    fn parse_path() {
+        let path =
+            std::env::temp_dir().join(format!("qentities-parse-path-{}.ent", std::process::id()));
+        fs::write(&path, b"{ classname worldspawn }\n{ classname light }\n").unwrap();
+        let res = QEntitiesParseOptions::new().parse_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        let entities = res.unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities.iter_by_key(b"classname").count(), 2);
+
+        let e = QEntitiesParseOptions::new()
+            .parse_path(path.with_extension("missing"))
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::Io);
+        assert_eq!(
+            <&io::Error>::try_from(&e).unwrap().kind(),
+            io::ErrorKind::NotFound,
+        );
+    }
+
+    #[test]
+    fn adversarial_braces() {
+        let parse_opts = [
+            QEntitiesParseOptions::new(),
+            QEntitiesParseOptions::quake3(),
+            QEntitiesParseOptions::vtmb(),
+        ];
+
+        for parse_opts in parse_opts.iter() {
+            let open_braces = vec![b'{'; 1 << 20];
+            ExpectedError {
+                src: &open_braces,
+                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::OpenBrace),
+                location: QEntitiesParserLocation {
+                    offset: 1,
+                    line: 1,
+                    column: 2,
                 },
+            }
+            .test(parse_opts);
 
-                // All other bytes are part of the string.
-                _ => {
-                    Self::push_string_buf(source_kind, buf, byte, max_length, start_location)?;
-                }
+            let close_braces = vec![b'}'; 1 << 20];
+            ExpectedError {
+                src: &close_braces,
+                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::CloseBrace),
+                location: QEntitiesParserLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
             }
+            .test(parse_opts);
+
+            let empty_entities = b"{}".repeat(1 << 16);
+            assert_eq!(
+                parse_opts.parse(&empty_entities[..]).unwrap().len(),
+                1 << 16
+            );
         }
 
-        Err(ParseError::UnterminatedQuotedString(start_location).into())
+        // Pseudo-random sequences of significant bytes must never cause a panic.
+        let significant_bytes = b"{}\"'/* \n\\k";
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next_byte = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            significant_bytes[(state % significant_bytes.len() as u64) as usize]
+        };
+        for _ in 0..1024 {
+            let src: Vec<u8> = (0..64).map(|_| next_byte()).collect();
+            for parse_opts in parse_opts.iter() {
+                let _ = parse_opts.parse(&src[..]);
+            }
+        }
     }
 
-    /// Reads bytes from the inner reader into given bufer until some terminating byte is
-    /// encountered.
-    fn parse_unquoted_string(
-        &mut self,
-        source_kind: StringSourceKind,
-        head_byte: u8,
-        buf: &mut Vec<u8>,
-    ) -> Result<(), QEntitiesParseError> {
-        buf.clear();
+    #[test]
+    fn interning() {
+        let data = b"{ classname light light 300 }{ classname light light 300 }";
 
-        let max_length = self.string_source_max_length(source_kind);
+        let interned = QEntitiesParseOptions::new().parse(&data[..]).unwrap();
+        let uninterned = QEntitiesParseOptions::new()
+            .with_intern(false)
+            .parse(&data[..])
+            .unwrap();
 
-        // Compute the start location so that it can be returned if an error is encountered.
-        let start_location = QEntitiesParserLocation {
-            offset: self.location.offset - 1,
-            line: self.location.line,
-            column: self.location.column - 1,
-        };
+        assert!(interned.iter().eq(uninterned.iter()));
+        assert_eq!(interned.byte_chunks.len(), 3);
+        assert_eq!(uninterned.byte_chunks.len(), 8);
+    }
 
-        Self::push_string_buf(source_kind, buf, head_byte, max_length, start_location)?;
+    #[test]
+    fn length_error_casts() {
+        let e = QEntitiesParseOptions::new()
+            .with_max_key_length(Some(4))
+            .parse(&b"{\n12345 1234 }"[..])
+            .unwrap_err();
+        let le = <&QEntitiesLengthError>::try_from(&e).unwrap();
+        assert_eq!(le.kind(), QEntitiesParseErrorKind::KeyTooLong);
+        assert_eq!(le.limit(), 4);
+        assert_eq!(
+            *le.location(),
+            QEntitiesParserLocation {
+                offset: 2,
+                line: 2,
+                column: 1,
+            }
+        );
+        assert!(<&QEntitiesUnexpectedTokenError>::try_from(&e).is_err());
+
+        let e = QEntitiesParseOptions::new()
+            .with_max_value_length(Some(3))
+            .parse(&br#"{"" "1234"}"#[..])
+            .unwrap_err();
+        let le = QEntitiesLengthError::try_from(e).unwrap();
+        assert_eq!(le.kind(), QEntitiesParseErrorKind::ValueTooLong);
+        assert_eq!(le.limit(), 3);
+        assert_eq!(le.location().offset, 4);
+
+        let e = QEntitiesParseOptions::new().parse(&b"}"[..]).unwrap_err();
+        assert!(<&QEntitiesLengthError>::try_from(&e).is_err());
+    }
 
-        while let Some(byte) = self.peek_byte()? {
-            match byte {
-                // Consume whitespace since it is not significant.
-                _ if byte.is_ascii_whitespace() => {
-                    let _ = self.next_byte_fresh();
-                    break;
-                }
+    #[test]
+    fn brace_escapes() {
+        fn first_kv(parse_opts: &QEntitiesParseOptions, src: &[u8]) -> (Vec<u8>, Vec<u8>) {
+            let entities = parse_opts.parse(src).unwrap();
+            let entity = entities.get(0).unwrap();
+            let kv = entity.get(0).unwrap();
+            (kv.key().to_vec(), kv.value().to_vec())
+        }
 
-                // Explicit control bytes just break so that they can be re-parsed.
-                b'{' | b'}' | b'"'
-                    if self
-                        .options
-                        .flags
-                        .contains(QEntitiesParseFlags::CONTROLS_TERMINATE_UNQUOTED_STRINGS) =>
-                {
-                    break;
-                }
+        let escape_options = QEntitiesParseEscapeOptions::new().with_braces(true);
+        let parse_opts = QEntitiesParseOptions::new().with_escape_options(Some(escape_options));
+        let controls_parse_opts = parse_opts
+            .clone()
+            .with_controls_terminate_unquoted_strings(true);
 
-                // `/` is special because it can be a comment. If it is a comment then we'll consume
-                // the comment and break, but otherwise the `/` is part of the string.
-                b'/' if self
-                    .options
-                    .flags
-                    .contains(QEntitiesParseFlags::COMMENTS_TERMINATE_UNQUOTED_STRINGS) =>
-                {
-                    let _ = self.next_byte_fresh();
-                    match self.peek_byte()? {
-                        // `//` is a C++ style comment.
-                        Some(b'/')
-                            if self
-                                .options
-                                .flags
-                                .contains(QEntitiesParseFlags::CPP_STYLE_COMMENTS) =>
-                        {
-                            let _ = self.next_byte_fresh();
-                            self.skip_cpp_style_comment()?;
-                            break;
-                        }
+        for parse_opts in [&parse_opts, &controls_parse_opts] {
+            assert_eq!(
+                first_kv(parse_opts, br#"{ \{a\} "\{b\}" }"#),
+                (b"{a}".to_vec(), b"{b}".to_vec())
+            );
+            assert_eq!(
+                first_kv(parse_opts, br#"{ a\b \\{ }"#),
+                (br"a\b".to_vec(), br"\{".to_vec())
+            );
+            assert_eq!(
+                first_kv(parse_opts, br#"{ a\ b\} }"#),
+                (br"a\".to_vec(), b"b}".to_vec())
+            );
+        }
+        assert_eq!(
+            first_kv(&controls_parse_opts, br#"{ \{"a" b\}"c" }"#),
+            (b"{".to_vec(), b"a".to_vec())
+        );
 
-                        // `/*` is a C style comment.
-                        Some(b'*')
-                            if self
-                                .options
-                                .flags
-                                .contains(QEntitiesParseFlags::C_STYLE_COMMENTS) =>
-                        {
-                            let _ = self.next_byte_fresh();
-                            self.skip_c_style_comment()?;
-                            break;
-                        }
+        // Without brace escapes the back-slash remains part of the unquoted string, and braces are
+        // parsed as controls.
+        let e = QEntitiesParseOptions::new()
+            .controls_terminate_unquoted_strings(true)
+            .escape_options(Some(QEntitiesParseEscapeOptions::new()))
+            .parse(&br"{ a\{ b }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+        assert_eq!(e.location().unwrap().offset, 4);
+
+        let e = parse_opts.parse(&br#"{ a "\b" }"#[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
+        assert_eq!(e.location().unwrap().offset, 5);
+    }
 
-                        // All other patterns are not comments. Note that the second byte is not
-                        // consumed because it may whitespace or a control byte.
-                        _ => {
-                            Self::push_string_buf(
-                                source_kind,
-                                buf,
-                                byte,
-                                max_length,
-                                start_location,
-                            )?;
-                        }
-                    }
-                }
+    #[test]
+    fn unquoted_escapes() {
+        let escape_options = QEntitiesParseEscapeOptions::new()
+            .with_double_quotes(true)
+            .with_whitespace(true);
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_escape_options(Some(escape_options.clone()))
+            .with_escape_unquoted(true);
+
+        let entities = parse_opts
+            .parse(
+                &br#"{ my\ key my\	value\"\\ \ lead\
+ trail\  }"#[..],
+            )
+            .unwrap();
+        let entity = entities.get(0).unwrap();
+        assert_eq!(entity.len(), 2);
+        let kv = entity.get(0).unwrap();
+        assert_eq!(kv.key(), b"my key");
+        assert_eq!(kv.value(), b"my\tvalue\"\\");
+        let kv = entity.get(1).unwrap();
+        assert_eq!(kv.key(), b" lead\n");
+        assert_eq!(kv.value(), b"trail ");
+
+        // Without escape sequences being parsed in unquoted strings the back-slashes remain.
+        let entities = QEntitiesParseOptions::new()
+            .with_escape_options(Some(escape_options))
+            .parse(&br"{ my\ key }"[..])
+            .unwrap();
+        let entity = entities.get(0).unwrap();
+        let kv = entity.get(0).unwrap();
+        assert_eq!(kv.key(), br"my\");
+        assert_eq!(kv.value(), b"key");
 
-                // All other bytes are part of the string.
-                _ => {
-                    let _ = self.next_byte_fresh();
-                    Self::push_string_buf(source_kind, buf, byte, max_length, start_location)?;
-                }
+        // Escape sequences must be valid.
+        [
+            (&br"{ a\b c }"[..], 3),
+            (&br"{ a \"[..], 4),
+            (&br"{ a b\"[..], 5),
+            (&b"{ a b\\\n}"[..], 5),
+        ]
+        .into_iter()
+        .for_each(|(src, offset)| {
+            let e = QEntitiesParseOptions::new()
+                .escape_options(Some(QEntitiesParseEscapeOptions::new()))
+                .escape_unquoted(true)
+                .parse(src)
+                .unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
+            assert_eq!(e.location().unwrap().offset, offset);
+        });
+    }
+
+    #[test]
+    fn token_kind_bytes() {
+        for byte in 0..=u8::MAX {
+            let kind = QEntitiesTokenKind::from_head_byte(byte);
+            match kind.as_opening_byte() {
+                Some(opening_byte) => assert_eq!(opening_byte, byte),
+                None => assert_eq!(kind, QEntitiesTokenKind::UnquotedString),
             }
         }
 
-        Ok(())
+        [
+            QEntitiesTokenKind::OpenBrace,
+            QEntitiesTokenKind::CloseBrace,
+            QEntitiesTokenKind::QuotedString,
+        ]
+        .into_iter()
+        .for_each(|kind| {
+            let opening_byte = kind.as_opening_byte().unwrap();
+            assert_eq!(QEntitiesTokenKind::from_head_byte(opening_byte), kind);
+        });
+        assert_eq!(
+            QEntitiesTokenKind::from_head_byte(b'\''),
+            QEntitiesTokenKind::UnquotedString
+        );
     }
 
-    fn parse<S: BuildHasher>(&mut self, hash_builder: S) -> Result<QEntities, QEntitiesParseError> {
-        /// State the parser can be in.
-        #[derive(Debug, Clone, Copy)]
-        enum ParseState {
-            /// The parser is searching for the next entity.
-            NextEntity,
-            /// The parser is searching for a key.
-            NextKey,
-            /// The parser is searching for a value.
-            NextValue,
-        }
-
-        // Location at which the last entity began. This is used to return an error if the EOF is
-        // reached while still parsing an entity.
-        let mut entity_start_loc = QEntitiesParserLocation {
-            offset: 0,
-            line: 0,
-            column: 0,
+    #[test]
+    fn start_location() {
+        let start_location = QEntitiesParserLocation {
+            offset: 1000,
+            line: 50,
+            column: 7,
         };
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_start_location(start_location)
+            .with_record_line_index(true);
 
-        // Intermediates for constructing the `QEntities` instance.
-        let mut entities = Vec::new();
-        let mut key_values = Vec::new();
-        let mut byte_chunks = ByteChunksBuilder::with_hasher(hash_builder);
-        let mut key_chunk = 0;
+        let e = parse_opts.parse(&b"{ a b }{ c"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(
+            *e.location().unwrap(),
+            QEntitiesParserLocation {
+                offset: 1007,
+                line: 50,
+                column: 14,
+            }
+        );
 
-        // Scratch buffer which is used to store keys and values.
-        let mut scratch = Vec::new();
+        let e = parse_opts.parse(&b"{ a b\n\n  \"c }"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedQuotedString);
+        assert_eq!(
+            *e.location().unwrap(),
+            QEntitiesParserLocation {
+                offset: 1009,
+                line: 52,
+                column: 3,
+            }
+        );
 
-        let mut state = ParseState::NextEntity;
-        while let Some((token_head_byte, token_location)) = self.next_significant_byte()? {
-            let token_kind = match token_head_byte {
-                b'{' => QEntitiesTokenKind::OpenBrace,
-                b'}' => QEntitiesTokenKind::CloseBrace,
-                b'"' => QEntitiesTokenKind::QuotedString,
-                _ => QEntitiesTokenKind::UnquotedString,
-            };
+        let entities = parse_opts.parse(&b"{ a b\n}\n{ c d }"[..]).unwrap();
+        assert_eq!(entities.line_index(), Some(&[0, 6, 8][..]));
+    }
 
-            state = match state {
-                ParseState::NextEntity => match token_kind {
-                    QEntitiesTokenKind::OpenBrace => {
-                        entity_start_loc = token_location;
+    #[test]
+    fn clone_non_io() {
+        let e = QEntitiesParseOptions::new()
+            .with_max_value_length(Some(2))
+            .with_capture_error_context(true)
+            .parse(&b"{ a bcd }"[..])
+            .unwrap_err();
+        let clone = e.clone_non_io().unwrap();
+        assert_eq!(clone.kind(), QEntitiesParseErrorKind::ValueTooLong);
+        assert_eq!(clone.location(), e.location());
+        assert_eq!(clone.context(), e.context());
+        assert_eq!(clone.to_string(), e.to_string());
+        assert_eq!(
+            <&QEntitiesLengthError>::try_from(&clone).unwrap().limit(),
+            2
+        );
 
-                        if entities.len() < self.options.max_entities {
-                            entities.push(QEntityInfo {
-                                first_kv: key_values.len(),
-                                kvs_length: 0,
-                            });
-                        } else {
-                            return Err(ParseError::TooManyEntities(entity_start_loc).into());
-                        }
+        let e = QEntitiesParseError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert!(e.clone_non_io().is_none());
+    }
 
-                        ParseState::NextKey
-                    }
+    #[test]
+    fn control_bytes() {
+        let parse_opts = QEntitiesParseOptions::new().with_reject_control_bytes(true);
+        [
+            (&b"{ \"a\0\" b }"[..], 4),
+            (&b"{ a \"\x0bb\" }"[..], 5),
+            (&b"{ a\0 b }"[..], 3),
+            (&b"{ a \x0bb }"[..], 4),
+            (&b"{ a b\x0b }"[..], 5),
+        ]
+        .into_iter()
+        .for_each(|(src, offset)| {
+            let e = parse_opts.parse(src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::IllegalControlByte);
+            assert_eq!(e.location().unwrap().offset, offset);
+
+            // Control bytes are stored as-is by default.
+            let entities = QEntitiesParseOptions::new().parse(src).unwrap();
+            assert_eq!(entities.get(0).unwrap().len(), 1);
+        });
+
+        // Whitespace is not rejected.
+        let entities = parse_opts
+            .parse(&b"{\t\"a\tb\"\r\n\"c\nd\"\x0c}"[..])
+            .unwrap();
+        let entity = entities.get(0).unwrap();
+        let kv = entity.get(0).unwrap();
+        assert_eq!(kv.key(), b"a\tb");
+        assert_eq!(kv.value(), b"c\nd");
+
+        // Form feeds are only whitespace within the ASCII whitespace set.
+        let e = parse_opts
+            .clone()
+            .with_whitespace_set(QEntitiesWhitespaceSet::SpaceTabNewline)
+            .parse(&b"{ a \x0cb }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::IllegalControlByte);
+        assert_eq!(e.location().unwrap().offset, 4);
+    }
 
-                    _ => {
-                        return Err(
-                            QEntitiesUnexpectedTokenError::new(token_kind, token_location).into(),
-                        )
-                    }
-                },
+    #[test]
+    fn unsupported_formats() {
+        let e = QEntitiesParseOptions::source2()
+            .parse(&b"// comment\n<!-- kv3 encoding:text:version{e21c7f3c-8a33-41c5-9977-a76d3a32aa0d} format:generic:version{7412167c-06e9-4698-aff2-e63eb59037e7} -->\n{\n\tkey = \"value\"\n}"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnsupportedFormat);
+        assert!(e.is_syntax());
+        assert_eq!(
+            *e.location().unwrap(),
+            QEntitiesParserLocation {
+                offset: 11,
+                line: 2,
+                column: 1,
+            }
+        );
 
-                ParseState::NextKey => match token_kind {
-                    QEntitiesTokenKind::CloseBrace => ParseState::NextEntity,
+        // The header is detected under any options, but a header-less file is not.
+        let e = QEntitiesParseOptions::new()
+            .parse(&b"<!-- kv3 -->\n{}"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnsupportedFormat);
+        let e = QEntitiesParseOptions::source2()
+            .parse(&b"{\n\tkey = \"value\"\n}"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+
+        // Other bytes remain unexpected tokens.
+        ["<!-", "<x", "<"].into_iter().for_each(|src| {
+            let e = QEntitiesParseOptions::source2()
+                .parse(src.as_bytes())
+                .unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+            assert_eq!(e.location().unwrap().offset, 0);
+        });
+
+        let entities = QEntitiesParseOptions::source2()
+            .parse(&b"{ \"classname\" \"worldspawn\" }"[..])
+            .unwrap();
+        assert_eq!(entities.worldspawn(), entities.get(0));
+
+        // Only a header at the start of the file is detected.
+        let e = QEntitiesParseOptions::source2()
+            .with_strict_eof(true)
+            .parse(&b"{ a b } <!-- x"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::TrailingContent);
+        assert_eq!(e.location().unwrap().offset, 8);
+
+        let e = QEntitiesParseOptions::source2()
+            .parse(&b"{ a b } <!-- x"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+        assert_eq!(e.location().unwrap().offset, 8);
+
+        let entities = QEntitiesParseOptions::source2()
+            .parse(&b"{ a b }{ <!-- x }"[..])
+            .unwrap();
+        assert_eq!(entities.get(1).unwrap().value_of(b"<!--"), Some(&b"x"[..]));
+    }
 
-                    QEntitiesTokenKind::QuotedString => {
-                        if entities.last().unwrap().kvs_length < self.options.max_entity_kvs {
-                            self.parse_quoted_string(StringSourceKind::Key, &mut scratch)?;
-                            key_chunk = byte_chunks.chunk(&scratch);
-                            ParseState::NextValue
-                        } else {
-                            return Err(ParseError::TooManyEntityKeyValues(token_location).into());
-                        }
-                    }
+    #[test]
+    fn valueless_keys() {
+        let e = QEntitiesParseOptions::new()
+            .parse(&b"{ key }"[..])
+            .unwrap_err();
+        let tke = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+        assert_eq!(tke.kind(), QEntitiesTokenKind::CloseBrace);
+        assert_eq!(tke.location().offset, 6);
+        assert_eq!(
+            tke.expected(),
+            [
+                QEntitiesTokenKind::QuotedString,
+                QEntitiesTokenKind::UnquotedString
+            ]
+        );
 
-                    QEntitiesTokenKind::UnquotedString => {
-                        if entities.last().unwrap().kvs_length < self.options.max_entity_kvs {
-                            self.parse_unquoted_string(
-                                StringSourceKind::Key,
-                                token_head_byte,
-                                &mut scratch,
-                            )?;
-                            key_chunk = byte_chunks.chunk(&scratch);
-                            ParseState::NextValue
-                        } else {
-                            return Err(ParseError::TooManyEntityKeyValues(token_location).into());
-                        }
-                    }
+        let parse_opts = QEntitiesParseOptions::new().with_allow_valueless_keys(true);
+        let entities = parse_opts
+            .parse(&b"{ key }{ \"a\" \"b\" \"c\"}{}"[..])
+            .unwrap();
+        assert_eq!(entities.len(), 3);
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .pairs()
+            .eq([(&b"key"[..], &b""[..])]));
+        assert!(entities
+            .get(1)
+            .unwrap()
+            .pairs()
+            .eq([(&b"a"[..], &b"b"[..]), (b"c", b"")]));
+        assert!(entities.get(2).unwrap().is_empty());
+
+        // An entity still cannot be left unterminated.
+        let e = parse_opts.parse(&b"{ key"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+    }
 
-                    _ => {
-                        return Err(
-                            QEntitiesUnexpectedTokenError::new(token_kind, token_location).into(),
-                        )
-                    }
-                },
+    #[test]
+    fn duplicate_keys() {
+        let src = b"{ a 1 b 2 \"a\" 3 c 4 a 5 }\n{ a 1 }\n{ d 1 d }";
+        let parse = |policy| {
+            QEntitiesParseOptions::new()
+                .allow_valueless_keys(true)
+                .duplicate_key_policy(policy)
+                .parse(&src[..])
+        };
+        fn pairs(entities: &QEntities, index: usize) -> Vec<(&[u8], &[u8])> {
+            entities.get(index).unwrap().pairs().collect()
+        }
 
-                ParseState::NextValue => {
-                    let value_chunk = match token_kind {
-                        QEntitiesTokenKind::QuotedString => {
-                            self.parse_quoted_string(StringSourceKind::Value, &mut scratch)?;
-                            byte_chunks.chunk(&scratch)
-                        }
+        let kept = parse(QEntitiesDuplicateKeyPolicy::Keep).unwrap();
+        assert_eq!(kept.key_values.len(), 8);
+        assert_eq!(
+            pairs(&kept, 0),
+            [
+                (&b"a"[..], &b"1"[..]),
+                (b"b", b"2"),
+                (b"a", b"3"),
+                (b"c", b"4"),
+                (b"a", b"5"),
+            ]
+        );
 
-                        QEntitiesTokenKind::UnquotedString => {
-                            self.parse_unquoted_string(
-                                StringSourceKind::Value,
-                                token_head_byte,
-                                &mut scratch,
-                            )?;
-                            byte_chunks.chunk(&scratch)
-                        }
+        let replaced = parse(QEntitiesDuplicateKeyPolicy::Replace).unwrap();
+        assert_eq!(replaced.len(), 3);
+        assert_eq!(
+            pairs(&replaced, 0),
+            [(&b"a"[..], &b"5"[..]), (b"b", b"2"), (b"c", b"4")]
+        );
+        assert_eq!(pairs(&replaced, 1), [(&b"a"[..], &b"1"[..])]);
+        assert_eq!(pairs(&replaced, 2), [(&b"d"[..], &b""[..])]);
 
-                        _ => {
-                            return Err(QEntitiesUnexpectedTokenError::new(
-                                token_kind,
-                                token_location,
-                            )
-                            .into())
-                        }
-                    };
+        let e = parse(QEntitiesDuplicateKeyPolicy::Error).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::DuplicateKey);
+        assert!(!e.is_syntax() && !e.is_limit());
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(10, 1, 11)));
+
+        // Keys are only compared within an entity.
+        let src = b"{ a 1 b 2 }{ a 1 b 2 }";
+        let entities = QEntitiesParseOptions::new()
+            .duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error)
+            .parse(&src[..])
+            .unwrap();
+        assert_eq!(entities.key_values.len(), 4);
+
+        // A valueless duplicate key is reported at the key.
+        let e = QEntitiesParseOptions::new()
+            .allow_valueless_keys(true)
+            .duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error)
+            .parse(&b"{ d 1\nd }"[..])
+            .unwrap_err();
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(6, 2, 1)));
+    }
 
-                    key_values.push(QEntityKeyValueInfo {
-                        key_chunk,
-                        value_chunk,
-                    });
-                    entities.last_mut().unwrap().kvs_length += 1;
+    #[test]
+    fn key_value_separators() {
+        let parse = |separator, src: &[u8]| {
+            QEntitiesParseOptions::quake()
+                .c_style_comments(true)
+                .allow_valueless_keys(true)
+                .key_value_separator(separator)
+                .parse(src)
+        };
 
-                    ParseState::NextKey
-                }
-            };
+        let src = b"{ a = 1 b=2 \"c\"= \"3\" d // comment\n = /* comment */ 4 e = = }\n{ f = }";
+        let entities = parse(Some(b'='), &src[..]).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(entities.get(0).unwrap().pairs().eq([
+            (&b"a"[..], &b"1"[..]),
+            (b"b", b"2"),
+            (b"c", b"3"),
+            (b"d", b"4"),
+            (b"e", b"="),
+        ]));
+        assert!(entities.get(1).unwrap().pairs().eq([(&b"f"[..], &b""[..])]));
+
+        // Without a separator the same bytes are parsed differently.
+        let entities = parse(None, &b"{ a = 1 = }"[..]).unwrap();
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .pairs()
+            .eq([(&b"a"[..], &b"="[..]), (b"1", b"="),]));
+
+        // Missing separators.
+        for (src, location) in [
+            (&b"{ a 1 }"[..], QEntitiesParserLocation::new(4, 1, 5)),
+            (b"{ a : 1 }", QEntitiesParserLocation::new(4, 1, 5)),
+            (b"{ \"a\"\"1\" }", QEntitiesParserLocation::new(5, 1, 6)),
+            (b"{ a\n{", QEntitiesParserLocation::new(4, 2, 1)),
+        ] {
+            let e = parse(Some(b'='), src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::MissingSeparator);
+            assert!(e.is_syntax());
+            assert_eq!(e.location(), Some(&location));
         }
 
-        match state {
-            ParseState::NextEntity => Ok(QEntities {
-                entities: entities.into(),
-                key_values: key_values.into(),
-                byte_chunks: byte_chunks.into(),
-            }),
-            _ => Err(ParseError::UnterminatedEntity(entity_start_loc).into()),
-        }
-    }
-}
+        // A close brace in place of the separator is only allowed for valueless keys.
+        let e = QEntitiesParseOptions::new()
+            .key_value_separator(Some(b'='))
+            .parse(&b"{ a }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::MissingSeparator);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use bstr::BStr;
+        let e = parse(Some(b'='), &b"{ a ="[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
 
-    /// Variant for an expected error in [`ExpectedError`].
-    #[derive(Clone, Copy)]
-    enum ExpectedErrorVariant {
-        SimpleKind(QEntitiesParseErrorKind),
-        UnexpectedToken(QEntitiesTokenKind),
+        // A byte that is only an entity delimiter by default can be a separator.
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_entity_delimiters(b'[', b']')
+            .with_key_value_separator(Some(b'{'));
+        assert_eq!(parse_opts.get_key_value_separator(), Some(b'{'));
     }
 
-    /// Helper for asserting that an error occured as expected.
-    struct ExpectedError<'a> {
-        src: &'a [u8],
-        kind: ExpectedErrorVariant,
-        location: QEntitiesParserLocation,
+    #[test]
+    #[should_panic]
+    fn key_value_separator_double_quote() {
+        let _ = QEntitiesParseOptions::new().with_key_value_separator(Some(b'"'));
     }
 
-    impl ExpectedError<'_> {
-        /// Asserts that the expected error occured while parsing with the given parse options.
-        fn test(&self, parse_opts: &QEntitiesParseOptions) {
-            match parse_opts.parse(&self.src[..]) {
-                Ok(_) => panic!(
-                    "parsing of {:?} unexpectedly succeeded",
-                    BStr::new(self.src),
-                ),
-                Err(e) => {
-                    match self.kind {
-                        ExpectedErrorVariant::SimpleKind(k) => assert_eq!(
-                            e.kind(),
-                            k,
-                            "parsing of {:?} returned unexpected error kind",
-                            BStr::new(self.src),
-                        ),
-                        ExpectedErrorVariant::UnexpectedToken(tk) => {
-                            assert_eq!(
-                                e.kind(),
-                                QEntitiesParseErrorKind::UnexpectedToken,
-                                "parsing of {:?} returned unexpected error kind",
-                                BStr::new(self.src),
-                            );
-                            let tke = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
-                            assert_eq!(
-                                tke.kind(),
-                                tk,
-                                "parsing of {:?} returned unexpected token kind",
-                                BStr::new(self.src),
-                            );
-                        }
-                    };
-                    assert_eq!(
-                        e.location().expect("error kind must carry a location"),
-                        &self.location,
-                        "parsing of {:?} returned unexpected error location",
-                        BStr::new(self.src),
-                    );
-                }
-            }
-        }
+    #[test]
+    #[should_panic]
+    fn key_value_separator_whitespace() {
+        let _ = QEntitiesParseOptions::new().with_key_value_separator(Some(b'\x0c'));
     }
 
     #[test]
-    fn unterminated_c_style_comments() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(
-                    QEntitiesParseErrorKind::UnterminatedCStyleComment,
-                ),
-                location,
-            }
-        }
+    #[should_panic]
+    fn key_value_separator_entity_delimiter() {
+        let _ = QEntitiesParseOptions::new()
+            .with_entity_delimiters(b'[', b']')
+            .with_key_value_separator(Some(b']'));
+    }
 
-        let parse_opts = QEntitiesParseOptions::new().with_c_style_comments(true);
-        [
-            expected_error(
-                br#"/*"#,
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"\n/*",
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                br#"/**"#,
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                br#"/***"#,
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                br#"{"k"/*"v"}"#,
-                QEntitiesParserLocation {
-                    offset: 4,
-                    line: 1,
-                    column: 5,
-                },
-            ),
-            expected_error(
-                br#"{"k" "v"}/*"#,
-                QEntitiesParserLocation {
-                    offset: 9,
-                    line: 1,
-                    column: 10,
-                },
-            ),
-            expected_error(
-                br#"{ k /* v }"#,
-                QEntitiesParserLocation {
-                    offset: 4,
-                    line: 1,
-                    column: 5,
-                },
-            ),
-            expected_error(
-                br#"{ k v }/*"#,
-                QEntitiesParserLocation {
-                    offset: 7,
-                    line: 1,
-                    column: 8,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+    #[test]
+    #[should_panic]
+    fn entity_delimiter_key_value_separator() {
+        let _ = QEntitiesParseOptions::new()
+            .with_key_value_separator(Some(b'='))
+            .with_entity_delimiters(b'=', b']');
     }
 
     #[test]
-    fn unterminated_quoted_strings() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(
-                    QEntitiesParseErrorKind::UnterminatedQuotedString,
-                ),
-                location,
-            }
+    fn empty_keys() {
+        let src = b"{ \"\" value }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .pairs()
+            .eq([(&b""[..], &b"value"[..])]));
+
+        let e = QEntitiesParseOptions::new()
+            .reject_empty_keys(true)
+            .parse(&src[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::EmptyKey);
+        assert!(!e.is_syntax() && !e.is_limit());
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(2, 1, 3)));
+
+        // Empty values, including those of valueless keys, are still allowed.
+        let entities = QEntitiesParseOptions::new()
+            .single_quoted_strings(true)
+            .allow_valueless_keys(true)
+            .reject_empty_keys(true)
+            .parse(&b"{ a \"\" b '' c }"[..])
+            .unwrap();
+        assert!(entities.get(0).unwrap().pairs().eq([
+            (&b"a"[..], &b""[..]),
+            (b"b", b""),
+            (b"c", b""),
+        ]));
+
+        let e = QEntitiesParseOptions::new()
+            .single_quoted_strings(true)
+            .reject_empty_keys(true)
+            .parse(&b"{ a b\n'' c }"[..])
+            .unwrap_err();
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(6, 2, 1)));
+    }
+
+    #[test]
+    fn parse_ref_inputs() {
+        let src = "{ classname worldspawn }\n{ classname light origin \"0 0 0\" }";
+        let options = QEntitiesParseOptions::new();
+        let expected = options.parse(src.as_bytes()).unwrap();
+
+        let owned_string = String::from(src);
+        let owned_bytes = Vec::from(src);
+        let parsed = [
+            options.parse_ref(src).unwrap(),
+            options.parse_ref(owned_string).unwrap(),
+            options.parse_ref(owned_bytes).unwrap(),
+            options
+                .parse_ref(Box::<[u8]>::from(src.as_bytes()))
+                .unwrap(),
+        ];
+        for entities in &parsed {
+            assert!(entities.iter().eq(expected.iter()));
         }
 
-        let parse_opts = QEntitiesParseOptions::new();
-        [
-            expected_error(
-                br#"{""#,
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 1,
-                    column: 2,
-                },
+        let e = options.parse_ref("{ classname").unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+    }
+
+    #[test]
+    fn expected_tokens() {
+        use QEntitiesTokenKind::*;
+
+        for (parse_opts, src, expected, message) in [
+            (
+                QEntitiesParseOptions::new(),
+                &b"}"[..],
+                &[OpenBrace][..],
+                r#"expected "{", found "}" token @0 line#1 column#1"#,
             ),
-            expected_error(
-                br#"{"key" ""#,
-                QEntitiesParserLocation {
-                    offset: 7,
-                    line: 1,
-                    column: 8,
-                },
+            (
+                QEntitiesParseOptions::new(),
+                b"{ a b {",
+                &[CloseBrace, QuotedString, UnquotedString],
+                r#"expected "}", "quoted string" or "unquoted string", found "{" token @6 line#1 column#7"#,
             ),
-            expected_error(
-                b"{k\n\"",
-                QEntitiesParserLocation {
-                    offset: 3,
-                    line: 2,
-                    column: 1,
-                },
+            (
+                QEntitiesParseOptions::new(),
+                b"{ a {",
+                &[QuotedString, UnquotedString],
+                r#"expected "quoted string" or "unquoted string", found "{" token @4 line#1 column#5"#,
             ),
-            expected_error(
-                b"{k \"v}",
-                QEntitiesParserLocation {
-                    offset: 3,
-                    line: 1,
-                    column: 4,
-                },
+            (
+                QEntitiesParseOptions::new().with_allow_valueless_keys(true),
+                b"{ a {",
+                &[CloseBrace, QuotedString, UnquotedString],
+                r#"expected "}", "quoted string" or "unquoted string", found "{" token @4 line#1 column#5"#,
             ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        ] {
+            let e = parse_opts.parse(src).unwrap_err();
+            let tke = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+            assert_eq!(tke.expected(), expected);
+            assert!(!tke.expected().contains(&tke.kind()));
+            assert_eq!(e.to_string(), message);
+        }
     }
 
     #[test]
-    fn unterminated_entities() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::UnterminatedEntity),
-                location,
-            }
+    fn line_endings() {
+        fn error_location(src: &[u8]) -> (u64, u64, u64) {
+            let e = QEntitiesParseOptions::new().parse(src).unwrap_err();
+            let location = e.location().unwrap();
+            (location.offset(), location.line(), location.column())
+        }
+        fn line_index(src: &[u8]) -> Vec<u64> {
+            let entities = QEntitiesParseOptions::new()
+                .record_line_index(true)
+                .parse(src)
+                .unwrap();
+            entities.line_index().unwrap().to_vec()
         }
 
-        let parse_opts = QEntitiesParseOptions::new();
-        [
-            expected_error(
-                b"{",
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"\n{",
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"{ k v }{",
-                QEntitiesParserLocation {
-                    offset: 7,
-                    line: 1,
-                    column: 8,
-                },
-            ),
-            expected_error(
-                b"{ k v }\n{",
-                QEntitiesParserLocation {
-                    offset: 8,
-                    line: 2,
-                    column: 1,
-                },
+        // Classic Mac OS line endings number lines sequentially and reset the column.
+        assert_eq!(error_location(b"{\ra b\r}\r  }"), (10, 4, 3));
+        assert_eq!(error_location(b"\r\r\r}"), (3, 4, 1));
+        assert_eq!(line_index(b"{\ra b\r}\r"), [0, 2, 6, 8]);
+
+        // Windows line endings are a single line ending.
+        assert_eq!(error_location(b"{\r\na b\r\n}\r\n  }"), (13, 4, 3));
+        assert_eq!(error_location(b"\r\n\r\n\r\n}"), (6, 4, 1));
+        assert_eq!(line_index(b"{\r\na b\r\n}\r\n"), [0, 3, 8, 11]);
+
+        // Mixed line endings, where `\n\r` is two line endings.
+        assert_eq!(error_location(b"\n\r\r\n\r}"), (5, 5, 1));
+        assert_eq!(line_index(b"\n\r\r\n\r{}"), [0, 1, 2, 4, 5]);
+
+        // The recorded line index agrees with the locations reported by the parser.
+        let src = b"{\r\na \"b\r\nc\"\r\r}\n\r\n{ d e }";
+        let entities = QEntitiesParseOptions::new()
+            .record_line_index(true)
+            .parse(&src[..])
+            .unwrap();
+        for (src_end, expected) in [(15, (15, 6, 1)), (17, (17, 7, 1)), (21, (21, 7, 5))] {
+            let location = entities.offset_to_location(expected.0).unwrap();
+            assert_eq!(
+                (location.offset(), location.line(), location.column()),
+                expected
+            );
+            let mut truncated = src[..src_end].to_vec();
+            truncated.push(b'}');
+            assert_eq!(error_location(&truncated), expected);
+        }
+    }
+
+    #[test]
+    fn implicit_top_level_entities() {
+        use QEntitiesTokenKind::*;
+
+        let parse_opts = QEntitiesParseOptions::quake().with_implicit_top_level_entity(true);
+
+        let src = b"// settings\n\"classname\" worldspawn\nmessage \"a b\" // trailing\n";
+        let entities = parse_opts.parse(&src[..]).unwrap();
+        assert_eq!(entities.len(), 1);
+        assert!(entities.get(0).unwrap().pairs().eq([
+            (&b"classname"[..], &b"worldspawn"[..]),
+            (b"message", b"a b"),
+        ]));
+
+        // Files beginning with braces, and empty files, are parsed as usual.
+        let entities = parse_opts.parse(&b"{ a b }{ c d }"[..]).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(parse_opts.parse(&b"// empty\n"[..]).unwrap().is_empty());
+
+        // Braces cannot appear within an implicit entity.
+        for (src, kind, offset, expected) in [
+            (
+                &b"a b {"[..],
+                OpenBrace,
+                4,
+                &[QuotedString, UnquotedString][..],
             ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+            (b"a b }", CloseBrace, 4, &[QuotedString, UnquotedString]),
+            (b"a }", CloseBrace, 2, &[QuotedString, UnquotedString]),
+        ] {
+            for parse_opts in [
+                parse_opts.clone(),
+                parse_opts.clone().with_allow_valueless_keys(true),
+            ] {
+                let e = parse_opts.parse(src).unwrap_err();
+                let tke = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+                assert_eq!(tke.kind(), kind);
+                assert_eq!(tke.location().offset(), offset);
+                assert_eq!(tke.expected(), expected);
+            }
+        }
+
+        // A key without a value is unterminated.
+        let e = parse_opts.parse(&b"a b c"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(0, 1, 1)));
+
+        // Without the option the first string is unexpected.
+        let e = QEntitiesParseOptions::quake().parse(&src[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+
+        // Limits still apply.
+        let e = parse_opts
+            .clone()
+            .with_max_entities(Some(0))
+            .parse(&src[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::TooManyEntities);
+        let e = parse_opts
+            .clone()
+            .with_max_entity_key_values(Some(1))
+            .parse(&src[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::TooManyEntityKeyValues);
     }
 
     #[test]
-    fn invalid_escape_sequences() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(
-                    QEntitiesParseErrorKind::InvalidEscapeSequence,
-                ),
-                location,
+    fn expected_chunk_counts() {
+        let src = b"{ classname light origin \"0 0 0\" }{ classname light origin \"0 0 8\" }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        assert_eq!(entities.chunk_count(), 5);
+
+        for expected_chunk_count in [0, 1, entities.chunk_count(), 1000] {
+            let hinted = QEntitiesParseOptions::new()
+                .with_expected_chunk_count(expected_chunk_count)
+                .parse(&src[..])
+                .unwrap();
+            assert!(hinted.iter().eq(entities.iter()));
+            assert_eq!(hinted.chunk_count(), entities.chunk_count());
+        }
+    }
+
+    #[test]
+    fn comments_in_quoted_strings() {
+        let presets = [
+            QEntitiesParseOptions::quake(),
+            QEntitiesParseOptions::quake2(),
+            QEntitiesParseOptions::goldsrc(),
+            QEntitiesParseOptions::quake3(),
+            QEntitiesParseOptions::source_engine(),
+            QEntitiesParseOptions::source2(),
+            QEntitiesParseOptions::vtmb(),
+        ];
+        let mut all_opts = presets.to_vec();
+        for cpp_style_comments in [false, true] {
+            for c_style_comments in [false, true] {
+                for comments_terminate_unquoted_strings in [false, true] {
+                    all_opts.push(
+                        QEntitiesParseOptions::new()
+                            .with_cpp_style_comments(cpp_style_comments)
+                            .with_c_style_comments(c_style_comments)
+                            .with_comments_terminate_unquoted_strings(
+                                comments_terminate_unquoted_strings,
+                            )
+                            .with_single_quoted_strings(true),
+                    );
+                }
             }
         }
 
-        let parse_opts = QEntitiesParseOptions::new()
-            .with_escape_options(Some(QEntitiesParseEscapeOptions::new()));
-        [
-            expected_error(
-                br#"{"\x" "value"}"#,
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 1,
-                    column: 3,
-                },
-            ),
-            expected_error(
-                b"{k\n\"\\x\"}",
-                QEntitiesParserLocation {
-                    offset: 4,
-                    line: 2,
-                    column: 2,
-                },
-            ),
-            expected_error(
-                b"{\"\\\x00\" \"\"}",
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 1,
-                    column: 3,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        for parse_opts in all_opts {
+            for (src, value) in [
+                (&b"{ k \"a // b /* c */ d\" }"[..], &b"a // b /* c */ d"[..]),
+                (b"{ k \"a /* b\" }", b"a /* b"),
+                (b"{ k \"*/\" }", b"*/"),
+                (b"{ k \"//\" }", b"//"),
+                (b"{ \"// k\" \"/* v */\" }", b"/* v */"),
+            ] {
+                let entities = parse_opts.parse(src).unwrap();
+                let entity = entities.get(0).unwrap();
+                assert_eq!(entity.len(), 1);
+                let kv = entity.get(0).unwrap();
+                assert_eq!(kv.value(), value);
+            }
+
+            if parse_opts
+                .flags
+                .contains(QEntitiesParseFlags::SINGLE_QUOTED_STRINGS)
+            {
+                let entities = parse_opts.parse(&b"{ k 'a // b /* c */ d' }"[..]).unwrap();
+                assert_eq!(
+                    entities.get(0).unwrap().value_of(b"k"),
+                    Some(&b"a // b /* c */ d"[..])
+                );
+            }
+        }
     }
 
     #[test]
-    fn nested_entities() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::OpenBrace),
-                location,
+    fn multiple_documents() {
+        let parse_opts = QEntitiesParseOptions::quake();
+
+        let src = b"{ classname worldspawn }\n\0// second\n{ classname light }{ a b }\0";
+        let documents = parse_opts
+            .parse_multi(&src[..], b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        assert!(documents[0].iter().eq(parse_opts
+            .parse(&b"{ classname worldspawn }"[..])
+            .unwrap()
+            .iter()));
+        assert!(documents[1].iter().eq(parse_opts
+            .parse(&b"{ classname light }{ a b }"[..])
+            .unwrap()
+            .iter()));
+
+        // Empty documents are yielded between consecutive separators, and the separator
+        // terminates unquoted strings but not quoted strings.
+        let src = b"\0\0{ a \"b\0c\" }\0a b\0c d";
+        let documents = parse_opts
+            .clone()
+            .with_implicit_top_level_entity(true)
+            .parse_multi(&src[..], b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let documents = documents
+            .iter()
+            .map(|entities| entities.iter().flat_map(|e| e.pairs()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            documents,
+            [
+                vec![],
+                vec![],
+                vec![(&b"a"[..], &b"b\0c"[..])],
+                vec![(b"a", b"b")],
+                vec![(b"c", b"d")],
+            ]
+        );
+
+        let documents = parse_opts
+            .parse_multi(&b"{ a b }\0{ c d"[..], b'\0')
+            .collect::<Vec<_>>();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].as_ref().unwrap().len(), 1);
+        let e = documents[1].as_ref().unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(8, 1, 9)));
+
+        // A separator within an entity terminates it early.
+        let documents = parse_opts
+            .parse_multi(&b"{ a b \0 c d }"[..], b'\0')
+            .collect::<Vec<_>>();
+        assert_eq!(documents.len(), 1);
+        let e = documents[0].as_ref().unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+
+        // An empty reader is a single empty document.
+        let documents = parse_opts.parse_multi(&b""[..], b'\0').collect::<Vec<_>>();
+        assert_eq!(documents.len(), 1);
+        assert!(documents[0].as_ref().unwrap().is_empty());
+
+        // Each document has the warnings and the lines of its own part of the reader.
+        let src = b"{ a 1 a 2 }\n\0\n{ b 1 }\n{}\0{\n}";
+        let documents = parse_opts
+            .clone()
+            .with_collect_warnings(true)
+            .with_record_line_index(true)
+            .parse_multi_with_warnings(&src[..], b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let kinds = documents
+            .iter()
+            .map(|(_, warnings)| warnings.iter().map(|w| w.kind()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                vec![QEntitiesWarningKind::DuplicateKey],
+                vec![QEntitiesWarningKind::EmptyEntity],
+                vec![QEntitiesWarningKind::EmptyEntity],
+            ]
+        );
+        let line_indices = documents
+            .iter()
+            .map(|(entities, _)| entities.line_index().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(line_indices, [&[0, 12][..], &[12, 14, 22], &[22, 27]]);
+        let location = documents[1].0.offset_to_location(15).unwrap();
+        assert_eq!((location.line(), location.column()), (3, 2));
+        assert_eq!(documents[2].0.offset_to_location(21), None);
+        let location = documents[2].0.offset_to_location(27).unwrap();
+        assert_eq!((location.line(), location.column()), (5, 1));
+        assert_eq!(documents[0].0.offset_to_location(14), None);
+    }
+
+    #[test]
+    fn error_kind_names() {
+        use QEntitiesParseErrorKind::*;
+
+        // Exhaustively matching ensures that every kind is listed.
+        for (index, kind) in QEntitiesParseErrorKind::ALL.iter().enumerate() {
+            let expected_index = match kind {
+                Io => 0,
+                UnterminatedCStyleComment => 1,
+                UnterminatedQuotedString => 2,
+                UnterminatedEntity => 3,
+                InvalidEscapeSequence => 4,
+                UnexpectedToken => 5,
+                KeyTooLong => 6,
+                ValueTooLong => 7,
+                TooManyEntities => 8,
+                TooManyEntityKeyValues => 9,
+                IllegalControlByte => 10,
+                UnsupportedFormat => 11,
+                DuplicateKey => 12,
+                MissingSeparator => 13,
+                EmptyKey => 14,
+                TrailingContent => 15,
+            };
+            assert_eq!(index, expected_index);
+        }
+        assert_eq!(QEntitiesParseErrorKind::ALL.len(), 16);
+
+        let names = QEntitiesParseErrorKind::ALL
+            .iter()
+            .map(|kind| kind.as_str())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(names.len(), QEntitiesParseErrorKind::ALL.len());
+        assert!(names
+            .iter()
+            .all(|name| name.bytes().all(|b| b.is_ascii_lowercase() || b == b'_')));
+
+        let e = QEntitiesParseOptions::new().parse(&b"{ a"[..]).unwrap_err();
+        assert_eq!(e.kind().as_str(), "unterminated_entity");
+    }
+
+    #[test]
+    fn retreat_columns() {
+        let location = QEntitiesParserLocation::new(10, 2, 4);
+        assert_eq!(location.retreat_columns(0), location);
+        assert_eq!(
+            location.retreat_columns(2),
+            QEntitiesParserLocation::new(8, 2, 2)
+        );
+
+        // Retreating never leaves the line, stopping at its first column.
+        let start = QEntitiesParserLocation::new(7, 2, 1);
+        assert_eq!(location.retreat_columns(3), start);
+        assert_eq!(location.retreat_columns(4), start);
+        assert_eq!(location.retreat_columns(u64::MAX), start);
+        assert_eq!(start.retreat_columns(1), start);
+
+        // The offset saturates for locations whose column exceeds their offset, such as those of
+        // an altered start location.
+        let location = QEntitiesParserLocation::new(1, 1, 5);
+        assert_eq!(
+            location.retreat_columns(3),
+            QEntitiesParserLocation::new(0, 1, 2)
+        );
+    }
+
+    #[test]
+    fn location_ordering() {
+        use std::collections::{BTreeSet, BinaryHeap};
+
+        let src = b"{ a b }\n{ c\n";
+        let locations = [
+            QEntitiesParserLocation::new(10, 2, 3),
+            QEntitiesParserLocation::new(0, 1, 1),
+            QEntitiesParserLocation::new(8, 2, 1),
+            QEntitiesParserLocation::new(2, 1, 3),
+        ];
+        let sorted = locations.iter().copied().collect::<BTreeSet<_>>();
+        assert!(sorted
+            .iter()
+            .map(|location| location.offset())
+            .eq([0, 2, 8, 10]));
+
+        let mut heap = locations.into_iter().collect::<BinaryHeap<_>>();
+        assert_eq!(heap.pop(), Some(QEntitiesParserLocation::new(10, 2, 3)));
+
+        let e = QEntitiesParseOptions::new().parse(&src[..]).unwrap_err();
+        assert!(e.location().unwrap() > &QEntitiesParserLocation::new(0, 1, 1));
+        assert!(e.location().unwrap() < &QEntitiesParserLocation::new(12, 3, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn location_serde() {
+        let location = QEntitiesParserLocation::new(123, 4, 56);
+        let json = serde_json::to_string(&location).unwrap();
+        assert_eq!(json, r#"{"offset":123,"line":4,"column":56}"#);
+        let deserialized: QEntitiesParserLocation = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, location);
+    }
+
+    #[test]
+    fn read_buffer_sizes() {
+        /// Reader that returns at most one byte per read.
+        struct ByteReader<'a>(&'a [u8]);
+
+        impl io::Read for ByteReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
             }
         }
 
-        let parse_opts = QEntitiesParseOptions::new();
-        [
-            expected_error(
-                b"{{",
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 1,
-                    column: 2,
-                },
-            ),
-            expected_error(
-                b"\n{{",
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 2,
-                    column: 2,
-                },
-            ),
-            expected_error(
-                b"{\n{",
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"\n{\n{",
-                QEntitiesParserLocation {
-                    offset: 3,
-                    line: 3,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"{ k v { k v } k v }",
-                QEntitiesParserLocation {
-                    offset: 6,
-                    line: 1,
-                    column: 7,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        let parse_opts = QEntitiesParseOptions::vtmb().with_record_line_index(true);
+        let expected = parse_opts.parse(VTMB_DATA).unwrap();
+        let invalid = &VTMB_DATA[..VTMB_DATA.len() - 3];
+        let expected_e = parse_opts.parse(invalid).unwrap_err();
+
+        for read_buffer_size in [0, 1, 2, 3, 7, 64, DEFAULT_READ_BUFFER_SIZE, 1 << 20] {
+            let parse_opts = parse_opts.clone().with_read_buffer_size(read_buffer_size);
+
+            let entities = parse_opts.parse(VTMB_DATA).unwrap();
+            assert!(entities.iter().eq(expected.iter()));
+            assert_eq!(entities.line_index(), expected.line_index());
+
+            let entities = parse_opts.parse(ByteReader(VTMB_DATA)).unwrap();
+            assert!(entities.iter().eq(expected.iter()));
+
+            let e = parse_opts.parse(invalid).unwrap_err();
+            assert_eq!(e.kind(), expected_e.kind());
+            assert_eq!(e.location(), expected_e.location());
+
+            let documents = parse_opts
+                .parse_multi(&b"{ a b }\0{ c d }"[..], b'\0')
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(documents.len(), 2);
+        }
+    }
+
+    #[test]
+    fn strict_eof() {
+        let strict_opts = QEntitiesParseOptions::quake().with_strict_eof(true);
+
+        // Whitespace and comments are allowed after the last entity.
+        for src in [
+            &b"{ a b }"[..],
+            b"{ a b }\n",
+            b"{ a b } // done",
+            b"{ a b }\r\n\t// done\n",
+            b"{ a b }{ c d }  ",
+            b"",
+            b"// empty\n",
+        ] {
+            for parse_opts in [QEntitiesParseOptions::quake(), strict_opts.clone()] {
+                assert!(parse_opts.parse(src).is_ok());
+            }
+        }
+
+        for (src, offset) in [
+            (&b"{ a b } c"[..], 8),
+            (b"{ a b }\n\"c\"", 8),
+            (b"{ a b }\n}", 8),
+            (b"{ a b }{ c d } // done\n}\n", 23),
+            (b"{ a b } c { d e }", 8),
+        ] {
+            let e = strict_opts.parse(src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::TrailingContent);
+            assert!(e.is_syntax());
+            assert_eq!(e.location().unwrap().offset(), offset);
+
+            // By default the content is an unexpected token.
+            let e = QEntitiesParseOptions::quake().parse(src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+            assert_eq!(e.location().unwrap().offset(), offset);
+        }
+
+        // Content preceding the first entity is still an unexpected token.
+        let e = strict_opts.parse(&b"} { a b }"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnexpectedToken);
+
+        // An open brace begins another entity.
+        let e = strict_opts.parse(&b"{ a b } {"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(e.location().unwrap().offset(), 8);
+    }
+
+    #[test]
+    fn resume_parser() {
+        let src = b"{ classname worldspawn }|\r\n{ a b }|  { c \"d\" }";
+
+        for read_buffer_size in [1, 3, 64] {
+            let parse_opts = QEntitiesParseOptions::new()
+                .with_read_buffer_size(read_buffer_size)
+                .with_record_line_index(true);
+
+            let source = BufferedReader::new(&src[..], &parse_opts);
+            let mut parser = Parser::new(source, &parse_opts);
+            parser.document_separator = Some(b'|');
+            let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+            parser.parse(&mut builder).unwrap();
+            assert!(parser.at_document_separator);
+
+            // Stop at the separator and resume parsing the rest of the reader as a new document
+            // without a separator.
+            let parts = parser.into_parts();
+            assert_eq!(parts.location, QEntitiesParserLocation::new(25, 1, 26));
+            let strict_opts = parse_opts.clone().with_strict_eof(true);
+            let mut parser = Parser::from_parts(parts, &strict_opts);
+            let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+            let e = parser.parse(&mut builder).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::TrailingContent);
+            assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(34, 2, 8)));
+
+            // Parsing resumes after the trailing content that stopped the previous parser.
+            let mut parser = Parser::from_parts(parser.into_parts(), &parse_opts);
+            let mut builder = QEntitiesBuilder::with_hasher(DefaultHashBuilder::default());
+            parser.parse(&mut builder).unwrap();
+            let entities = builder.finish();
+            assert!(entities
+                .get(0)
+                .unwrap()
+                .pairs()
+                .eq([(&b"c"[..], &b"d"[..])]));
+            assert_eq!(parser.location.offset, src.len() as u64);
+            assert_eq!(parser.line_starts, [0, 27]);
+        }
     }
 
     #[test]
-    fn unpaired_close_braces() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::UnexpectedToken(QEntitiesTokenKind::CloseBrace),
-                location,
-            }
+    fn warnings() {
+        let src = b"{ a 1 a 2 b 3 }{}\n{ \"a\" 1 }\n{ b 1 b 2 b }";
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_collect_warnings(true)
+            .with_allow_valueless_keys(true);
+
+        let expected = [
+            (QEntitiesWarningKind::DuplicateKey, 6),
+            (QEntitiesWarningKind::EmptyEntity, 15),
+            (QEntitiesWarningKind::DuplicateKey, 34),
+            (QEntitiesWarningKind::DuplicateKey, 38),
+        ];
+        for policy in [
+            QEntitiesDuplicateKeyPolicy::Keep,
+            QEntitiesDuplicateKeyPolicy::Replace,
+        ] {
+            let (entities, warnings) = parse_opts
+                .clone()
+                .with_duplicate_key_policy(policy)
+                .parse_with_warnings(&src[..])
+                .unwrap();
+            assert_eq!(entities.len(), 4);
+            assert_eq!(
+                warnings
+                    .iter()
+                    .map(|w| (w.kind(), w.location().offset()))
+                    .collect::<Vec<_>>(),
+                expected
+            );
         }
 
-        let parse_opts = QEntitiesParseOptions::new();
-        [
-            expected_error(
-                b"}",
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"\n}",
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                b"{}}",
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 1,
-                    column: 3,
-                },
-            ),
-            expected_error(
-                b"{ k v }}",
-                QEntitiesParserLocation {
-                    offset: 7,
-                    line: 1,
-                    column: 8,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        // Keys are only duplicates within the same entity, and parsing without collecting
+        // warnings yields none.
+        let (_, warnings) = QEntitiesParseOptions::new()
+            .with_collect_warnings(true)
+            .parse_with_warnings(&b"{ a 1 }{ a 1 }"[..])
+            .unwrap();
+        assert!(warnings.is_empty());
+        let (entities, warnings) = QEntitiesParseOptions::new()
+            .parse_with_warnings(&src[..17])
+            .unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(warnings.is_empty());
+
+        let (_, warnings) = parse_opts.parse_with_warnings(&b"\n  {\n}"[..]).unwrap();
+        assert_eq!(
+            warnings[0].to_string(),
+            format!("empty entity {}", warnings[0].location())
+        );
+        assert_eq!(
+            warnings[0].location(),
+            &QEntitiesParserLocation::new(3, 2, 3)
+        );
+
+        // Warnings are not collected for duplicate keys that are errors.
+        let e = parse_opts
+            .clone()
+            .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error)
+            .parse_with_warnings(&src[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::DuplicateKey);
     }
 
     #[test]
-    fn exterior_key_values() {
-        fn expected_error(
-            kind: QEntitiesTokenKind,
-            src: &[u8],
-            location: QEntitiesParserLocation,
-        ) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::UnexpectedToken(kind),
-                location,
-            }
+    fn unicode_escapes() {
+        let parse_opts = QEntitiesParseOptions::new().with_escape_options(Some(
+            QEntitiesParseEscapeOptions::new().with_unicode_escapes(true),
+        ));
+        let parse_value = |src: &[u8]| {
+            parse_opts
+                .parse(src)
+                .map(|entities| entities.get(0).unwrap().get(0).unwrap().value().to_vec())
+        };
+
+        // Characters of the basic multilingual plane are pushed as their UTF-8 encoding.
+        assert_eq!(parse_value(br#"{ a "\u0041" }"#).unwrap(), b"A");
+        assert_eq!(
+            parse_value(br#"{ a "\u00e9\u00E9" }"#).unwrap(),
+            "éé".as_bytes()
+        );
+        assert_eq!(
+            parse_value(br#"{ a "\u20AC1\uFFFF" }"#).unwrap(),
+            "€1\u{FFFF}".as_bytes()
+        );
+        assert_eq!(parse_value(br#"{ a "\u0000" }"#).unwrap(), b"\0");
+
+        // Surrogates and sequences with fewer than four digits are invalid at the back-slash.
+        for src in [
+            &br#"{ a "x\uD800" }"#[..],
+            br#"{ a "x\udfff" }"#,
+            br#"{ a "x\u12" }"#,
+            br#"{ a "x\u12G4" }"#,
+            br#"{ a "x\u"#,
+        ] {
+            let e = parse_value(src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
+            assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(6, 1, 7)));
         }
 
-        let parse_opts = QEntitiesParseOptions::new();
-        [
-            expected_error(
-                QEntitiesTokenKind::QuotedString,
-                b"\"k\" \"v\"",
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                QEntitiesTokenKind::UnquotedString,
-                b"k v",
-                QEntitiesParserLocation {
-                    offset: 0,
-                    line: 1,
-                    column: 1,
-                },
-            ),
-            expected_error(
-                QEntitiesTokenKind::QuotedString,
-                b"{ k v }\n\"k\" \"v\"",
-                QEntitiesParserLocation {
-                    offset: 8,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        // Unicode escape sequences count towards the maximum length in bytes.
+        let e = parse_opts
+            .clone()
+            .with_max_value_length(Some(2))
+            .parse(&br#"{ a "\u20AC" }"#[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::ValueTooLong);
+
+        // Unicode escape sequences are not parsed unless enabled.
+        let e = QEntitiesParseOptions::new()
+            .with_escape_options(Some(QEntitiesParseEscapeOptions::new()))
+            .parse(&br#"{ a "\u0041" }"#[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
+
+        // Unquoted strings also parse unicode escape sequences when escapes are enabled for them.
+        let entities = parse_opts
+            .clone()
+            .with_escape_unquoted(true)
+            .parse(&br#"{ a caf\u00E9 }"#[..])
+            .unwrap();
+        assert_eq!(
+            entities.get(0).unwrap().value_of(b"a"),
+            Some("café".as_bytes())
+        );
     }
 
     #[test]
-    fn overlong_keys() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::KeyTooLong),
-                location,
-            }
-        }
+    fn entity_delimiters() {
+        let parse_opts = QEntitiesParseOptions::new().with_entity_delimiters(b'[', b']');
+        let parse_pairs = |parse_opts: &QEntitiesParseOptions, src: &[u8]| {
+            parse_opts.parse(src).map(|entities| {
+                entities
+                    .iter()
+                    .map(|entity| {
+                        entity
+                            .pairs()
+                            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
 
-        let parse_opts = QEntitiesParseOptions::new().with_max_key_length(Some(4));
-        [
-            expected_error(
-                br#"{"12345" ""}"#,
-                QEntitiesParserLocation {
-                    offset: 1,
-                    line: 1,
-                    column: 2,
-                },
-            ),
-            expected_error(
-                br#"{"1234" "1234" "12345" "1234" }"#,
-                QEntitiesParserLocation {
-                    offset: 15,
-                    line: 1,
-                    column: 16,
-                },
-            ),
-            expected_error(
-                b"{\n12345 1234 }",
-                QEntitiesParserLocation {
-                    offset: 2,
-                    line: 2,
-                    column: 1,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        assert_eq!(
+            parse_pairs(&parse_opts, b"[ k v ]\n[ ]\n[ \"{\" } ]").unwrap(),
+            [
+                vec![(b"k".to_vec(), b"v".to_vec())],
+                vec![],
+                vec![(b"{".to_vec(), b"}".to_vec())],
+            ]
+        );
+
+        // Braces no longer delimit entities.
+        let e = parse_opts.parse(&b"{ k v }"[..]).unwrap_err();
+        let e = <&QEntitiesUnexpectedTokenError>::try_from(&e).unwrap();
+        assert_eq!(e.kind(), QEntitiesTokenKind::UnquotedString);
+        assert_eq!(
+            e.to_string(),
+            r#"expected "[", found "unquoted string" token @0 line#1 column#1"#
+        );
+        let e = parse_opts.parse(&b"[ k v ] ]"[..]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            r#"expected "[", found "]" token @8 line#1 column#9"#
+        );
+        let e = parse_opts.parse(&b"[ k v"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+
+        // The delimiters terminate unquoted strings and are escaped in place of braces.
+        let parse_opts = parse_opts
+            .with_controls_terminate_unquoted_strings(true)
+            .with_escape_options(Some(QEntitiesParseEscapeOptions::new().with_braces(true)));
+        assert_eq!(
+            parse_pairs(&parse_opts, br#"[k v][a\]b{c} "\[\]"]"#).unwrap(),
+            [
+                vec![(b"k".to_vec(), b"v".to_vec())],
+                vec![(b"a]b{c}".to_vec(), b"[]".to_vec())],
+            ]
+        );
+        let e = parse_opts.parse(&br#"[ k "\{" ]"#[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::InvalidEscapeSequence);
     }
 
     #[test]
-    fn overlong_values() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::ValueTooLong),
-                location,
+    fn escape_option_flags() {
+        type Setter =
+            fn(&mut QEntitiesParseEscapeOptions, bool) -> &mut QEntitiesParseEscapeOptions;
+        let setters: [Setter; 5] = [
+            QEntitiesParseEscapeOptions::double_quotes,
+            QEntitiesParseEscapeOptions::single_quotes,
+            QEntitiesParseEscapeOptions::braces,
+            QEntitiesParseEscapeOptions::whitespace,
+            QEntitiesParseEscapeOptions::unicode_escapes,
+        ];
+
+        let other_flag_sets = [
+            QEntitiesParseFlags::empty(),
+            QEntitiesParseFlags::all().difference(QEntitiesParseFlags::ESCAPE_OPTIONS),
+            QEntitiesParseOptions::quake().flags,
+        ];
+        for combination in 0u32..1 << setters.len() {
+            let mut escape_options = QEntitiesParseEscapeOptions::new();
+            for (i, setter) in setters.iter().enumerate() {
+                setter(&mut escape_options, combination & 1 << i != 0);
+            }
+            let escape_flags = escape_options.flags;
+            assert!(escape_flags.contains(QEntitiesParseFlags::ESCAPE));
+            assert!(QEntitiesParseFlags::ESCAPE_OPTIONS.contains(escape_flags));
+            assert_eq!(
+                escape_flags.bits().count_ones(),
+                1 + combination.count_ones()
+            );
+
+            for other_flags in other_flag_sets {
+                let mut parse_opts = QEntitiesParseOptions::new();
+                parse_opts.flags = other_flags | QEntitiesParseFlags::ESCAPE_OPTIONS;
+
+                parse_opts.escape_options(Some(escape_options.clone()));
+                assert_eq!(
+                    parse_opts.flags,
+                    other_flags.difference(QEntitiesParseFlags::ESCAPE_OPTIONS) | escape_flags
+                );
+
+                parse_opts.escape_options(None);
+                assert_eq!(
+                    parse_opts.flags,
+                    other_flags.difference(QEntitiesParseFlags::ESCAPE_OPTIONS)
+                );
             }
         }
 
-        let parse_opts = QEntitiesParseOptions::new().with_max_value_length(Some(4));
-        [
-            expected_error(
-                br#"{"" "12345"}"#,
-                QEntitiesParserLocation {
-                    offset: 4,
-                    line: 1,
-                    column: 5,
-                },
-            ),
-            expected_error(
-                br#"{"1234" "1234" "1234" "12345" }"#,
-                QEntitiesParserLocation {
-                    offset: 22,
-                    line: 1,
-                    column: 23,
-                },
-            ),
-            expected_error(
-                b"{\n1234\n12345 }",
-                QEntitiesParserLocation {
-                    offset: 7,
-                    line: 3,
-                    column: 1,
-                },
-            ),
+        // Stray flags carried by escape options never leak into the other options.
+        let stray = QEntitiesParseEscapeOptions {
+            flags: QEntitiesParseFlags::all(),
+        };
+        let parse_opts = QEntitiesParseOptions::new().with_escape_options(Some(stray));
+        assert_eq!(parse_opts.flags, QEntitiesParseFlags::ESCAPE_OPTIONS);
+    }
+
+    /// Options covering the grammar features and limits, used for checking that arbitrary input
+    /// never causes a panic.
+    fn fuzz_options() -> Vec<QEntitiesParseOptions> {
+        let all_escapes = QEntitiesParseEscapeOptions::new()
+            .with_double_quotes(true)
+            .with_single_quotes(true)
+            .with_braces(true)
+            .with_whitespace(true)
+            .with_unicode_escapes(true);
+        let end = QEntitiesParserLocation::new(u64::MAX - 1, u64::MAX - 1, u64::MAX - 1);
+
+        vec![
+            QEntitiesParseOptions::new(),
+            QEntitiesParseOptions::quake(),
+            QEntitiesParseOptions::quake2(),
+            QEntitiesParseOptions::goldsrc(),
+            QEntitiesParseOptions::quake3(),
+            QEntitiesParseOptions::source2(),
+            QEntitiesParseOptions::quake()
+                .with_escape_options(Some(all_escapes.clone()))
+                .with_escape_unquoted(true)
+                .with_single_quoted_strings(true)
+                .with_controls_terminate_unquoted_strings(true)
+                .with_comments_terminate_unquoted_strings(true)
+                .with_retain_comments(true)
+                .with_retain_parse_options(true),
+            QEntitiesParseOptions::new()
+                .with_escape_options(Some(all_escapes))
+                .with_whitespace_set(QEntitiesWhitespaceSet::SpaceTabNewline)
+                .with_reject_control_bytes(true)
+                .with_reject_empty_keys(true)
+                .with_lowercase_keys(true)
+                .with_nul_terminates_input(true)
+                .with_intern(false),
+            QEntitiesParseOptions::quake()
+                .with_implicit_top_level_entity(true)
+                .with_allow_valueless_keys(true)
+                .with_key_value_separator(Some(b'='))
+                .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Replace),
+            QEntitiesParseOptions::new()
+                .with_strict_eof(true)
+                .with_allow_valueless_keys(true)
+                .with_entity_delimiters(b'[', b']')
+                .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error),
+            QEntitiesParseOptions::quake()
+                .with_max_key_length(Some(0))
+                .with_max_value_length(Some(1))
+                .with_max_entities(Some(1))
+                .with_max_entity_key_values(Some(1)),
+            QEntitiesParseOptions::quake()
+                .with_max_entities(Some(0))
+                .with_implicit_top_level_entity(true),
+            QEntitiesParseOptions::quake()
+                .with_start_location(end)
+                .with_record_line_index(true)
+                .with_capture_error_context(true)
+                .with_collect_warnings(true)
+                .with_read_buffer_size(1)
+                .with_expected_chunk_count(usize::MAX),
         ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
     }
 
-    #[test]
-    fn too_many_entities() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(QEntitiesParseErrorKind::TooManyEntities),
-                location,
+    /// Bytes that are significant to some part of the grammar, from which arbitrary input is
+    /// mostly built so that it reaches deeper into the parser.
+    const FUZZ_BYTES: &[u8] = b"{}[]\"'/*\\ \t\r\n\x0c=:<!-u09aAfF\0\x01\x7f\x80\xff";
+
+    proptest::proptest! {
+        #[test]
+        fn arbitrary_input_never_panics(
+            src in proptest::collection::vec(
+                proptest::prop_oneof![
+                    3 => proptest::sample::select(FUZZ_BYTES),
+                    1 => proptest::num::u8::ANY,
+                ],
+                0..96,
+            ),
+        ) {
+            for parse_opts in fuzz_options() {
+                match parse_opts.parse_with_warnings(&src[..]) {
+                    Ok((entities, warnings)) => {
+                        let _ = entities.to_string();
+                        for warning in &warnings {
+                            let _ = warning.to_string();
+                        }
+                        if let Some(line_index) = entities.line_index() {
+                            let last = line_index.last().copied().unwrap_or(0);
+                            assert!(entities.offset_to_location(last).is_some());
+                            let past_end = src.len() as u64 + 1;
+                            assert!(entities.offset_to_location(past_end).is_none());
+                        }
+                    }
+                    Err(e) => {
+                        let _ = e.to_string();
+                        let _ = e.location().map(|location| location.retreat_columns(2));
+                    }
+                }
+                let _ = parse_opts.parse_multi(&src[..], b'\0').count();
+                let _ = parse_opts.parse_in_arena(&src[..], &mut QEntitiesArena::new());
             }
         }
+    }
 
-        let parse_opts = QEntitiesParseOptions::new().with_max_entities(Some(4));
-        [
-            expected_error(
-                b"{}{}{}{}{}",
-                QEntitiesParserLocation {
-                    offset: 8,
-                    line: 1,
-                    column: 9,
-                },
-            ),
-            expected_error(
-                b"{ k v }{ k v }{ k v }{ k v }{ k v }",
-                QEntitiesParserLocation {
-                    offset: 28,
-                    line: 1,
-                    column: 29,
-                },
-            ),
-            expected_error(
-                b"{}\n{}\n{}\n{}\n{}",
-                QEntitiesParserLocation {
-                    offset: 12,
-                    line: 5,
-                    column: 1,
-                },
-            ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+    #[test]
+    fn option_getters() {
+        let options = QEntitiesParseOptions::new();
+        assert!(!options.cpp_style_comments_enabled());
+        assert!(options.intern_enabled());
+        assert!(options.get_escape_options().is_none());
+        assert_eq!(options.get_whitespace_set(), QEntitiesWhitespaceSet::Ascii);
+        assert_eq!(
+            options.get_duplicate_key_policy(),
+            QEntitiesDuplicateKeyPolicy::Keep
+        );
+        assert_eq!(options.get_entity_delimiters(), (b'{', b'}'));
+        assert_eq!(options.get_max_key_length(), None);
+
+        let escape_options = QEntitiesParseEscapeOptions::new()
+            .with_double_quotes(true)
+            .with_unicode_escapes(true);
+        assert!(escape_options.double_quotes_enabled());
+        assert!(!escape_options.single_quotes_enabled());
+        assert!(!escape_options.braces_enabled());
+        assert!(!escape_options.whitespace_enabled());
+        assert!(escape_options.unicode_escapes_enabled());
+
+        let location = QEntitiesParserLocation::new(10, 2, 3);
+        let options = QEntitiesParseOptions::new()
+            .with_cpp_style_comments(true)
+            .with_c_style_comments(true)
+            .with_controls_terminate_unquoted_strings(true)
+            .with_comments_terminate_unquoted_strings(true)
+            .with_single_quoted_strings(true)
+            .with_escape_options(Some(escape_options))
+            .with_escape_unquoted(true)
+            .with_whitespace_set(QEntitiesWhitespaceSet::SpaceTabNewline)
+            .with_capture_error_context(true)
+            .with_intern(false)
+            .with_expected_chunk_count(64)
+            .with_record_line_index(true)
+            .with_allow_valueless_keys(true)
+            .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Replace)
+            .with_implicit_top_level_entity(true)
+            .with_strict_eof(true)
+            .with_collect_warnings(true)
+            .with_key_value_separator(Some(b'='))
+            .with_entity_delimiters(b'[', b']')
+            .with_reject_control_bytes(true)
+            .with_reject_empty_keys(true)
+            .with_max_key_length(Some(1))
+            .with_max_value_length(Some(2))
+            .with_max_entities(Some(3))
+            .with_max_entity_key_values(Some(4))
+            .with_read_buffer_size(5)
+            .with_start_location(location);
+        assert!(options.cpp_style_comments_enabled());
+        assert!(options.c_style_comments_enabled());
+        assert!(options.controls_terminate_unquoted_strings_enabled());
+        assert!(options.comments_terminate_unquoted_strings_enabled());
+        assert!(options.single_quoted_strings_enabled());
+        assert!(options.escape_unquoted_enabled());
+        assert!(options.capture_error_context_enabled());
+        assert!(!options.intern_enabled());
+        assert!(options.record_line_index_enabled());
+        assert!(options.allow_valueless_keys_enabled());
+        assert!(options.implicit_top_level_entity_enabled());
+        assert!(options.strict_eof_enabled());
+        assert!(options.collect_warnings_enabled());
+        assert!(options.reject_control_bytes_enabled());
+        assert!(options.reject_empty_keys_enabled());
+
+        let escape_options = options.get_escape_options().unwrap();
+        assert!(escape_options.double_quotes_enabled());
+        assert!(!escape_options.single_quotes_enabled());
+        assert!(escape_options.unicode_escapes_enabled());
+
+        assert_eq!(
+            options.get_whitespace_set(),
+            QEntitiesWhitespaceSet::SpaceTabNewline
+        );
+        assert_eq!(options.get_expected_chunk_count(), 64);
+        assert_eq!(
+            options.get_duplicate_key_policy(),
+            QEntitiesDuplicateKeyPolicy::Replace
+        );
+        assert_eq!(options.get_key_value_separator(), Some(b'='));
+        assert_eq!(options.get_entity_delimiters(), (b'[', b']'));
+        assert_eq!(options.get_max_key_length(), Some(1));
+        assert_eq!(options.get_max_value_length(), Some(2));
+        assert_eq!(options.get_max_entities(), Some(3));
+        assert_eq!(options.get_max_entity_key_values(), Some(4));
+        assert_eq!(options.get_read_buffer_size(), 5);
+        assert_eq!(options.get_start_location(), location);
+
+        // A policy that is changed back is reported as such.
+        let options = options.with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error);
+        assert_eq!(
+            options.get_duplicate_key_policy(),
+            QEntitiesDuplicateKeyPolicy::Error
+        );
+        assert!(options
+            .with_escape_options(None)
+            .get_escape_options()
+            .is_none());
     }
 
     #[test]
-    fn too_many_entity_kvs() {
-        fn expected_error(src: &[u8], location: QEntitiesParserLocation) -> ExpectedError {
-            ExpectedError {
-                src,
-                kind: ExpectedErrorVariant::SimpleKind(
-                    QEntitiesParseErrorKind::TooManyEntityKeyValues,
-                ),
-                location,
-            }
+    fn long_unquoted_values() {
+        let blob = "QUFB".repeat(256 * 1024);
+        let src = format!("{{ blob {blob} }}\n{{ classname light blob {blob} }}");
+
+        for read_buffer_size in [1, 7, 8192] {
+            let entities = QEntitiesParseOptions::new()
+                .with_read_buffer_size(read_buffer_size)
+                .parse(src.as_bytes())
+                .unwrap();
+            let (entity_a, entity_b) = (entities.get(0).unwrap(), entities.get(1).unwrap());
+            let (a, b) = (entity_a.get(0).unwrap(), entity_b.get(1).unwrap());
+            assert_eq!(a.value(), blob.as_bytes());
+            assert_eq!(b.value(), blob.as_bytes());
+
+            // The blob is stored once, no matter how many times it appears.
+            assert!(a.shares_storage_with(&b));
+            assert_eq!(entities.chunk_count(), 4);
+            assert_eq!(entities.byte_footprint(), 4 + 9 + 5 + blob.len());
         }
 
-        let parse_opts = QEntitiesParseOptions::new().with_max_entity_key_values(Some(4));
-        [
-            expected_error(
-                b"{ k v k v k v k v k v }",
-                QEntitiesParserLocation {
-                    offset: 18,
-                    line: 1,
-                    column: 19,
-                },
+        let entities = QEntitiesParseOptions::new()
+            .with_intern(false)
+            .parse(src.as_bytes())
+            .unwrap();
+        assert_eq!(entities.byte_footprint(), 4 + 9 + 5 + 4 + 2 * blob.len());
+    }
+
+    #[test]
+    fn nul_terminates_input() {
+        let src = b"{ classname worldspawn }\n{ classname light }\0\xcd\xcd}{ \"garbage\0";
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_nul_terminates_input(true)
+            .with_strict_eof(true)
+            .with_reject_control_bytes(true);
+
+        for read_buffer_size in [1, 3, 8192] {
+            let parse_opts = parse_opts.clone().with_read_buffer_size(read_buffer_size);
+            let entities = parse_opts.parse(&src[..]).unwrap();
+            assert_eq!(entities.len(), 2);
+            assert_eq!(
+                entities.get(1).unwrap().value_of(b"classname"),
+                Some(&b"light"[..])
+            );
+
+            // Parsing stops at the NUL byte as it would at the end of the reader.
+            let mut multi = parse_opts.parse_multi(&src[..], b'|');
+            assert_eq!(multi.next().unwrap().unwrap().len(), 2);
+            assert!(multi.next().is_none());
+        }
+
+        // A NUL byte terminates the input within a token.
+        let e = parse_opts
+            .parse(&b"{ classname \"world\0spawn\" }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedQuotedString);
+        let e = parse_opts
+            .parse(&b"{ classname world\0spawn }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(0, 1, 1)));
+
+        // Without the option the NUL byte is an ordinary control byte.
+        let parse_opts = parse_opts.with_nul_terminates_input(false);
+        let e = parse_opts.parse(&src[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::TrailingContent);
+        let e = parse_opts
+            .parse(&b"{ classname world\0spawn }"[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::IllegalControlByte);
+    }
+
+    #[test]
+    fn unterminated_quote_bytes() {
+        let parse_opts = QEntitiesParseOptions::new().with_single_quoted_strings(true);
+
+        for (src, quote, location) in [
+            (
+                &b"{ classname \"light }"[..],
+                b'"',
+                QEntitiesParserLocation::new(12, 1, 13),
             ),
-            expected_error(
-                br#"{ k v k v k v k v "k" "v" }"#,
-                QEntitiesParserLocation {
-                    offset: 18,
-                    line: 1,
-                    column: 19,
-                },
+            (
+                b"{ classname 'light\" }",
+                b'\'',
+                QEntitiesParserLocation::new(12, 1, 13),
             ),
-            expected_error(
-                b"{ k v k v k v k v }{ k v k v k v k v k v }",
-                QEntitiesParserLocation {
-                    offset: 37,
-                    line: 1,
-                    column: 38,
-                },
+            (
+                b"{\n'classname\" light",
+                b'\'',
+                QEntitiesParserLocation::new(2, 2, 1),
             ),
-            expected_error(
-                b"{\nk v\nk v\nk v\nk v\nk v\n}",
-                QEntitiesParserLocation {
-                    offset: 18,
-                    line: 6,
-                    column: 1,
-                },
+            (
+                b"{ classname light\r\n\"origin' }",
+                b'"',
+                QEntitiesParserLocation::new(19, 2, 1),
             ),
-        ]
-        .iter()
-        .for_each(|ee| ee.test(&parse_opts));
+        ] {
+            let e = parse_opts.parse(src).unwrap_err();
+            assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedQuotedString);
+            assert_eq!(e.quote(), Some(quote));
+            assert_eq!(e.location(), Some(&location));
+            assert_eq!(e.clone_non_io().unwrap().quote(), Some(quote));
+            assert!(e.to_string().starts_with(&format!(
+                "unterminated quoted string opened with `{}`",
+                char::from(quote)
+            )));
+        }
+
+        let e = parse_opts.parse(&b"{ classname light"[..]).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::UnterminatedEntity);
+        assert_eq!(e.quote(), None);
     }
 
     #[test]
-    fn comments() {
-        #[rustfmt::skip]
-        let data =
-br#"// first line
-{ k0 v0 //
-}{ k1 /*c*/ v1 } /***/
-//c
-{ /* c */ k2 v2 }//c
-{ k3//c
-v3/**c**/}
-{ k4/**c**/v4//
-}//"#;
+    fn byte_sources() {
+        /// A source that yields the bytes of a slice and then fails instead of ending.
+        struct FailingSource<'a> {
+            bytes: &'a [u8],
+            reads: usize,
+        }
 
-        let entities = QEntitiesParseOptions::new()
-            .cpp_style_comments(true)
-            .c_style_comments(true)
-            .comments_terminate_unquoted_strings(true)
-            .parse(&data[..])
-            .unwrap();
+        impl QEntitiesByteSource for FailingSource<'_> {
+            fn next_byte(&mut self) -> io::Result<Option<u8>> {
+                self.reads += 1;
+                match self.bytes.next_byte()? {
+                    Some(byte) => Ok(Some(byte)),
+                    None => Err(io::Error::other("source failed")),
+                }
+            }
+        }
 
-        assert_eq!(entities.len(), 5);
-        for (index, entity) in entities.iter().enumerate() {
-            assert_eq!(entity.len(), 1);
-            let (key, value) = entity.get(0).map(|kv| (kv.key(), kv.value())).unwrap();
-            assert_eq!(key, format!("k{}", index).into_bytes());
-            assert_eq!(value, format!("v{}", index).into_bytes());
+        let parse_opts = QEntitiesParseOptions::quake()
+            .with_escape_options(Some(QEntitiesParseEscapeOptions::new()))
+            .with_record_line_index(true);
+        for src in [
+            &b"{ classname worldspawn }\r\n// comment\n{ \"a\" \"b\\\\\" c d }"[..],
+            b"",
+            b"{ classname worldspawn",
+            b"{ a \"b }",
+            b"}",
+        ] {
+            let expected = parse_opts.parse(src);
+            match (parse_opts.parse_source(src), expected) {
+                (Ok(entities), Ok(expected)) => {
+                    assert_eq!(
+                        entities.iter().collect::<Vec<_>>(),
+                        expected.iter().collect::<Vec<_>>()
+                    );
+                    assert_eq!(entities.line_index(), expected.line_index());
+                }
+                (Err(e), Err(expected)) => {
+                    assert_eq!(e.kind(), expected.kind());
+                    assert_eq!(e.location(), expected.location());
+                }
+                (res, expected) => panic!("{res:?} != {expected:?}"),
+            }
         }
+
+        // Errors of the source are reported as I/O errors, and the source is not read from again
+        // once it has failed.
+        let mut source = FailingSource {
+            bytes: b"{ classname worldspawn }",
+            reads: 0,
+        };
+        let e = parse_opts.parse_source(&mut source).unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::Io);
+        assert_eq!(source.reads, 25);
+
+        // Nothing following a NUL byte that terminates the input is read from the source.
+        let mut src = &b"{ classname worldspawn }\0{"[..];
+        let entities = parse_opts
+            .clone()
+            .with_nul_terminates_input(true)
+            .parse_source(&mut src)
+            .unwrap();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(src, b"{");
     }
 
     #[test]
-    fn vtmb_entities() {
-        #[rustfmt::skip]
-        let data =
-br#"// vtmb
-{
-"world_maxs" "4096 4096 4096"
-"world_mins" "-4096 -4096 -4096"
-"classname" "worldspawn"
-"skyname" "thesky"
-"sounds" "1"
-"MaxRange" "1337"
-"fogcolor" "255 255 255"
-"fogcolor2" "255 255 255"
-"fogdir" "0 1 0"
-"fogstart" "123.0"
-"fogend" "456.0"
-"wetness_fadetarget" "0.11"
-"wetness_fadein" "2.3"
-"wetness_fadeout" "5.4"
-"levelscript" "thescript"
-"safearea" "2"
-"nosferatu_tolerrant" "1"
-}
-{
-"classname" "logic_relay"
-"StartDisabled" "0"
-"targetname" "relay_a"
-"spawnflags" "1"
-"OnTrigger" ",,,0,-1,ScriptFn(\"arg_a\", \"arg_b\"),"
-"origin" "1 2 3"
-}
-{
-"classname" "logic_relay"
-"StartDisabled" "0"
-"targetname" "relay_b"
-"spawnflags" "1"
-"OnTrigger" ",,,0,-1,ScriptFn(\"a\", \"b\", \"c\"),"
-"origin" "4 5 6"
-}"#;
+    fn retain_comments() {
+        let src = b"// spawn point\r\n// facing east\n{ classname info_player_start /* inner */ angle 0 }\n\
+            /* a\n * block */{ classname light // inner\n}{}\n// eof\n/**/";
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_cpp_style_comments(true)
+            .with_c_style_comments(true)
+            .with_retain_comments(true);
+        let entities = parse_opts.parse(&src[..]).unwrap();
+        assert_eq!(entities.len(), 3);
+
+        let leading = |index: usize| -> Vec<&[u8]> {
+            entities.get(index).unwrap().leading_comments().collect()
+        };
+        assert_eq!(leading(0), [&b" spawn point"[..], b" facing east"]);
+        assert_eq!(leading(1), [&b" a\n * block "[..]]);
+        assert!(leading(2).is_empty());
+        assert!(entities.trailing_comments().eq([&b" eof"[..], b""]));
+        assert_eq!(entities.trailing_comments().rev().len(), 2);
+
+        // Comments within an entity are discarded, including those terminating unquoted strings.
+        let entities = parse_opts
+            .clone()
+            .with_comments_terminate_unquoted_strings(true)
+            .parse(&b"{ a b//c\n}/*d*/{ e f/*g*/ }"[..])
+            .unwrap();
+        assert_eq!(entities.get(0).unwrap().leading_comments().len(), 0);
+        assert!(entities.get(1).unwrap().leading_comments().eq([&b"d"[..]]));
+        assert_eq!(entities.trailing_comments().len(), 0);
+
+        // The comments of a file without entities are all trailing.
+        let entities = parse_opts.parse(&b"// a\n/* b */"[..]).unwrap();
+        assert!(entities.trailing_comments().eq([&b" a"[..], b" b "]));
+
+        // The implicit entity is preceded by the comments before its first string.
+        let entities = parse_opts
+            .clone()
+            .with_implicit_top_level_entity(true)
+            .parse(&b"// header\nclassname worldspawn // inner"[..])
+            .unwrap();
+        assert!(entities
+            .get(0)
+            .unwrap()
+            .leading_comments()
+            .eq([&b" header"[..]]));
+        assert_eq!(entities.trailing_comments().len(), 0);
+
+        // Each document retains its own comments.
+        let documents = parse_opts
+            .parse_multi(&b"// a\n{}// b\n\0// c\n{}"[..], b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(documents[0]
+            .get(0)
+            .unwrap()
+            .leading_comments()
+            .eq([&b" a"[..]]));
+        assert!(documents[0].trailing_comments().eq([&b" b"[..]]));
+        assert!(documents[1]
+            .get(0)
+            .unwrap()
+            .leading_comments()
+            .eq([&b" c"[..]]));
+
+        // Comments are retained through edits, but not by derived collections.
+        let entities = parse_opts.parse(&src[..]).unwrap();
+        let mut editor = entities.edit();
+        editor.set_value(0, 1, b"90");
+        let edited = editor.finish();
+        assert_eq!(edited.get(0).unwrap().leading_comments().len(), 2);
+        let filtered = edited.filter(|_| true);
+        assert_eq!(filtered.get(0).unwrap().leading_comments().len(), 0);
+        assert_eq!(filtered.trailing_comments().len(), 0);
+
+        // Comments are not retained unless enabled.
+        let entities = parse_opts
+            .clone()
+            .with_retain_comments(false)
+            .parse(&src[..])
+            .unwrap();
+        assert_eq!(entities.get(0).unwrap().leading_comments().len(), 0);
+        assert_eq!(entities.trailing_comments().len(), 0);
+    }
 
-        let expected_entities: &[&[(&[u8], &[u8])]] = &[
-            &[
-                (b"world_maxs", b"4096 4096 4096"),
-                (b"world_mins", b"-4096 -4096 -4096"),
-                (b"classname", b"worldspawn"),
-                (b"skyname", b"thesky"),
-                (b"sounds", b"1"),
-                (b"MaxRange", b"1337"),
-                (b"fogcolor", b"255 255 255"),
-                (b"fogcolor2", b"255 255 255"),
-                (b"fogdir", b"0 1 0"),
-                (b"fogstart", b"123.0"),
-                (b"fogend", b"456.0"),
-                (b"wetness_fadetarget", b"0.11"),
-                (b"wetness_fadein", b"2.3"),
-                (b"wetness_fadeout", b"5.4"),
-                (b"levelscript", b"thescript"),
-                (b"safearea", b"2"),
-                (b"nosferatu_tolerrant", b"1"),
-            ],
-            &[
-                (b"classname", b"logic_relay"),
-                (b"StartDisabled", b"0"),
-                (b"targetname", b"relay_a"),
-                (b"spawnflags", b"1"),
-                (b"OnTrigger", b",,,0,-1,ScriptFn(\"arg_a\", \"arg_b\"),"),
-                (b"origin", b"1 2 3"),
-            ],
-            &[
-                (b"classname", b"logic_relay"),
-                (b"StartDisabled", b"0"),
-                (b"targetname", b"relay_b"),
-                (b"spawnflags", b"1"),
-                (b"OnTrigger", b",,,0,-1,ScriptFn(\"a\", \"b\", \"c\"),"),
-                (b"origin", b"4 5 6"),
-            ],
+    #[test]
+    fn locations_after_unquoted_strings() {
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_cpp_style_comments(true)
+            .with_c_style_comments(true)
+            .with_comments_terminate_unquoted_strings(true);
+        let controls_opts = parse_opts
+            .clone()
+            .with_controls_terminate_unquoted_strings(true);
+        let separator_opts = parse_opts.clone().with_key_value_separator(Some(b'='));
+
+        // The unexpected brace following an unquoted value is reported at its own location
+        // regardless of how the value was terminated.
+        let cases = [
+            (
+                &parse_opts,
+                &b"{ a b {"[..],
+                QEntitiesParserLocation::new(6, 1, 7),
+            ),
+            (
+                &parse_opts,
+                b"{ a b\t\t{",
+                QEntitiesParserLocation::new(7, 1, 8),
+            ),
+            (
+                &parse_opts,
+                b"{ a b\n{",
+                QEntitiesParserLocation::new(6, 2, 1),
+            ),
+            (
+                &parse_opts,
+                b"{ a b\r\n{",
+                QEntitiesParserLocation::new(7, 2, 1),
+            ),
+            (
+                &parse_opts,
+                b"{ a b\n\n {",
+                QEntitiesParserLocation::new(8, 3, 2),
+            ),
+            (
+                &parse_opts,
+                b"{ a b// c\n{",
+                QEntitiesParserLocation::new(10, 2, 1),
+            ),
+            (
+                &parse_opts,
+                b"{ a b/* c */{",
+                QEntitiesParserLocation::new(12, 1, 13),
+            ),
+            (
+                &parse_opts,
+                b"{ a b/c {",
+                QEntitiesParserLocation::new(8, 1, 9),
+            ),
+            (
+                &controls_opts,
+                b"{ a b{",
+                QEntitiesParserLocation::new(5, 1, 6),
+            ),
+            (
+                &controls_opts,
+                b"{ a b {",
+                QEntitiesParserLocation::new(6, 1, 7),
+            ),
+            (
+                &separator_opts,
+                b"{ a={",
+                QEntitiesParserLocation::new(4, 1, 5),
+            ),
+            (
+                &separator_opts,
+                b"{ a = {",
+                QEntitiesParserLocation::new(6, 1, 7),
+            ),
         ];
+        for (opts, src, location) in cases {
+            let e = opts.parse(src).unwrap_err();
+            assert_eq!(
+                e.kind(),
+                QEntitiesParseErrorKind::UnexpectedToken,
+                "{src:?}"
+            );
+            assert_eq!(e.location(), Some(&location), "{src:?}");
+        }
 
-        let entities = QEntitiesParseOptions::vtmb().parse(&data[..]).unwrap();
+        // The key following an unquoted value is located exactly.
+        let (_, warnings) = parse_opts
+            .clone()
+            .with_collect_warnings(true)
+            .parse_with_warnings(&b"{ a 1 a 2\ta 3\n a 4 }"[..])
+            .unwrap();
+        let locations: Vec<_> = warnings.iter().map(|w| *w.location()).collect();
         assert_eq!(
-            entities.len(),
-            expected_entities.len(),
-            "entities length mismatch",
+            locations,
+            [
+                QEntitiesParserLocation::new(6, 1, 7),
+                QEntitiesParserLocation::new(10, 1, 11),
+                QEntitiesParserLocation::new(15, 2, 2),
+            ]
         );
+    }
 
-        for (entity_index, (entity, expected_entity)) in entities
-            .iter()
-            .zip(expected_entities.iter().copied())
-            .enumerate()
-        {
+    #[test]
+    fn lowercase_keys() {
+        let src = b"{ Classname light classname Light \"ORIGIN\" \"0 0 0\" } { CLASSNAME light }";
+        let parse_opts = QEntitiesParseOptions::new().with_lowercase_keys(true);
+        let entities = parse_opts.parse(&src[..]).unwrap();
+
+        // Keys differing only in case are interned as a single chunk, while values are untouched.
+        let pairs: Vec<_> = entities.iter_pairs().collect();
+        assert_eq!(
+            pairs,
+            [
+                (0, &b"classname"[..], &b"light"[..]),
+                (0, b"classname", b"Light"),
+                (0, b"origin", b"0 0 0"),
+                (1, b"classname", b"light"),
+            ]
+        );
+        assert_eq!(entities.chunk_count(), 5);
+        let first = entities.key_value(0).unwrap();
+        let last = entities.key_value(3).unwrap();
+        assert!(first.shares_storage_with(&last));
+
+        // Without the option keys keep their case.
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        assert_eq!(entities.get(0).unwrap().get(0).unwrap().key(), b"Classname");
+        assert_eq!(entities.chunk_count(), 7);
+
+        // Keys differing only in case are duplicates.
+        let e = parse_opts
+            .clone()
+            .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Error)
+            .parse(&src[..])
+            .unwrap_err();
+        assert_eq!(e.kind(), QEntitiesParseErrorKind::DuplicateKey);
+        assert_eq!(e.location(), Some(&QEntitiesParserLocation::new(18, 1, 19)));
+
+        let entities = parse_opts
+            .clone()
+            .with_duplicate_key_policy(QEntitiesDuplicateKeyPolicy::Replace)
+            .parse(&src[..])
+            .unwrap();
+        assert_eq!(
+            entities.get(0).unwrap().value_of(b"classname"),
+            Some(&b"Light"[..])
+        );
+        assert_eq!(entities.get(0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn options_equality() {
+        use std::collections::hash_map::RandomState;
+        use std::hash::BuildHasher;
+
+        let all_escapes = QEntitiesParseEscapeOptions::new()
+            .with_double_quotes(true)
+            .with_braces(true);
+        let built = QEntitiesParseOptions::quake()
+            .with_c_style_comments(true)
+            .with_escape_options(Some(all_escapes.clone()))
+            .with_max_key_length(Some(32))
+            .with_retain_comments(true);
+
+        // The same options built independently and in a different order.
+        let escapes = QEntitiesParseEscapeOptions::new()
+            .with_braces(true)
+            .with_single_quotes(true)
+            .with_double_quotes(true)
+            .with_single_quotes(false);
+        let rebuilt = QEntitiesParseOptions::new()
+            .with_retain_comments(true)
+            .with_max_key_length(Some(32))
+            .with_escape_options(Some(escapes.clone()))
+            .with_c_style_comments(true)
+            .with_cpp_style_comments(true);
+
+        let state = RandomState::new();
+        assert_eq!(escapes, all_escapes);
+        assert_eq!(state.hash_one(&escapes), state.hash_one(&all_escapes));
+        assert_eq!(rebuilt, built);
+        assert_eq!(state.hash_one(&rebuilt), state.hash_one(&built));
+        assert_eq!(rebuilt.get_escape_options(), Some(all_escapes));
+
+        // Options can be used as the keys of a cache.
+        let mut cache = HashMap::new();
+        cache.insert(built.clone(), 1);
+        assert_eq!(cache.get(&rebuilt), Some(&1));
+
+        // Any differing option makes the options unequal.
+        let differing = [
+            built.clone().with_retain_comments(false),
+            built.clone().with_max_key_length(Some(33)),
+            built.clone().with_max_value_length(Some(32)),
+            built.clone().with_escape_options(None),
+            built.clone().with_read_buffer_size(64),
+            built
+                .clone()
+                .with_start_location(QEntitiesParserLocation::new(1, 1, 2)),
+            built.clone().with_key_value_separator(Some(b'=')),
+        ];
+        for options in &differing {
+            assert_ne!(options, &built);
+            assert!(!cache.contains_key(options));
+        }
+        assert_ne!(
+            QEntitiesParseEscapeOptions::new(),
+            QEntitiesParseEscapeOptions::new().with_whitespace(true)
+        );
+    }
+
+    #[test]
+    fn dyn_readers() {
+        let src = b"{ classname worldspawn }\n{ classname light }";
+        let parse_opts = QEntitiesParseOptions::new()
+            .with_read_buffer_size(4)
+            .with_record_line_index(true);
+
+        let mut readers: Vec<Box<dyn io::Read>> = vec![
+            Box::new(&src[..]),
+            Box::new(io::Cursor::new(src.to_vec())),
+            Box::new(io::BufReader::with_capacity(3, &src[..])),
+            Box::new(io::Read::chain(&src[..10], &src[10..])),
+        ];
+        for reader in &mut readers {
+            let reader: &mut dyn io::Read = &mut **reader;
+            let entities = parse_opts.parse(reader).unwrap();
+            assert_eq!(entities.len(), 2);
             assert_eq!(
-                entity.len(),
-                expected_entity.len(),
-                "entity #{entity_index} length mismatch",
+                entities.get(1).unwrap().value_of(b"classname"),
+                Some(&b"light"[..])
             );
+            assert_eq!(entities.line_index(), Some(&[0, 25][..]));
+        }
 
-            for (kv_index, ((k, v), (expected_k, expected_v))) in entity
-                .iter()
-                .map(|kv| (kv.key(), kv.value()))
-                .zip(expected_entity.iter().copied())
-                .enumerate()
-            {
-                assert_eq!(
-                    k,
-                    expected_k,
-                    "key #{kv_index} in entity #{entity_index} mismatch: {:?} != {:?}",
-                    String::from_utf8_lossy(k),
-                    String::from_utf8_lossy(expected_k),
-                );
-                assert_eq!(
-                    v,
-                    expected_v,
-                    "value #{kv_index} in entity #{entity_index} mismatch: {:?} != {:?}",
-                    String::from_utf8_lossy(v),
-                    String::from_utf8_lossy(expected_v),
-                );
+        // The same reader can be parsed again, after which it has nothing left to parse.
+        let mut reader: Box<dyn io::Read> = Box::new(&src[..]);
+        assert_eq!(parse_opts.parse(&mut *reader).unwrap().len(), 2);
+        assert_eq!(parse_opts.parse(&mut *reader).unwrap().len(), 0);
+
+        // Every means of parsing a reader accepts one that is dynamically dispatched.
+        let mut reader: Box<dyn io::Read> = Box::new(&b"{ a 1 a 2 }\0{ b 3 }"[..]);
+        let documents = parse_opts
+            .parse_multi(&mut *reader, b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+
+        let mut reader: Box<dyn io::Read> = Box::new(&b"{ a 1 a 2 }"[..]);
+        let (_, warnings) = parse_opts
+            .clone()
+            .with_collect_warnings(true)
+            .parse_with_warnings(&mut *reader)
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        let mut arena = QEntitiesArena::new();
+        let mut reader: Box<dyn io::Read> = Box::new(&src[..]);
+        let entities = parse_opts.parse_in_arena(&mut *reader, &mut arena).unwrap();
+        assert_eq!(entities.len(), 2);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "32")]
+    #[ignore = "allocates most of the address space"]
+    fn parse_exceeding_address_space() {
+        /// A source of an endless sequence of entities, each holding a long value.
+        struct EndlessSource {
+            position: usize,
+        }
+
+        impl QEntitiesByteSource for EndlessSource {
+            fn next_byte(&mut self) -> io::Result<Option<u8>> {
+                const ENTITY: &[u8] = b"{ k \"";
+                const VALUE_LENGTH: usize = 1 << 16;
+                let position = self.position % (ENTITY.len() + VALUE_LENGTH + 3);
+                self.position += 1;
+                Ok(Some(match position.checked_sub(ENTITY.len()) {
+                    None => ENTITY[position],
+                    Some(offset) if offset < VALUE_LENGTH => b'a',
+                    Some(offset) => b"\" }"[offset - VALUE_LENGTH],
+                }))
             }
         }
+
+        // Without interning, the bytes of the values exceed the maximum size of an allocation long
+        // before the source ends, which is reported rather than overflowing or aborting.
+        let e = QEntitiesParseOptions::new()
+            .with_intern(false)
+            .parse_source(EndlessSource { position: 0 })
+            .unwrap_err();
+        let e = io::Error::try_from(e).unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::OutOfMemory);
     }
 }
@@ -0,0 +1,294 @@
+//! Module containing the implementation for validating the keys of the entities within a
+//! [`QEntities`] collection against a schema.
+
+use super::QEntities;
+use bstr::BStr;
+use core::fmt;
+use hashbrown::{HashMap, HashSet};
+
+/// The keys of the entities of a single classname within a [`QEntitiesSchema`].
+#[derive(Debug, Clone, Default)]
+struct QEntitiesClassSchema {
+    /// The keys that must be present, in the order that they were required.
+    required: Vec<Vec<u8>>,
+    /// The keys that may be present, including the required keys.
+    allowed: HashSet<Vec<u8>>,
+}
+
+/// Schema describing the keys that the entities of each classname must and may have, against which
+/// a collection can be checked with [`QEntities::validate()`].
+///
+/// The schema only describes the classnames for which keys have been
+/// [required](Self::require) or [allowed](Self::allow). The keys of an entity of such a classname
+/// must include every required key, and must each be either required, allowed, or `classname`
+/// itself. Entities of other classnames, and entities without a `classname`, are never in violation
+/// of the schema.
+///
+/// The classname of an entity is determined by
+/// [`QEntityRef::value_of()`](crate::QEntityRef::value_of).
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::parse::QEntitiesParseOptions;
+/// use qentities::schema::{QEntitiesSchema, QEntitiesSchemaViolationKind};
+///
+/// let src = b"{ classname worldspawn wad base.wad }
+///             { classname light light 300 }
+///             { classname light origin \"0 0 0\" style 1 }";
+///
+/// let schema = QEntitiesSchema::new()
+///     .with_require(b"light", b"origin")
+///     .with_allow(b"light", b"light");
+///
+/// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+/// let violations = entities.validate(&schema);
+/// assert_eq!(violations.len(), 2);
+///
+/// assert_eq!(violations[0].entity_index(), 1);
+/// assert_eq!(violations[0].kind(), QEntitiesSchemaViolationKind::MissingRequired);
+/// assert_eq!(violations[0].key(), b"origin");
+///
+/// assert_eq!(violations[1].entity_index(), 2);
+/// assert_eq!(violations[1].kind(), QEntitiesSchemaViolationKind::UnknownKey);
+/// assert_eq!(violations[1].key(), b"style");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QEntitiesSchema {
+    /// Map from classnames to the keys of the entities of that classname.
+    classes: HashMap<Vec<u8>, QEntitiesClassSchema>,
+}
+
+impl QEntitiesSchema {
+    /// Creates a new schema that describes no classnames.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires the entities of a classname to have a key.
+    ///
+    /// A required key is also allowed.
+    pub fn require(&mut self, classname: &[u8], key: &[u8]) -> &mut Self {
+        let class = self.classes.entry_ref(classname).or_default();
+        if !class.required.iter().any(|required| required == key) {
+            class.required.push(key.to_vec());
+            class.allowed.insert(key.to_vec());
+        }
+        self
+    }
+
+    /// Same as [`require()`](Self::require) but takes `self` by value.
+    #[inline]
+    pub fn with_require(mut self, classname: &[u8], key: &[u8]) -> Self {
+        self.require(classname, key);
+        self
+    }
+
+    /// Allows the entities of a classname to have a key.
+    pub fn allow(&mut self, classname: &[u8], key: &[u8]) -> &mut Self {
+        let class = self.classes.entry_ref(classname).or_default();
+        class.allowed.insert(key.to_vec());
+        self
+    }
+
+    /// Same as [`allow()`](Self::allow) but takes `self` by value.
+    #[inline]
+    pub fn with_allow(mut self, classname: &[u8], key: &[u8]) -> Self {
+        self.allow(classname, key);
+        self
+    }
+
+    /// Checks the keys of the entities of a collection against the schema.
+    pub(super) fn validate<'a>(
+        &'a self,
+        entities: &'a QEntities,
+    ) -> Vec<QEntitiesSchemaViolation<'a>> {
+        let mut violations = Vec::new();
+        let mut unknown_keys = HashSet::new();
+        for (entity_index, entity) in entities.iter().enumerate() {
+            let Some(classname) = entity.value_of(b"classname") else {
+                continue;
+            };
+            let Some(class) = self.classes.get(classname) else {
+                continue;
+            };
+
+            let violation = |kind, key| QEntitiesSchemaViolation {
+                entity_index,
+                classname,
+                key,
+                kind,
+            };
+
+            // Each unknown key is only reported once per entity.
+            unknown_keys.clear();
+            for key in entity.iter().map(|kv| kv.key()) {
+                if key != b"classname" && !class.allowed.contains(key) && unknown_keys.insert(key) {
+                    violations.push(violation(QEntitiesSchemaViolationKind::UnknownKey, key));
+                }
+            }
+
+            for key in &class.required {
+                if entity.value_of(key).is_none() {
+                    violations.push(violation(
+                        QEntitiesSchemaViolationKind::MissingRequired,
+                        key,
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// The kinds of [`QEntitiesSchemaViolation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QEntitiesSchemaViolationKind {
+    /// An entity did not have a key that is required for its classname.
+    MissingRequired,
+    /// An entity had a key that is neither required nor allowed for its classname.
+    UnknownKey,
+}
+
+/// A violation of a [`QEntitiesSchema`] by an entity, as reported by [`QEntities::validate()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QEntitiesSchemaViolation<'a> {
+    /// The index of the entity within the collection.
+    entity_index: usize,
+    /// The classname of the entity.
+    classname: &'a [u8],
+    /// The key that is either missing or unknown.
+    key: &'a [u8],
+    /// The kind of violation.
+    kind: QEntitiesSchemaViolationKind,
+}
+
+impl<'a> QEntitiesSchemaViolation<'a> {
+    /// Gets the index of the entity within the collection.
+    #[inline]
+    pub fn entity_index(&self) -> usize {
+        self.entity_index
+    }
+
+    /// Gets the classname of the entity.
+    #[inline]
+    pub fn classname(&self) -> &'a [u8] {
+        self.classname
+    }
+
+    /// Gets the key that the entity is either missing or should not have.
+    #[inline]
+    pub fn key(&self) -> &'a [u8] {
+        self.key
+    }
+
+    /// Gets the kind of violation.
+    #[inline]
+    pub fn kind(&self) -> QEntitiesSchemaViolationKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for QEntitiesSchemaViolation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (entity_index, classname, key) = (
+            self.entity_index,
+            BStr::new(self.classname),
+            BStr::new(self.key),
+        );
+        match self.kind {
+            QEntitiesSchemaViolationKind::MissingRequired => {
+                write!(
+                    f,
+                    "{classname} entity #{entity_index} is missing required key {key:?}"
+                )
+            }
+            QEntitiesSchemaViolationKind::UnknownKey => {
+                write!(
+                    f,
+                    "{classname} entity #{entity_index} has unknown key {key:?}"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QEntitiesSchema, QEntitiesSchemaViolationKind};
+    use crate::parse::QEntitiesParseOptions;
+
+    #[test]
+    fn validate_schema() {
+        let src = br#"
+            { classname worldspawn message "anything goes" }
+            { classname light origin "0 0 0" light 300 }
+            { classname light light 200 _color "1 0 0" _color "0 1 0" spawnflags 1 }
+            { classname info_player_start angle 90 }
+            { origin "0 0 0" }
+            { classname func_door }
+        "#;
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        let mut schema = QEntitiesSchema::new();
+        schema
+            .require(b"light", b"origin")
+            .allow(b"light", b"light")
+            .allow(b"light", b"spawnflags")
+            .require(b"info_player_start", b"origin")
+            .require(b"info_player_start", b"angle")
+            .require(b"info_player_start", b"origin")
+            .require(b"func_door", b"model")
+            .allow(b"func_door", b"model");
+
+        let violations: Vec<_> = entities
+            .validate(&schema)
+            .into_iter()
+            .map(|v| (v.entity_index(), v.classname(), v.key(), v.kind()))
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                (
+                    2,
+                    &b"light"[..],
+                    &b"_color"[..],
+                    QEntitiesSchemaViolationKind::UnknownKey
+                ),
+                (
+                    2,
+                    b"light",
+                    b"origin",
+                    QEntitiesSchemaViolationKind::MissingRequired
+                ),
+                (
+                    3,
+                    b"info_player_start",
+                    b"origin",
+                    QEntitiesSchemaViolationKind::MissingRequired
+                ),
+                (
+                    5,
+                    b"func_door",
+                    b"model",
+                    QEntitiesSchemaViolationKind::MissingRequired
+                ),
+            ]
+        );
+
+        let violations = entities.validate(&schema);
+        assert_eq!(
+            violations[0].to_string(),
+            "light entity #2 has unknown key \"_color\""
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "light entity #2 is missing required key \"origin\""
+        );
+
+        // An empty schema describes no classnames.
+        assert!(entities.validate(&QEntitiesSchema::new()).is_empty());
+    }
+}
@@ -0,0 +1,418 @@
+//! Module containing the types for writing q-entities files.
+
+use super::parse::QEntitiesVisitor;
+use super::{QEntities, QEntityKeyValueRef, QEntityRef};
+use bitflags::bitflags;
+use std::io;
+
+bitflags! {
+    /// Bit-flags describing the options for writing a q-entities file.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct QEntitiesWriteFlags: u8 {
+        /// Whether or not the key-values of each entity are sorted.
+        const SORT_KEYS = 0x01;
+    }
+}
+
+/// Options that describe how a q-entities file is written.
+///
+/// Each entity is written as a `{ ... }` block with one quoted key-value pair per line. Keys and
+/// values are written byte-for-byte, so a written collection can be parsed back using the baseline
+/// grammar of [`QEntitiesParseOptions::new()`]. The baseline grammar has no means of representing
+/// a double-quote (`"`) within a quoted string, so writing a key or value that contains one
+/// results in an [`InvalidInput`](io::ErrorKind::InvalidInput) error. Every key and value is
+/// checked before anything is written, such that a rejected collection or entity is never written
+/// in part.
+///
+/// [`QEntitiesParseOptions::new()`]: crate::parse::QEntitiesParseOptions::new
+#[derive(Debug, Clone)]
+pub struct QEntitiesWriteOptions {
+    /// Bit-flag options.
+    flags: QEntitiesWriteFlags,
+}
+
+impl QEntitiesWriteOptions {
+    /// Creates a new write options instance that writes entities and their key-values in the order
+    /// they appear in the collection.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            flags: QEntitiesWriteFlags::empty(),
+        }
+    }
+
+    /// Changes whether or not the key-values of each entity are sorted before being written.
+    ///
+    /// Key-values are sorted by their key bytes and then by their value bytes, where the relative
+    /// order of equal key-values is preserved. The written collection itself is not modified.
+    ///
+    /// This intentionally does not preserve the order of the key-values within the collection, and
+    /// is intended for producing canonical output that is suitable for reproducible builds and
+    /// diffing.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use qentities::write::QEntitiesWriteOptions;
+    ///
+    /// let src = b"{ origin \"0 0 0\" classname light light 300 }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    ///
+    /// let mut dst = Vec::new();
+    /// QEntitiesWriteOptions::new()
+    ///     .sort_keys(true)
+    ///     .write(&entities, &mut dst)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     dst,
+    ///     b"{\n\"classname\" \"light\"\n\"light\" \"300\"\n\"origin\" \"0 0 0\"\n}\n",
+    /// );
+    /// ```
+    #[inline]
+    pub fn sort_keys(&mut self, value: bool) -> &mut Self {
+        self.flags.set(QEntitiesWriteFlags::SORT_KEYS, value);
+        self
+    }
+
+    /// Same as [`sort_keys()`](Self::sort_keys) but takes `self` by value.
+    #[inline]
+    pub fn with_sort_keys(mut self, value: bool) -> Self {
+        self.sort_keys(value);
+        self
+    }
+
+    /// Creates a visitor that writes the entities and key-values it visits to a writer as a
+    /// q-entities file, using these options.
+    ///
+    /// See [`QEntitiesWriter`].
+    #[inline]
+    pub fn writer<W: io::Write>(&self, writer: W) -> QEntitiesWriter<W> {
+        QEntitiesWriter {
+            writer,
+            options: self.clone(),
+            kvs: Vec::new(),
+        }
+    }
+
+    /// Write a collection to a writer as a q-entities file.
+    ///
+    /// # Examples
+    /// Basic usage:
+    /// ```
+    /// use qentities::parse::QEntitiesParseOptions;
+    /// use qentities::write::QEntitiesWriteOptions;
+    ///
+    /// let src = b"{ classname worldspawn }";
+    /// let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+    ///
+    /// let mut dst = Vec::new();
+    /// QEntitiesWriteOptions::new().write(&entities, &mut dst).unwrap();
+    /// assert_eq!(dst, b"{\n\"classname\" \"worldspawn\"\n}\n");
+    /// ```
+    pub fn write<W: io::Write>(&self, entities: &QEntities, mut writer: W) -> io::Result<()> {
+        check_key_values(entities.iter_pairs().map(|(_, key, value)| (key, value)))?;
+
+        // Scratch buffer which is used to sort the key-values of each entity.
+        let mut sorted_kvs = Vec::new();
+
+        for entity in entities.iter() {
+            self.write_checked_entity(entity, &mut writer, &mut sorted_kvs)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a single entity to a writer as a `{ ... }` block, using the given scratch buffer to
+    /// sort its key-values if they are being sorted.
+    pub(crate) fn write_entity<'a, W: io::Write>(
+        &self,
+        entity: QEntityRef<'a>,
+        writer: &mut W,
+        sorted_kvs: &mut Vec<QEntityKeyValueRef<'a>>,
+    ) -> io::Result<()> {
+        check_key_values(entity.pairs())?;
+        self.write_checked_entity(entity, writer, sorted_kvs)
+    }
+
+    /// Same as [`write_entity()`](Self::write_entity) but for an entity whose key-values have
+    /// already been checked.
+    fn write_checked_entity<'a, W: io::Write>(
+        &self,
+        entity: QEntityRef<'a>,
+        writer: &mut W,
+        sorted_kvs: &mut Vec<QEntityKeyValueRef<'a>>,
+    ) -> io::Result<()> {
+        writer.write_all(b"{\n")?;
+        if self.flags.contains(QEntitiesWriteFlags::SORT_KEYS) {
+            sorted_kvs.clear();
+            sorted_kvs.extend(entity.iter());
+            sorted_kvs.sort();
+            for kv in sorted_kvs.iter() {
+                write_key_value(writer, kv.key(), kv.value())?;
+            }
+        } else {
+            for kv in entity.iter() {
+                write_key_value(writer, kv.key(), kv.value())?;
+            }
+        }
+        writer.write_all(b"}\n")
+    }
+}
+
+impl Default for QEntitiesWriteOptions {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A visitor that writes the entities and key-values it visits to a writer as a q-entities file.
+///
+/// This can be passed to [`QEntitiesParseOptions::parse_visitor()`] to reformat a q-entities file
+/// without constructing an intermediate collection. The written output is the same as that of
+/// [`QEntitiesWriteOptions::write()`], except that each entity is written once it ends, so that a
+/// rejected entity is never written in part.
+///
+/// # Examples
+/// Basic usage:
+/// ```
+/// use qentities::parse::QEntitiesParseOptions;
+/// use qentities::write::{QEntitiesWriteOptions, QEntitiesWriter};
+///
+/// let src = b"// comment\n{ classname worldspawn }\n{ origin \"0 0 0\" classname light }";
+///
+/// let mut writer = QEntitiesWriter::new(Vec::new());
+/// QEntitiesParseOptions::quake()
+///     .parse_visitor(&src[..], &mut writer)
+///     .unwrap();
+/// assert_eq!(
+///     writer.into_inner(),
+///     b"{\n\"classname\" \"worldspawn\"\n}\n{\n\"origin\" \"0 0 0\"\n\"classname\" \"light\"\n}\n",
+/// );
+///
+/// let mut writer = QEntitiesWriteOptions::new()
+///     .with_sort_keys(true)
+///     .writer(Vec::new());
+/// QEntitiesParseOptions::quake()
+///     .parse_visitor(&src[..], &mut writer)
+///     .unwrap();
+/// assert_eq!(
+///     writer.into_inner(),
+///     b"{\n\"classname\" \"worldspawn\"\n}\n{\n\"classname\" \"light\"\n\"origin\" \"0 0 0\"\n}\n",
+/// );
+/// ```
+///
+/// [`QEntitiesParseOptions::parse_visitor()`]: crate::parse::QEntitiesParseOptions::parse_visitor
+pub struct QEntitiesWriter<W: io::Write> {
+    /// The inner writer to which bytes are written.
+    writer: W,
+    /// Options used for writing.
+    options: QEntitiesWriteOptions,
+    /// The key-values of the current entity.
+    kvs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<W: io::Write> QEntitiesWriter<W> {
+    /// Creates a new writer visitor using the default write options.
+    ///
+    /// See [`QEntitiesWriteOptions::writer()`] for using other write options.
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        QEntitiesWriteOptions::new().writer(writer)
+    }
+
+    /// Gets a reference to the inner writer.
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the inner writer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the writer visitor, returning the inner writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> QEntitiesVisitor for QEntitiesWriter<W> {
+    fn begin_entity(&mut self) -> io::Result<()> {
+        self.kvs.clear();
+        Ok(())
+    }
+
+    fn key_value(&mut self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        check_key_values([(key, value)])?;
+        self.kvs.push((key.to_vec(), value.to_vec()));
+        Ok(())
+    }
+
+    fn end_entity(&mut self) -> io::Result<()> {
+        if self.options.flags.contains(QEntitiesWriteFlags::SORT_KEYS) {
+            self.kvs.sort();
+        }
+        self.writer.write_all(b"{\n")?;
+        for (key, value) in self.kvs.drain(..) {
+            write_key_value(&mut self.writer, &key, &value)?;
+        }
+        self.writer.write_all(b"}\n")
+    }
+}
+
+/// Checks that key-values can be written, returning an
+/// [`InvalidInput`](io::ErrorKind::InvalidInput) error if any key or value contains a double-quote.
+fn check_key_values<'a>(kvs: impl IntoIterator<Item = (&'a [u8], &'a [u8])>) -> io::Result<()> {
+    let mut kvs = kvs.into_iter();
+    if kvs.any(|(key, value)| key.contains(&b'"') || value.contains(&b'"')) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "keys and values containing a double-quote cannot be written",
+        ));
+    }
+    Ok(())
+}
+
+/// Writes a single key-value as a line of quoted strings.
+fn write_key_value<W: io::Write>(writer: &mut W, key: &[u8], value: &[u8]) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    writer.write_all(key)?;
+    writer.write_all(b"\" \"")?;
+    writer.write_all(value)?;
+    writer.write_all(b"\"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::QEntitiesParseOptions;
+
+    #[test]
+    fn write_round_trip() {
+        let src =
+            b"{ classname worldspawn wad \"a.wad;b.wad\" }{}{ classname light origin \"0 0 0\" }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        let mut dst = Vec::new();
+        QEntitiesWriteOptions::new()
+            .write(&entities, &mut dst)
+            .unwrap();
+        let written = QEntitiesParseOptions::new().parse(&dst[..]).unwrap();
+        assert!(entities.iter().eq(written.iter()));
+    }
+
+    #[test]
+    fn write_sorted_keys() {
+        let src = b"{ b 1 a 2 b 0 a 2 }{ z z }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+
+        let mut dst = Vec::new();
+        QEntitiesWriteOptions::new()
+            .with_sort_keys(true)
+            .write(&entities, &mut dst)
+            .unwrap();
+        assert_eq!(
+            dst,
+            b"{\n\"a\" \"2\"\n\"a\" \"2\"\n\"b\" \"0\"\n\"b\" \"1\"\n}\n{\n\"z\" \"z\"\n}\n"
+        );
+
+        // The collection itself retains its order.
+        let entity = entities.get(0).unwrap();
+        let keys = entity.iter().map(|kv| kv.key()).collect::<Vec<_>>();
+        assert_eq!(keys, [&b"b"[..], b"a", b"b", b"a"]);
+    }
+
+    #[test]
+    fn write_entity() {
+        let src = b"{ classname worldspawn }{ classname light origin \"0 0 0\" light 300 }";
+        let entities = QEntitiesParseOptions::new().parse(&src[..]).unwrap();
+        let entity = entities.get(1).unwrap();
+
+        for write_opts in [
+            QEntitiesWriteOptions::new(),
+            QEntitiesWriteOptions::new().with_sort_keys(true),
+        ] {
+            let mut dst = Vec::new();
+            entity.write_to(&mut dst, &write_opts).unwrap();
+
+            let written = QEntitiesParseOptions::new().parse(&dst[..]).unwrap();
+            assert_eq!(written.len(), 1);
+            let written_entity = written.get(0).unwrap();
+            assert_eq!(written_entity.to_multimap(), entity.to_multimap(),);
+        }
+
+        let mut dst = Vec::new();
+        entity
+            .write_to(&mut dst, &QEntitiesWriteOptions::new().with_sort_keys(true))
+            .unwrap();
+        assert_eq!(
+            dst,
+            b"{\n\"classname\" \"light\"\n\"light\" \"300\"\n\"origin\" \"0 0 0\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn writer_visitor() {
+        let src = b"// comment\n{ classname worldspawn wad a.wad }{}{ b 1 a 2 b 0 a 2 }";
+        let entities = QEntitiesParseOptions::quake().parse(&src[..]).unwrap();
+
+        for write_opts in [
+            QEntitiesWriteOptions::new(),
+            QEntitiesWriteOptions::new().with_sort_keys(true),
+        ] {
+            let mut written = Vec::new();
+            write_opts.write(&entities, &mut written).unwrap();
+
+            let mut writer = write_opts.writer(Vec::new());
+            QEntitiesParseOptions::quake()
+                .parse_visitor(&src[..], &mut writer)
+                .unwrap();
+            assert_eq!(writer.into_inner(), written);
+        }
+
+        // Errors from the inner writer stop parsing.
+        let mut dst = [0; 8];
+        let mut writer = QEntitiesWriter::new(&mut dst[..]);
+        let e = QEntitiesParseOptions::quake()
+            .parse_visitor(&src[..], &mut writer)
+            .unwrap_err();
+        assert!(e.is_io());
+    }
+
+    #[test]
+    fn write_rejects_double_quotes() {
+        let entities = QEntitiesParseOptions::new()
+            .with_single_quoted_strings(true)
+            .parse(&b"{ classname worldspawn }{ classname light message 'say \"hi\"' }"[..])
+            .unwrap();
+
+        let mut dst = Vec::new();
+        let e = QEntitiesWriteOptions::new()
+            .write(&entities, &mut dst)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        // Nothing is written, not even the entities preceding the rejected one.
+        assert!(dst.is_empty());
+
+        let entity = entities.get(1).unwrap();
+        let e = entity
+            .write_to(&mut dst, &QEntitiesWriteOptions::new())
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(dst.is_empty());
+
+        // The writer visitor writes the entities preceding the rejected one, but none of it.
+        let mut writer = QEntitiesWriter::new(Vec::new());
+        let e = QEntitiesParseOptions::new()
+            .with_single_quoted_strings(true)
+            .parse_visitor(&b"{ a b }{ c d 'e\"' f }"[..], &mut writer)
+            .unwrap_err();
+        assert!(e.is_io());
+        assert_eq!(writer.into_inner(), b"{\n\"a\" \"b\"\n}\n");
+    }
+}